@@ -59,6 +59,34 @@ CREATE TABLE IF NOT EXISTS textbook_dict
     created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
     UNIQUE (textbook_id, type_code, item_value)                     -- 确保同一类型下 value 唯一
 );
+-- 字典沿教材树向下继承, 子节点同名字典项覆盖祖先节点, is_hidden 为 true 时屏蔽继承下来的同名字典项
+ALTER TABLE textbook_dict
+    ADD COLUMN IF NOT EXISTS is_hidden BOOLEAN DEFAULT FALSE;
+
+-- 1.4. 知识点前置关系, 知识点节点之间的有向图, 不允许成环
+CREATE TABLE IF NOT EXISTS knowledge_prerequisite
+(
+    id              SERIAL PRIMARY KEY,
+    knowledge_id    INTEGER NOT NULL REFERENCES textbook (id) ON DELETE CASCADE, -- 知识点节点
+    prerequisite_id INTEGER NOT NULL REFERENCES textbook (id) ON DELETE CASCADE, -- 需要先掌握的知识点节点
+    created_at      TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (knowledge_id, prerequisite_id)
+);
+-- 反向查询解锁的知识点
+CREATE INDEX IF NOT EXISTS idx_prerequisite_id ON knowledge_prerequisite (prerequisite_id);
+
+-- 1.5. 不同版本教材章节之间的对应关系, 关系无方向, 保存时 source_id 为较小的标识
+CREATE TABLE IF NOT EXISTS chapter_mapping
+(
+    id         SERIAL PRIMARY KEY,
+    source_id  INTEGER  NOT NULL REFERENCES textbook (id) ON DELETE CASCADE, -- 章节节点
+    target_id  INTEGER  NOT NULL REFERENCES textbook (id) ON DELETE CASCADE, -- 其它版本的章节节点
+    confidence SMALLINT NOT NULL DEFAULT 100,                                -- 匹配程度 0-100
+    note       VARCHAR(500),                                                 -- 说明
+    created_at TIMESTAMPTZ       DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (source_id, target_id)
+);
+CREATE INDEX IF NOT EXISTS idx_chapter_mapping_target_id ON chapter_mapping (target_id);
 
 -- 2. 题目表
 CREATE TABLE IF NOT EXISTS question
@@ -97,7 +125,7 @@ CREATE TABLE IF NOT EXISTS question
     remark                 TEXT,                                                                      -- 其它备注, 不展示记录一些信息
     remark_ext             TEXT,                                                                      -- 备注
 
-    status                 SMALLINT     NOT NULL DEFAULT 0,                                           -- 0 草稿 1 审核中 2 审核通过 3 拒绝
+    status                 SMALLINT     NOT NULL DEFAULT 0,                                           -- 0 草稿 1 审核中 2 审核通过 3 拒绝 4 已归档
     approve_id             BIGINT                DEFAULT 0,                                           -- 审核人
     reject_reason          TEXT,                                                                      -- 审核拒绝后的反馈意见
     approve_at             TIMESTAMPTZ,                                                               -- 审核时间
@@ -122,6 +150,16 @@ ALTER TABLE question
 -- 增加核心素养字段
 ALTER TABLE question
     ADD COLUMN IF NOT EXISTS question_dimension_ids JSONB DEFAULT '[]'::jsonb;
+-- 审核领取, 领取后其他审核人在锁定时间内不能审核
+ALTER TABLE question
+    ADD COLUMN IF NOT EXISTS review_claim_id BIGINT,     -- 领取人
+    ADD COLUMN IF NOT EXISTS review_claim_at TIMESTAMPTZ; -- 领取时间
+-- 待审核队列按创建时间排序
+CREATE INDEX IF NOT EXISTS idx_status_created_at ON question (status, created_at);
+-- 勘误修订, 修订稿是原题的草稿副本
+ALTER TABLE question
+    ADD COLUMN IF NOT EXISTS revision_of BIGINT NOT NULL DEFAULT 0, -- 修订的原题, 0 表示不是修订稿
+    ADD COLUMN IF NOT EXISTS applied_at TIMESTAMPTZ;                -- 修订稿审核通过后更新到原题的时间
 
 -- 2.1. 变式题
 CREATE TABLE IF NOT EXISTS question_similar
@@ -133,6 +171,40 @@ CREATE TABLE IF NOT EXISTS question_similar
     UNIQUE (question_id, child_id)
 );
 
+-- 2.2. 题目关联的知识点节点, question.knowledge 只是文本描述
+CREATE TABLE IF NOT EXISTS question_knowledge
+(
+    id           BIGSERIAL PRIMARY KEY,
+    question_id  BIGINT  NOT NULL REFERENCES question (id) ON DELETE CASCADE, -- 题目主键
+    knowledge_id INTEGER NOT NULL REFERENCES textbook (id) ON DELETE CASCADE, -- 知识点节点
+    created_at   TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (question_id, knowledge_id)
+);
+-- 通过知识点查询题目
+CREATE INDEX IF NOT EXISTS idx_question_knowledge_id ON question_knowledge (knowledge_id);
+
+-- 2.3. 题目勘误, 使用者反馈的错误
+CREATE TABLE IF NOT EXISTS question_errata
+(
+    id          BIGSERIAL PRIMARY KEY,
+    question_id BIGINT      NOT NULL REFERENCES question (id) ON DELETE CASCADE, -- 题目主键
+    field       VARCHAR(64) NULL,                                              -- 出错的字段, 比如 title options.B answer analysis
+    content     TEXT        NOT NULL,                                          -- 错误描述
+    suggestion  TEXT        NULL,                                              -- 修改建议
+    reporter_id BIGINT      NOT NULL,                                          -- 反馈人
+    status      SMALLINT    NOT NULL DEFAULT 1,                                -- 1 待处理 2 已采纳 3 已驳回 4 已修复
+    reply       TEXT        NULL,                                              -- 处理意见, 反馈人可以看到
+    handler_id  BIGINT      NOT NULL DEFAULT 0,                                -- 处理人
+    handled_at  TIMESTAMPTZ NULL,                                              -- 处理时间
+    revision_id BIGINT      NOT NULL DEFAULT 0,                                -- 采纳后生成的修订稿
+    created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+-- 待处理队列
+CREATE INDEX IF NOT EXISTS idx_question_errata_status ON question_errata (status, created_at);
+-- 反馈人查看自己的反馈
+CREATE INDEX IF NOT EXISTS idx_question_errata_reporter ON question_errata (reporter_id);
+
 -- 3. 任务管理
 CREATE TABLE IF NOT EXISTS task
 (
@@ -151,6 +223,51 @@ CREATE TABLE IF NOT EXISTS task
 );
 -- 查看作者自己的任务
 CREATE INDEX IF NOT EXISTS idx_cate_task ON task (question_cate_id, author_id, task_type);
+-- 通用任务, 任务类型由 kind 区分, 参数和结果使用 JSON 保存, 原有的题目上传字段保留默认值
+ALTER TABLE task
+    ADD COLUMN IF NOT EXISTS kind   VARCHAR(64) NOT NULL DEFAULT 'question-upload', -- 任务类型名称, 对应注册的任务
+    ADD COLUMN IF NOT EXISTS params JSONB       NOT NULL DEFAULT '{}'::jsonb,       -- 任务参数
+    ADD COLUMN IF NOT EXISTS output JSONB       NULL;                               -- 任务结构化结果, result 保存可读的结果
+ALTER TABLE task
+    ALTER COLUMN question_cate_id SET DEFAULT 0,
+    ALTER COLUMN task_type SET DEFAULT 0,
+    ALTER COLUMN url SET DEFAULT '',
+    ALTER COLUMN email SET DEFAULT '',
+    ALTER COLUMN textbook_id SET DEFAULT 0;
+-- 原有的题目上传任务补充参数
+UPDATE task
+SET params = jsonb_build_object('questionCateId', question_cate_id, 'textbookId', textbook_id, 'url', url)
+WHERE kind = 'question-upload' AND params = '{}'::jsonb;
+-- 按类型查询待处理的任务
+CREATE INDEX IF NOT EXISTS idx_task_kind_status ON task (kind, status);
+-- 任务领取, 多个执行进程同时运行时通过行锁领取, 心跳超时的任务重新等待执行
+ALTER TABLE task
+    ADD COLUMN IF NOT EXISTS worker_id    VARCHAR(128) NULL, -- 执行进程标识
+    ADD COLUMN IF NOT EXISTS started_at   TIMESTAMPTZ  NULL, -- 开始执行时间
+    ADD COLUMN IF NOT EXISTS heartbeat_at TIMESTAMPTZ  NULL; -- 最后一次心跳时间
+-- 任务重试和取消, 状态增加 11 已取消
+ALTER TABLE task
+    ADD COLUMN IF NOT EXISTS attempts         INTEGER     NOT NULL DEFAULT 0,     -- 已执行次数
+    ADD COLUMN IF NOT EXISTS run_after        TIMESTAMPTZ NULL,                   -- 重试等待, 在该时间之后才能领取
+    ADD COLUMN IF NOT EXISTS cancel_requested BOOLEAN     NOT NULL DEFAULT FALSE; -- 执行中的任务请求取消, 执行进程心跳时检查
+-- 任务执行进度, 每次领取时重置
+ALTER TABLE task
+    ADD COLUMN IF NOT EXISTS progress_phase VARCHAR(64) NOT NULL DEFAULT '', -- 当前阶段
+    ADD COLUMN IF NOT EXISTS progress_done  INTEGER     NOT NULL DEFAULT 0,  -- 已处理数量
+    ADD COLUMN IF NOT EXISTS progress_total INTEGER     NOT NULL DEFAULT 0;  -- 总数量, 为 0 时表示数量未知
+
+-- 3.1. 任务操作记录, 任务删除后保留
+CREATE TABLE IF NOT EXISTS task_history
+(
+    id          BIGSERIAL PRIMARY KEY,
+    task_id     BIGINT       NOT NULL,           -- 任务主键
+    action      VARCHAR(32)  NOT NULL,           -- 操作 create start success failed canceled recover retry cancel delete
+    operator_id BIGINT       NOT NULL DEFAULT 0, -- 操作人, 0 表示执行进程
+    worker_id   VARCHAR(128) NULL,               -- 执行进程标识
+    message     TEXT         NULL,               -- 操作说明
+    created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_task_history_task_id ON task_history (task_id);
 
 -- 4. 试卷主表
 CREATE TABLE paper
@@ -170,7 +287,7 @@ CREATE TABLE paper
     author_name   VARCHAR(100) NOT NULL,           -- 上传者原始昵称
     count         INTEGER      NOT NULL DEFAULT 0, -- 小题数量
     remark_ext    TEXT,                            -- 备注
-    status        SMALLINT     NOT NULL DEFAULT 0, -- 0 草稿 1 审核中 2 审核通过 3 拒绝
+    status        SMALLINT     NOT NULL DEFAULT 0, -- 0 草稿 1 审核中 2 审核通过 3 拒绝 4 已归档
     approve_id    BIGINT       NOT NULL DEFAULT 0, -- 审核人
     reject_reason TEXT,                            -- 审核拒绝后的反馈意见
     approve_at    TIMESTAMPTZ,                     -- 审核时间
@@ -213,3 +330,22 @@ CREATE TABLE paper_question
     score          INTEGER     NOT NULL DEFAULT 0            -- 题目分数, 不校验
 );
 CREATE INDEX idx_paper_question_group_id ON paper_question (paper_id, group_id);
+
+-- 5. 审核评论, 题目和试卷共用, 回复挂在第一层评论下面
+CREATE TABLE IF NOT EXISTS review_comment
+(
+    id          BIGSERIAL PRIMARY KEY,
+    target_type SMALLINT    NOT NULL,               -- 评论对象 1 题目 2 试卷
+    target_id   BIGINT      NOT NULL,               -- 评论对象主键
+    parent_id   BIGINT      NOT NULL DEFAULT 0,     -- 回复的评论, 0 表示第一层评论
+    anchor      VARCHAR(64) NULL,                   -- 评论的字段, 比如 title options.B answer analysis
+    author_id   BIGINT      NOT NULL,               -- 评论人
+    content     TEXT        NOT NULL,               -- 评论内容
+    is_resolved BOOLEAN     NOT NULL DEFAULT FALSE, -- 是否已解决, 只有第一层评论有效
+    resolved_id BIGINT      NOT NULL DEFAULT 0,     -- 解决人
+    resolved_at TIMESTAMPTZ NULL,                   -- 解决时间
+    created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+-- 查询对象的评论
+CREATE INDEX IF NOT EXISTS idx_review_comment_target ON review_comment (target_type, target_id);
@@ -55,3 +55,75 @@ pub async fn remove(
 ) -> ApiResponse<bool> {
     ApiResponse::response(chapter_knowledge::remove(app_conf, req.into_inner()).await)
 }
+
+// 批量绑定/解绑, 章节和知识点两两组合, 支持一个章节对多个知识点以及反过来
+#[derive(Deserialize)]
+pub struct BatchChapterKnowledgeReq {
+    #[serde(rename(deserialize = "chapterIds"))]
+    pub chapter_ids: Vec<i32>,
+    #[serde(rename(deserialize = "knowledgeIds"))]
+    pub knowledge_ids: Vec<i32>,
+}
+
+#[derive(Serialize)]
+pub struct BatchChapterKnowledgeResp {
+    pub affected: u64,                      // 实际新增或者删除的数量
+    pub skipped: Vec<ChapterKnowledgeResp>, // 已关联题型不能解绑的关系
+}
+
+// 批量绑定, 已存在的关系直接跳过
+#[post("/batch/bind")]
+pub async fn batch_bind(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<BatchChapterKnowledgeReq>,
+) -> ApiResponse<BatchChapterKnowledgeResp> {
+    ApiResponse::response(chapter_knowledge::batch_bind(app_conf, req.into_inner()).await)
+}
+
+// 批量解绑, 不存在的关系直接跳过, 已关联题型的关系保留
+#[post("/batch/unbind")]
+pub async fn batch_unbind(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<BatchChapterKnowledgeReq>,
+) -> ApiResponse<BatchChapterKnowledgeResp> {
+    ApiResponse::response(chapter_knowledge::batch_unbind(app_conf, req.into_inner()).await)
+}
+
+#[derive(Serialize)]
+pub struct CoverageNodeResp {
+    pub id: i32,
+    pub label: String,
+}
+
+#[derive(Serialize)]
+pub struct CoverageBindingResp {
+    pub id: i32,
+    #[serde(rename(serialize = "chapterId"))]
+    pub chapter_id: i32,
+    #[serde(rename(serialize = "chapterLabel"))]
+    pub chapter_label: String,
+    #[serde(rename(serialize = "knowledgeId"))]
+    pub knowledge_id: i32,
+    #[serde(rename(serialize = "knowledgeLabel"))]
+    pub knowledge_label: String,
+    #[serde(rename(serialize = "questionCount"))]
+    pub question_count: i64, // 已发布的题目数量
+}
+
+#[derive(Serialize)]
+pub struct ChapterKnowledgeCoverageResp {
+    #[serde(rename(serialize = "unboundChapters"))]
+    pub unbound_chapters: Vec<CoverageNodeResp>, // 没有绑定任何知识点的章节小节
+    #[serde(rename(serialize = "unboundKnowledge"))]
+    pub unbound_knowledge: Vec<CoverageNodeResp>, // 没有绑定任何章节的知识点小类
+    pub bindings: Vec<CoverageBindingResp>,
+}
+
+// 教材菜单下的绑定覆盖情况
+#[get("/coverage/{root_id}")]
+pub async fn coverage(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> ApiResponse<ChapterKnowledgeCoverageResp> {
+    ApiResponse::response(chapter_knowledge::coverage(app_conf, path.into_inner().0).await)
+}
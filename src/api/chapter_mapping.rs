@@ -0,0 +1,66 @@
+use crate::AppConfig;
+use crate::api::question_cate::QuestionCateResp;
+use crate::service::chapter_mapping;
+use crate::util::response::ApiResponse;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// 不同版本教材章节对应关系
+
+#[derive(Deserialize)]
+pub struct CreateChapterMappingReq {
+    #[serde(rename(deserialize = "chapterId"))]
+    pub chapter_id: i32,
+    #[serde(rename(deserialize = "otherId"))]
+    pub other_id: i32, // 其它版本的章节
+    pub confidence: Option<i16>, // 匹配程度 0-100, 默认 100
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RemoveChapterMappingReq {
+    #[serde(rename(deserialize = "chapterId"))]
+    pub chapter_id: i32,
+    #[serde(rename(deserialize = "otherId"))]
+    pub other_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct ChapterMappingResp {
+    pub id: i32,
+    #[serde(rename(serialize = "chapterId"))]
+    pub chapter_id: i32, // 对应的其它版本章节
+    pub label: String,
+    pub confidence: i16,
+    pub note: Option<String>,
+    #[serde(rename(serialize = "questionCates"))]
+    pub question_cates: Vec<QuestionCateResp>, // 对应章节下的题型列表
+}
+
+// 添加或者更新对应关系
+#[post("/add")]
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<CreateChapterMappingReq>,
+) -> ApiResponse<i32> {
+    ApiResponse::response(chapter_mapping::add(app_conf, req.into_inner()).await)
+}
+
+// 删除对应关系
+#[post("/remove")]
+pub async fn remove(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<RemoveChapterMappingReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(chapter_mapping::remove(app_conf, req.into_inner()).await)
+}
+
+// 章节在其它版本中对应的章节及题型列表, edition_id 为其它版本的菜单标识, 0 表示不限制版本
+#[get("/match/{chapter_id}/{edition_id}")]
+pub async fn matches(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32, i32)>,
+) -> ApiResponse<Vec<ChapterMappingResp>> {
+    let path = path.into_inner();
+    ApiResponse::response(chapter_mapping::matches(app_conf, path.0, path.1).await)
+}
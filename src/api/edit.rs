@@ -12,6 +12,8 @@ pub struct EditStatusReq {
     pub status: i16,
     #[serde(rename(deserialize = "rejectReason"))]
     pub reject_reason: Option<String>,
+    #[serde(rename(deserialize = "reviewerId"))]
+    pub reviewer_id: Option<i64>, // 审核人, 审核通过和拒绝时必填
 }
 
 // 更新状态
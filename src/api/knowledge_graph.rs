@@ -0,0 +1,102 @@
+use crate::AppConfig;
+use crate::service::knowledge_graph;
+use crate::util::response::ApiResponse;
+use actix_web::{Either, HttpResponse, get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// 知识点前置关系图
+
+#[derive(Deserialize)]
+pub struct KnowledgePrerequisiteReq {
+    #[serde(rename(deserialize = "knowledgeId"))]
+    pub knowledge_id: i32, // 知识点
+    #[serde(rename(deserialize = "prerequisiteId"))]
+    pub prerequisite_id: i32, // 需要先掌握的知识点
+}
+
+#[derive(Serialize)]
+pub struct KnowledgeRelationResp {
+    pub id: i32,
+    pub label: String,
+    pub distance: i32, // 相隔层数, 1 为直接关联
+}
+
+#[derive(Serialize)]
+pub struct KnowledgeGraphNode {
+    pub id: i32,
+    pub label: String,
+}
+
+// 边的方向为 前置知识点 -> 知识点
+#[derive(Serialize)]
+pub struct KnowledgeGraphEdge {
+    pub from: i32,
+    pub to: i32,
+}
+
+#[derive(Serialize)]
+pub struct KnowledgeGraphResp {
+    pub nodes: Vec<KnowledgeGraphNode>,
+    pub edges: Vec<KnowledgeGraphEdge>,
+}
+
+// 添加前置关系
+#[post("/add")]
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<KnowledgePrerequisiteReq>,
+) -> ApiResponse<i32> {
+    ApiResponse::response(knowledge_graph::add(app_conf, req.into_inner()).await)
+}
+
+// 删除前置关系
+#[post("/remove")]
+pub async fn remove(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<KnowledgePrerequisiteReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(knowledge_graph::remove(app_conf, req.into_inner()).await)
+}
+
+// 所有直接或间接的前置知识点
+#[get("/prerequisites/{knowledge_id}")]
+pub async fn prerequisites(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> ApiResponse<Vec<KnowledgeRelationResp>> {
+    ApiResponse::response(knowledge_graph::prerequisites(app_conf, path.into_inner().0).await)
+}
+
+// 掌握当前知识点后可以继续学习的知识点
+#[get("/unlocks/{knowledge_id}")]
+pub async fn unlocks(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> ApiResponse<Vec<KnowledgeRelationResp>> {
+    ApiResponse::response(knowledge_graph::unlocks(app_conf, path.into_inner().0).await)
+}
+
+// 导出菜单下所有知识点的关系图 - json
+#[get("/export/{root_id}/json")]
+pub async fn export_json(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> ApiResponse<KnowledgeGraphResp> {
+    ApiResponse::response(knowledge_graph::export(app_conf, path.into_inner().0).await)
+}
+
+// 导出菜单下所有知识点的关系图 - graphviz dot 文本, 失败时返回统一的错误结构
+#[get("/export/{root_id}/dot")]
+pub async fn export_dot(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> Either<HttpResponse, ApiResponse<()>> {
+    match knowledge_graph::export_dot(app_conf, path.into_inner().0).await {
+        Ok(dot) => Either::Left(
+            HttpResponse::Ok()
+                .content_type("text/vnd.graphviz; charset=utf-8")
+                .body(dot),
+        ),
+        Err(e) => Either::Right(ApiResponse::response(Err(e))),
+    }
+}
@@ -1,8 +1,15 @@
 pub mod chapter_knowledge;
+pub mod chapter_mapping;
 pub mod edit;
+pub mod knowledge_graph;
 pub mod other_dict;
 pub mod question;
 pub mod question_cate;
+pub mod question_errata;
+pub mod question_knowledge;
+pub mod question_similar;
+pub mod review;
+pub mod review_comment;
 pub mod textbook;
 pub mod file;
 pub mod task;
@@ -17,6 +17,8 @@ pub struct CreateTextbookDictReq {
     pub sort_order: i32,
     #[serde(rename(deserialize = "isSelect"))]
     pub is_select: bool,
+    #[serde(rename(deserialize = "isHidden"), default)]
+    pub is_hidden: bool, // 屏蔽祖先节点继承下来的同名字典项
 }
 
 #[derive(Serialize)]
@@ -32,6 +34,55 @@ pub struct TextbookDictResp {
     pub sort_order: i32,
     #[serde(rename(serialize = "isSelect"))]
     pub is_select: bool,
+    #[serde(rename(serialize = "isHidden"))]
+    pub is_hidden: bool,
+}
+
+#[derive(Serialize)]
+pub struct EffectiveDictResp {
+    #[serde(flatten)]
+    pub item: TextbookDictResp,
+    #[serde(rename(serialize = "sourceLabel"))]
+    pub source_label: String, // 字典项所在的节点名称
+    pub inherited: bool,  // 是否从祖先节点继承
+    pub overridden: bool, // 是否覆盖了祖先节点的同名字典项
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTextbookDictReq {
+    pub id: i32,
+    #[serde(rename(deserialize = "itemValue"))]
+    pub item_value: String,
+    #[serde(rename(deserialize = "sortOrder"))]
+    pub sort_order: i32,
+    #[serde(rename(deserialize = "isSelect"))]
+    pub is_select: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SortTextbookDictReq {
+    #[serde(rename(deserialize = "textbookId"))]
+    pub textbook_id: i32,
+    #[serde(rename(deserialize = "typeCode"))]
+    pub type_code: String,
+    pub ids: Vec<i32>, // 按新顺序排列的字典项标识
+}
+
+#[derive(Deserialize)]
+pub struct MergeTextbookDictReq {
+    #[serde(rename(deserialize = "sourceId"))]
+    pub source_id: i32, // 被合并的字典项, 合并后删除
+    #[serde(rename(deserialize = "targetId"))]
+    pub target_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct TextbookDictUsageResp {
+    pub id: i32,
+    #[serde(rename(serialize = "itemValue"))]
+    pub item_value: String,
+    #[serde(rename(serialize = "questionCount"))]
+    pub question_count: i64, // 引用该字典项的题目数量
 }
 
 // 字典添加
@@ -58,3 +109,59 @@ pub async fn list(
 pub async fn remove(app_conf: web::Data<AppConfig>, path: web::Path<(i32,)>) -> ApiResponse<bool> {
     ApiResponse::response(textbook_dict::delete(app_conf, path.into_inner().0).await)
 }
+
+// 节点实际生效的字典, 合并祖先节点继承下来的字典项
+#[get("/effective/{textbook_id}")]
+pub async fn effective(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> ApiResponse<Vec<EffectiveDictResp>> {
+    ApiResponse::response(textbook_dict::effective(app_conf, path.into_inner().0, None).await)
+}
+
+// 节点实际生效的某个类型的字典
+#[get("/effective/{textbook_id}/{type_code}")]
+pub async fn effective_by_type(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32, String)>,
+) -> ApiResponse<Vec<EffectiveDictResp>> {
+    let path = path.into_inner();
+    ApiResponse::response(textbook_dict::effective(app_conf, path.0, Some(path.1)).await)
+}
+
+// 字典修改
+#[post("/edit")]
+pub async fn edit(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<UpdateTextbookDictReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(textbook_dict::edit(app_conf, req.into_inner()).await)
+}
+
+// 字典批量排序
+#[post("/sort")]
+pub async fn sort(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<SortTextbookDictReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(textbook_dict::sort(app_conf, req.into_inner()).await)
+}
+
+// 字典项被题目引用的数量
+#[get("/usage/{textbook_id}/{type_code}")]
+pub async fn usage(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32, String)>,
+) -> ApiResponse<Vec<TextbookDictUsageResp>> {
+    let path = path.into_inner();
+    ApiResponse::response(textbook_dict::usage(app_conf, path.0, path.1).await)
+}
+
+// 字典合并 - 题目中的引用改为目标字典项, 然后删除被合并的字典项, 返回修改的题目数量
+#[post("/merge")]
+pub async fn merge(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<MergeTextbookDictReq>,
+) -> ApiResponse<u64> {
+    ApiResponse::response(textbook_dict::merge(app_conf, req.into_inner()).await)
+}
@@ -91,6 +91,8 @@ pub struct PaperResp {
     pub reject_reason: Option<String>, // 拒绝原因
     #[serde(rename(serialize = "approveAt"))]
     pub approve_at: Option<String>, // 审核时间
+    #[serde(rename(serialize = "unresolvedComments"))]
+    pub unresolved_comments: i64, // 未解决的审核评论数量
 
     pub remark: Option<String>,
     pub count: i32,
@@ -107,6 +107,8 @@ pub struct QuestionBaseResp {
     pub reject_reason: Option<String>, // 拒绝原因
     #[serde(rename(serialize = "approveAt"))]
     pub approve_at: Option<String>, // 审核时间
+    #[serde(rename(serialize = "unresolvedComments"))]
+    pub unresolved_comments: i64, // 未解决的审核评论数量
 
     pub steps: Option<Json<Vec<Step>>>, // 解题步骤需要返回
 
@@ -157,6 +159,8 @@ pub struct QuestionListReq {
     pub title_val: Option<String>,
     #[serde(rename(deserialize = "tagIds"))]
     pub tag_ids: Option<Vec<i32>>,
+    #[serde(rename(deserialize = "knowledgeId"))]
+    pub knowledge_id: Option<i32>, // 知识点节点, 包含子孙节点
     #[serde(rename(deserialize = "pageNo"))]
     pub page_no: i32,
     #[serde(rename(deserialize = "pageSize"))]
@@ -221,3 +225,54 @@ pub async fn delete(
 ) -> ApiResponse<bool> {
     ApiResponse::response(question::delete(app_conf, req.into_inner()).await)
 }
+
+#[derive(Deserialize)]
+pub struct CloneQuestionReq {
+    pub id: i64,
+    #[serde(rename(deserialize = "targetCateId"))]
+    pub target_cate_id: Option<i32>, // 为空时复制到原题型
+    #[serde(rename(deserialize = "textbookId"))]
+    pub textbook_id: Option<i32>, // 目标教材节点, 用于匹配字典, 为空时使用目标题型关联的章节
+    #[serde(rename(deserialize = "withVariants"), default)]
+    pub with_variants: bool, // 是否连同所有层级的变式题一起复制
+}
+
+#[derive(Serialize)]
+pub struct CloneQuestionResp {
+    pub id: i64,               // 复制得到的题目主键
+    pub count: usize,          // 复制的题目数量, 包含变式题
+    pub unmapped: Vec<String>, // 目标教材中没有匹配上的字典项
+}
+
+// 复制题目, 复制的题目都是草稿状态
+#[post("/clone")]
+pub async fn clone(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<CloneQuestionReq>,
+) -> ApiResponse<CloneQuestionResp> {
+    ApiResponse::response(question::clone(app_conf, req.into_inner()).await)
+}
+
+#[derive(Deserialize)]
+pub struct MoveQuestionReq {
+    pub ids: Vec<i64>,
+    #[serde(rename(deserialize = "targetCateId"))]
+    pub target_cate_id: i32,
+    #[serde(rename(deserialize = "textbookId"))]
+    pub textbook_id: Option<i32>, // 目标教材节点, 用于匹配字典, 为空时使用目标题型关联的章节
+}
+
+#[derive(Serialize)]
+pub struct MoveQuestionResp {
+    pub moved: u64,
+    pub unmapped: Vec<String>, // 目标教材中没有匹配上的字典项, 保留原来的标识
+}
+
+// 批量移动题目到其它题型, 题目类型和标签按名称匹配目标教材的字典
+#[post("/move")]
+pub async fn move_to(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<MoveQuestionReq>,
+) -> ApiResponse<MoveQuestionResp> {
+    ApiResponse::response(question::move_to(app_conf, req.into_inner()).await)
+}
@@ -25,6 +25,42 @@ pub struct QuestionCateResp {
     pub sort_order: i32,
 }
 
+#[derive(Deserialize)]
+pub struct UpdateQuestionCateReq {
+    pub id: i32,
+    pub label: String,
+}
+
+#[derive(Deserialize)]
+pub struct SortQuestionCateReq {
+    #[serde(rename(deserialize = "relatedId"))]
+    pub related_id: i32,
+    pub ids: Vec<i32>, // 按新顺序排列的题型标识
+}
+
+#[derive(Deserialize)]
+pub struct MoveQuestionCateReq {
+    pub id: i32,
+    #[serde(rename(deserialize = "relatedId"))]
+    pub related_id: i32, // 新的章节和知识点关联标识
+}
+
+#[derive(Deserialize)]
+pub struct MergeQuestionCateReq {
+    #[serde(rename(deserialize = "sourceId"))]
+    pub source_id: i32, // 被合并的题型, 合并后删除
+    #[serde(rename(deserialize = "targetId"))]
+    pub target_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct MergeQuestionCateResp {
+    #[serde(rename(serialize = "questionCount"))]
+    pub question_count: i64, // 需要改挂的题目数量
+    #[serde(rename(serialize = "taskCount"))]
+    pub task_count: i64, // 需要改挂的任务数量
+}
+
 // 添加题型
 #[post("/add")]
 pub async fn add(
@@ -48,3 +84,53 @@ pub async fn list(
 pub async fn remove(app_conf: web::Data<AppConfig>, path: web::Path<(i32,)>) -> ApiResponse<bool> {
     ApiResponse::response(question_cate::remove(app_conf, path.into_inner().0).await)
 }
+
+// 修改题型名称
+#[post("/edit")]
+pub async fn edit(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<UpdateQuestionCateReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_cate::edit(app_conf, req.into_inner()).await)
+}
+
+// 批量排序
+#[post("/sort")]
+pub async fn sort(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<SortQuestionCateReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_cate::sort(app_conf, req.into_inner()).await)
+}
+
+// 移动到其它章节和知识点关联下
+#[post("/move")]
+pub async fn move_to(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<MoveQuestionCateReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_cate::move_to(app_conf, req.into_inner()).await)
+}
+
+// 合并预览 - 返回需要改挂的数据量
+#[get("/merge/preview/{source_id}/{target_id}")]
+pub async fn merge_preview(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32, i32)>,
+) -> ApiResponse<MergeQuestionCateResp> {
+    let path = path.into_inner();
+    let req = MergeQuestionCateReq {
+        source_id: path.0,
+        target_id: path.1,
+    };
+    ApiResponse::response(question_cate::merge_preview(app_conf, req).await)
+}
+
+// 合并题型 - 题目和任务改挂到目标题型, 然后删除被合并的题型
+#[post("/merge")]
+pub async fn merge(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<MergeQuestionCateReq>,
+) -> ApiResponse<MergeQuestionCateResp> {
+    ApiResponse::response(question_cate::merge(app_conf, req.into_inner()).await)
+}
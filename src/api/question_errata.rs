@@ -0,0 +1,128 @@
+use crate::AppConfig;
+use crate::service::question_errata;
+use crate::util::response::ApiResponse;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// 题目勘误, 反馈人和处理人暂时没有登录信息, 由请求传入
+
+#[derive(Deserialize)]
+pub struct CreateErrataReq {
+    #[serde(rename(deserialize = "questionId"))]
+    pub question_id: i64,
+    pub field: Option<String>, // 出错的字段, 比如 title options.B answer analysis
+    pub content: String,       // 错误描述
+    pub suggestion: Option<String>,
+    #[serde(rename(deserialize = "reporterId"))]
+    pub reporter_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ErrataListReq {
+    pub status: Option<i16>, // 为空时查询待处理的
+    #[serde(rename(deserialize = "questionId"))]
+    pub question_id: Option<i64>,
+    #[serde(rename(deserialize = "pageNo"))]
+    pub page_no: i32,
+    #[serde(rename(deserialize = "pageSize"))]
+    pub page_size: i32,
+}
+
+#[derive(Deserialize)]
+pub struct MyErrataListReq {
+    #[serde(rename(deserialize = "reporterId"))]
+    pub reporter_id: i64,
+    pub status: Option<i16>, // 为空时查询全部
+    #[serde(rename(deserialize = "pageNo"))]
+    pub page_no: i32,
+    #[serde(rename(deserialize = "pageSize"))]
+    pub page_size: i32,
+}
+
+#[derive(Deserialize)]
+pub struct HandleErrataReq {
+    pub id: i64,
+    pub status: i16,           // 2 采纳 3 驳回 4 已修复
+    pub reply: Option<String>, // 处理意见, 驳回时必填
+    #[serde(rename(deserialize = "handlerId"))]
+    pub handler_id: i64,
+}
+
+#[derive(Serialize)]
+pub struct ErrataResp {
+    pub id: i64,
+    #[serde(rename(serialize = "questionId"))]
+    pub question_id: i64,
+    pub field: Option<String>,
+    pub content: String,
+    pub suggestion: Option<String>,
+    #[serde(rename(serialize = "reporterId"))]
+    pub reporter_id: i64,
+    pub status: i16,
+    #[serde(rename(serialize = "statusDesc"))]
+    pub status_desc: String,
+    pub reply: Option<String>,
+    #[serde(rename(serialize = "handlerId"))]
+    pub handler_id: i64,
+    #[serde(rename(serialize = "handledAt"))]
+    pub handled_at: Option<String>,
+    #[serde(rename(serialize = "revisionId"))]
+    pub revision_id: i64, // 采纳后生成的修订稿, 0 表示没有
+    #[serde(rename(serialize = "createdAt"))]
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ErrataListResp {
+    pub list: Vec<ErrataResp>,
+    #[serde(rename(serialize = "pageNo"))]
+    pub page_no: i32,
+    #[serde(rename(serialize = "pageSize"))]
+    pub page_size: i32,
+    pub total: i64,
+}
+
+// 提交勘误
+#[post("/add")]
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<CreateErrataReq>,
+) -> ApiResponse<i64> {
+    ApiResponse::response(question_errata::add(app_conf, req.into_inner()).await)
+}
+
+// 处理队列
+#[post("/list")]
+pub async fn list(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<ErrataListReq>,
+) -> ApiResponse<ErrataListResp> {
+    ApiResponse::response(question_errata::list(app_conf, req.into_inner()).await)
+}
+
+// 反馈人查看自己提交的勘误和处理结果
+#[post("/mine")]
+pub async fn mine(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<MyErrataListReq>,
+) -> ApiResponse<ErrataListResp> {
+    ApiResponse::response(question_errata::mine(app_conf, req.into_inner()).await)
+}
+
+// 勘误详情
+#[get("/info/{id}")]
+pub async fn info(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i64,)>,
+) -> ApiResponse<ErrataResp> {
+    ApiResponse::response(question_errata::info(app_conf, path.into_inner().0).await)
+}
+
+// 处理勘误, 采纳后生成修订稿交给作者修改
+#[post("/handle")]
+pub async fn handle(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<HandleErrataReq>,
+) -> ApiResponse<ErrataResp> {
+    ApiResponse::response(question_errata::handle(app_conf, req.into_inner()).await)
+}
@@ -0,0 +1,40 @@
+use crate::AppConfig;
+use crate::service::question_knowledge;
+use crate::util::response::ApiResponse;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// 题目关联的知识点节点
+
+#[derive(Deserialize)]
+pub struct QuestionKnowledgeReq {
+    #[serde(rename(deserialize = "questionId"))]
+    pub question_id: i64,
+    #[serde(rename(deserialize = "knowledgeIds"))]
+    pub knowledge_ids: Vec<i32>, // 覆盖原有的关联, 为空时清空
+}
+
+#[derive(Serialize)]
+pub struct QuestionKnowledgeResp {
+    #[serde(rename(serialize = "knowledgeId"))]
+    pub knowledge_id: i32,
+    pub label: String,
+}
+
+// 保存题目关联的知识点
+#[post("/bind")]
+pub async fn bind(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<QuestionKnowledgeReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_knowledge::bind(app_conf, req.into_inner()).await)
+}
+
+// 题目关联的知识点列表
+#[get("/list/{question_id}")]
+pub async fn list(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i64,)>,
+) -> ApiResponse<Vec<QuestionKnowledgeResp>> {
+    ApiResponse::response(question_knowledge::list(app_conf, path.into_inner().0).await)
+}
@@ -0,0 +1,107 @@
+use crate::AppConfig;
+use crate::service::question_similar;
+use crate::util::response::ApiResponse;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// 变式题关联管理, 变式题可以继续有自己的变式题
+
+#[derive(Deserialize)]
+pub struct QuestionSimilarReq {
+    #[serde(rename(deserialize = "questionId"))]
+    pub question_id: i64, // 父题
+    #[serde(rename(deserialize = "childId"))]
+    pub child_id: i64, // 变式题
+}
+
+#[derive(Deserialize)]
+pub struct PromoteQuestionSimilarReq {
+    pub id: i64,
+}
+
+#[derive(Serialize)]
+pub struct QuestionSimilarTreeResp {
+    pub id: i64,
+    pub title: String,
+    pub status: i16,
+    pub children: Vec<QuestionSimilarTreeResp>,
+}
+
+#[derive(Serialize)]
+pub struct SimilarFeatureResp {
+    pub name: String, // text 文本, latex 公式结构, type 题目类型, knowledge 知识点
+    pub score: f64,   // 单项相似度 0-1
+}
+
+#[derive(Serialize)]
+pub struct SimilarSuggestionResp {
+    pub id: i64,
+    pub title: String,
+    #[serde(rename(serialize = "questionTypeId"))]
+    pub question_type_id: i32,
+    pub score: f64,                        // 综合得分 0-100
+    pub features: Vec<SimilarFeatureResp>, // 匹配上的特征
+}
+
+// 关联变式题, 变式题已经有父题时需要使用改挂
+#[post("/link")]
+pub async fn link(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<QuestionSimilarReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_similar::link(app_conf, req.into_inner()).await)
+}
+
+// 解除关联, 变式题连同它自己的变式题成为独立的母题
+#[post("/unlink")]
+pub async fn unlink(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<QuestionSimilarReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_similar::unlink(app_conf, req.into_inner()).await)
+}
+
+// 改挂到新的父题下, 变式题自己的变式题跟随移动
+#[post("/reparent")]
+pub async fn reparent(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<QuestionSimilarReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_similar::reparent(app_conf, req.into_inner()).await)
+}
+
+// 变式题提升为父题, 原来的父题和其它变式题都挂到它下面
+#[post("/promote")]
+pub async fn promote(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<PromoteQuestionSimilarReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_similar::promote(app_conf, req.into_inner().id).await)
+}
+
+// 题目所在的整棵变式题树, 从根节点母题开始
+#[get("/tree/{id}")]
+pub async fn tree(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i64,)>,
+) -> ApiResponse<QuestionSimilarTreeResp> {
+    ApiResponse::response(question_similar::tree(app_conf, path.into_inner().0).await)
+}
+
+// 推荐变式题, 按内容相似度排序
+#[get("/suggest/{id}")]
+pub async fn suggest(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i64,)>,
+) -> ApiResponse<Vec<SimilarSuggestionResp>> {
+    ApiResponse::response(question_similar::suggest(app_conf, path.into_inner().0).await)
+}
+
+// 采纳推荐的变式题
+#[post("/suggest/accept")]
+pub async fn accept(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<QuestionSimilarReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(question_similar::link(app_conf, req.into_inner()).await)
+}
@@ -0,0 +1,77 @@
+use crate::AppConfig;
+use crate::api::question::QuestionBaseResp;
+use crate::service::review;
+use crate::util::response::ApiResponse;
+use actix_web::{post, web};
+use serde::{Deserialize, Serialize};
+
+/// 题目审核, 审核人暂时没有登录信息, 由请求传入
+
+#[derive(Deserialize)]
+pub struct ReviewQueueReq {
+    #[serde(rename(deserialize = "reviewerId"))]
+    pub reviewer_id: i64,
+    #[serde(rename(deserialize = "textbookId"))]
+    pub textbook_id: Option<i32>, // 审核范围, 教材节点及其子孙节点, 为空时不限制
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewClaimReq {
+    #[serde(rename(deserialize = "reviewerId"))]
+    pub reviewer_id: i64,
+    pub id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct BatchReviewReq {
+    #[serde(rename(deserialize = "reviewerId"))]
+    pub reviewer_id: i64,
+    pub ids: Vec<i64>,
+    pub status: i16, // 2 审核通过 3 拒绝
+    #[serde(rename(deserialize = "rejectReason"))]
+    pub reject_reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReviewResultResp {
+    pub id: i64,
+    pub success: bool,
+    pub message: String,
+}
+
+// 待审核队列, 最早提交的排在前面
+#[post("/queue")]
+pub async fn queue(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<ReviewQueueReq>,
+) -> ApiResponse<Vec<QuestionBaseResp>> {
+    ApiResponse::response(review::queue(app_conf, req.into_inner()).await)
+}
+
+// 领取题目, 锁定时间内其他审核人不能审核
+#[post("/claim")]
+pub async fn claim(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<ReviewClaimReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(review::claim(app_conf, req.into_inner()).await)
+}
+
+// 释放领取的题目
+#[post("/release")]
+pub async fn release(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<ReviewClaimReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(review::release(app_conf, req.into_inner()).await)
+}
+
+// 批量审核通过或者拒绝, 返回每个题目的审核结果
+#[post("/batch")]
+pub async fn batch(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<BatchReviewReq>,
+) -> ApiResponse<Vec<ReviewResultResp>> {
+    ApiResponse::response(review::batch(app_conf, req.into_inner()).await)
+}
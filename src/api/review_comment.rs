@@ -0,0 +1,93 @@
+use crate::AppConfig;
+use crate::service::review_comment;
+use crate::util::response::ApiResponse;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// 题目和试卷的审核评论, 评论人暂时没有登录信息, 由请求传入
+
+#[derive(Deserialize)]
+pub struct CreateReviewCommentReq {
+    #[serde(rename(deserialize = "targetType"))]
+    pub target_type: i16, // 1 题目 2 试卷
+    #[serde(rename(deserialize = "targetId"))]
+    pub target_id: i64,
+    #[serde(rename(deserialize = "parentId"))]
+    pub parent_id: Option<i64>, // 回复的评论, 为空时是第一层评论
+    pub anchor: Option<String>, // 评论的字段, 比如 title options.B answer analysis
+    #[serde(rename(deserialize = "authorId"))]
+    pub author_id: i64,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResolveReviewCommentReq {
+    pub id: i64,
+    pub resolved: bool, // true 解决 false 重新打开
+    #[serde(rename(deserialize = "operatorId"))]
+    pub operator_id: i64,
+}
+
+#[derive(Deserialize)]
+pub struct RemoveReviewCommentReq {
+    pub id: i64,
+    #[serde(rename(deserialize = "operatorId"))]
+    pub operator_id: i64,
+}
+
+#[derive(Serialize)]
+pub struct ReviewCommentResp {
+    pub id: i64,
+    #[serde(rename(serialize = "parentId"))]
+    pub parent_id: i64,
+    pub anchor: Option<String>,
+    #[serde(rename(serialize = "authorId"))]
+    pub author_id: i64,
+    pub content: String,
+    #[serde(rename(serialize = "isResolved"))]
+    pub is_resolved: bool,
+    #[serde(rename(serialize = "resolvedId"))]
+    pub resolved_id: i64,
+    #[serde(rename(serialize = "resolvedAt"))]
+    pub resolved_at: Option<String>,
+    pub replies: Vec<ReviewCommentResp>, // 回复列表, 按时间先后
+    #[serde(rename(serialize = "createdAt"))]
+    pub created_at: String,
+}
+
+// 添加评论或者回复
+#[post("/add")]
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<CreateReviewCommentReq>,
+) -> ApiResponse<i64> {
+    ApiResponse::response(review_comment::add(app_conf, req.into_inner()).await)
+}
+
+// 解决或者重新打开评论
+#[post("/resolve")]
+pub async fn resolve(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<ResolveReviewCommentReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(review_comment::resolve(app_conf, req.into_inner()).await)
+}
+
+// 删除自己的评论, 第一层评论连同回复一起删除
+#[post("/remove")]
+pub async fn remove(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<RemoveReviewCommentReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(review_comment::remove(app_conf, req.into_inner()).await)
+}
+
+// 对象的评论列表
+#[get("/list/{target_type}/{target_id}")]
+pub async fn list(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i16, i64)>,
+) -> ApiResponse<Vec<ReviewCommentResp>> {
+    let (target_type, target_id) = path.into_inner();
+    ApiResponse::response(review_comment::list(app_conf, target_type, target_id).await)
+}
@@ -1,8 +1,10 @@
 use crate::AppConfig;
 use crate::service::task;
 use crate::util::response::ApiResponse;
-use actix_web::{post, web};
+use actix_web::http::header;
+use actix_web::{HttpResponse, get, post, web};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// 任务管理
 
@@ -17,6 +19,10 @@ pub struct TaskAddReq {
     pub email: String,
     #[serde(rename(deserialize = "textbookId"))]
     pub textbook_id: i32,
+    pub kind: Option<String>,  // 任务类型名称, 为空时为题目上传
+    pub params: Option<Value>, // 任务参数, 为空时题目上传使用上面的字段
+    #[serde(default)]
+    pub partial: bool, // 题目上传跳过有问题的题目, 提交其余题目
 }
 
 // 创建任务
@@ -44,6 +50,7 @@ pub struct TaskInfoResp {
     pub question_cate_id: i64,
     #[serde(rename(serialize = "taskType"))]
     pub task_type: i16,
+    pub kind: String,
     pub name: String,
     pub author: String,
     pub email: String,
@@ -51,6 +58,14 @@ pub struct TaskInfoResp {
     #[serde(rename(serialize = "statusDesc"))]
     pub status_desc: String,
     pub result: Option<String>,
+    pub attempts: i32, // 已执行次数
+    #[serde(rename(serialize = "runAfter"))]
+    pub run_after: Option<String>, // 重试等待, 在该时间之后执行
+    #[serde(rename(serialize = "cancelRequested"))]
+    pub cancel_requested: bool, // 执行中的任务已请求取消
+    pub progress: TaskProgressResp,
+    pub params: Value,
+    pub output: Option<Value>, // 结构化结果
     // 创建更新时间
     #[serde(rename(serialize = "createdAt"))]
     pub created_at: String,
@@ -76,3 +91,101 @@ pub async fn list(
 ) -> ApiResponse<TaskListResp> {
     ApiResponse::response(task::list(app_conf, req.into_inner()).await)
 }
+
+#[derive(Deserialize)]
+pub struct TaskRetryReq {
+    pub id: i64,
+    #[serde(rename(deserialize = "operatorId"))]
+    pub operator_id: i64,
+    #[serde(default)]
+    pub backoff: bool, // 按执行次数延迟重试
+}
+
+#[derive(Deserialize)]
+pub struct TaskOperateReq {
+    pub id: i64,
+    #[serde(rename(deserialize = "operatorId"))]
+    pub operator_id: i64,
+}
+
+#[derive(Serialize)]
+pub struct TaskHistoryResp {
+    pub id: i64,
+    pub action: String,
+    #[serde(rename(serialize = "operatorId"))]
+    pub operator_id: i64, // 0 表示执行进程
+    #[serde(rename(serialize = "workerId"))]
+    pub worker_id: Option<String>,
+    pub message: Option<String>,
+    #[serde(rename(serialize = "createdAt"))]
+    pub created_at: String,
+}
+
+// 重试失败或者取消的任务
+#[post("/retry")]
+pub async fn retry(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<TaskRetryReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(task::retry(app_conf, req.into_inner()).await)
+}
+
+// 取消任务, 执行中的任务由执行进程检查后停止
+#[post("/cancel")]
+pub async fn cancel(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<TaskOperateReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(task::cancel(app_conf, req.into_inner()).await)
+}
+
+// 删除没有在执行的任务
+#[post("/delete")]
+pub async fn delete(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<TaskOperateReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(task::delete(app_conf, req.into_inner()).await)
+}
+
+// 任务操作记录
+#[get("/history/{id}")]
+pub async fn history(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i64,)>,
+) -> ApiResponse<Vec<TaskHistoryResp>> {
+    ApiResponse::response(task::history(app_conf, path.into_inner().0).await)
+}
+
+// 执行进度
+#[derive(Serialize)]
+pub struct TaskProgressResp {
+    pub phase: String, // 当前阶段
+    pub done: i32,     // 已处理数量
+    pub total: i32,    // 总数量, 为 0 时表示数量未知
+}
+
+// 推送的任务事件内容
+#[derive(Serialize)]
+pub struct TaskEventResp {
+    pub id: i64,
+    pub status: i16,
+    #[serde(rename(serialize = "statusDesc"))]
+    pub status_desc: String,
+    pub result: Option<String>, // 结束后的结果
+    pub progress: TaskProgressResp,
+    #[serde(rename(serialize = "updatedAt"))]
+    pub updated_at: String,
+}
+
+// 推送任务进度和状态变化, 任务结束后断开
+// 事件类型 status 为状态变化, progress 为进度变化, error 为任务不存在等错误
+#[get("/{id}/events")]
+pub async fn events(app_conf: web::Data<AppConfig>, path: web::Path<(i64,)>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // 避免反向代理缓冲推送内容
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(task::events(app_conf, path.into_inner().0))
+}
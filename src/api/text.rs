@@ -1,8 +1,10 @@
+use crate::AppConfig;
 use crate::api::question::CreateQuestionReq;
 use crate::service::question_upload;
+use crate::task::question::QuestionImportItem;
 use crate::util::response::ApiResponse;
 use actix_web::{post, web};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 // 文本片段解析工具
 
@@ -37,3 +39,43 @@ pub async fn question_snippet(
 ) -> ApiResponse<CreateQuestionReq> {
     ApiResponse::response(question_upload::parse_question_snippet(req.into_inner()).await)
 }
+
+// 题目文件预览, 文件名称和文本内容二选一, 同时存在时使用文本内容
+#[derive(Deserialize)]
+pub struct QuestionPreviewReq {
+    #[serde(rename(deserialize = "questionCateId"))]
+    pub question_cate_id: i32,
+    #[serde(rename(deserialize = "textbookId"))]
+    pub textbook_id: i32,
+    pub url: Option<String>,     // 已上传的文件名称
+    pub content: Option<String>, // markdown 文本内容
+}
+
+#[derive(Serialize)]
+pub struct QuestionPreviewResp {
+    #[serde(rename(serialize = "questionCount"))]
+    pub question_count: usize, // 解析出的题目数量, 包括变式题
+    #[serde(rename(serialize = "validCount"))]
+    pub valid_count: usize, // 可以添加的题目数量
+    pub warnings: Vec<String>, // 文件相关的警告
+    #[serde(rename(serialize = "questionTypeList"))]
+    pub question_type_list: Vec<String>, // 教材节点可用的题目类型
+    #[serde(rename(serialize = "questionTagList"))]
+    pub question_tag_list: Vec<String>,
+    pub items: Vec<QuestionPreviewItem>,
+}
+
+#[derive(Serialize)]
+pub struct QuestionPreviewItem {
+    #[serde(flatten)]
+    pub item: QuestionImportItem, // 解析结果, 警告和字典匹配结果
+    pub req: CreateQuestionReq, // 将要添加的题目内容
+}
+
+#[post("/question/preview")]
+pub async fn question_preview(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<QuestionPreviewReq>,
+) -> ApiResponse<QuestionPreviewResp> {
+    ApiResponse::response(question_upload::preview(app_conf, req.into_inner()).await)
+}
@@ -95,6 +95,74 @@ pub async fn edit(
     ApiResponse::response(textbook::edit(app_conf, req.into_inner()).await)
 }
 
+// 批量排序, ids 为目标父级下排好序的子级列表, 其中父级不同的节点会被移动到目标父级下
+#[derive(Deserialize)]
+pub struct SortTextbookReq {
+    #[serde(rename(deserialize = "parentId"))]
+    pub parent_id: Option<i32>,
+    pub ids: Vec<i32>,
+}
+
+// 批量排序和拖拽移动
+#[post("/sort")]
+pub async fn sort(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<SortTextbookReq>,
+) -> ApiResponse<bool> {
+    ApiResponse::response(textbook::sort(app_conf, req.into_inner()).await)
+}
+
+// 节点及其子孙节点的关联内容统计
+#[derive(Serialize)]
+pub struct TextbookDependencyResp {
+    pub id: i32,
+    #[serde(rename(serialize = "nodeCount"))]
+    pub node_count: i64, // 子孙节点数量, 不包括自己
+    #[serde(rename(serialize = "chapterKnowledgeCount"))]
+    pub chapter_knowledge_count: i64, // 章节和知识点绑定关系数量
+    #[serde(rename(serialize = "questionCateCount"))]
+    pub question_cate_count: i64, // 题型数量
+    #[serde(rename(serialize = "questionCount"))]
+    pub question_count: i64, // 题目数量
+    #[serde(rename(serialize = "paperCount"))]
+    pub paper_count: i64, // 试卷数量
+    #[serde(rename(serialize = "taskCount"))]
+    pub task_count: i64, // 任务数量
+    #[serde(rename(serialize = "dictCount"))]
+    pub dict_count: i64, // 字典数量
+    #[serde(rename(serialize = "prerequisiteCount"))]
+    pub prerequisite_count: i64, // 知识点前置关系数量
+    #[serde(rename(serialize = "chapterMappingCount"))]
+    pub chapter_mapping_count: i64, // 跨版本章节对应关系数量
+    #[serde(rename(serialize = "questionKnowledgeCount"))]
+    pub question_knowledge_count: i64, // 题目和知识点关联数量
+}
+
+// 删除前查看节点的关联内容
+#[get("/dependency/{id}")]
+pub async fn dependency(
+    app_conf: web::Data<AppConfig>,
+    path: web::Path<(i32,)>,
+) -> ApiResponse<TextbookDependencyResp> {
+    ApiResponse::response(textbook::dependency(app_conf, path.into_inner().0).await)
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveTextbookReq {
+    pub id: i32,
+    #[serde(rename(deserialize = "targetId"))]
+    pub target_id: Option<i32>, // 试卷, 任务和字典改挂的节点, 为空时试卷直接归档
+}
+
+// 级联归档删除菜单, 返回归档前的关联内容统计
+#[post("/archive")]
+pub async fn archive(
+    app_conf: web::Data<AppConfig>,
+    req: web::Json<ArchiveTextbookReq>,
+) -> ApiResponse<TextbookDependencyResp> {
+    ApiResponse::response(textbook::archive(app_conf, req.into_inner()).await)
+}
+
 // 删除菜单
 #[get("/delete/{id}")]
 pub async fn delete(app_conf: web::Data<AppConfig>, path: web::Path<(i32,)>) -> ApiResponse<bool> {
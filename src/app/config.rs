@@ -1,3 +1,4 @@
+use crate::util::mail::{Lang, Mailer, SmtpConfig};
 use crate::{AppConfig, EnvConfig};
 use dotenvy::dotenv;
 use envy::from_env;
@@ -9,25 +10,58 @@ use std::str::FromStr;
 // 公共初始化配置函数
 // 目前 web cron 服务共用一个数据库连接池, 后续有变更再拆分
 pub async fn init() -> (EnvConfig, AppConfig) {
+    let env_config = load_env();
+    let app_config = init_app(&env_config).await;
+
+    (env_config, app_config)
+}
+
+// 读取环境变量配置
+pub fn load_env() -> EnvConfig {
     dotenv().ok();
 
-    let env_config: EnvConfig =
-        from_env::<EnvConfig>().expect("Failed to parse environment variable configuration");
+    from_env::<EnvConfig>().expect("Failed to parse environment variable configuration")
+}
 
+// 根据环境变量配置初始化数据库连接池等
+pub async fn init_app(env_config: &EnvConfig) -> AppConfig {
     let options = PgConnectOptions::from_str(&env_config.database_url)
         .expect("database url format is incorrect")
         .options([("timezone", "Asia/Shanghai")]);
 
     let pool = PgPoolOptions::new()
-        .max_connections(2) // 连接池后续追加至 .env 配置文件中
+        .max_connections(env_config.database_max_connections)
         .connect_with(options)
         .await
         .expect("Unable to connect to the database");
 
-    let app_config = AppConfig {
+    AppConfig {
         db: pool,
         meta_path: env_config.meta_path.clone(),
+        mailer: init_mailer(env_config),
+    }
+}
+
+// 配置了邮件服务地址才发送邮件, 配置错误时直接退出
+fn init_mailer(env_config: &EnvConfig) -> Option<Mailer> {
+    if env_config.smtp_host.is_empty() {
+        return None;
+    }
+
+    let smtp = SmtpConfig {
+        host: &env_config.smtp_host,
+        port: env_config.smtp_port,
+        tls: &env_config.smtp_tls,
+        username: &env_config.smtp_username,
+        password: &env_config.smtp_password,
+        from: &env_config.smtp_from,
     };
+    let mailer = Mailer::new(
+        &smtp,
+        Lang::from(&env_config.mail_lang),
+        env_config.mail_task_url.clone(),
+    )
+    .expect("smtp configuration is incorrect");
 
-    (env_config, app_config)
+    Some(mailer)
 }
@@ -1,23 +1,29 @@
 use crate::app::config;
 use crate::task;
+use log::error;
 
 /// 运行定时任务入口
 /// 启动方式类似:
-/// ./open-tiku-api question-upload // 上传题目
+/// ./open-tiku-api task question-upload // 上传题目
+/// 任务名称为注册的任务类型名称, 新的任务在 task::registry() 中注册
 pub async fn run_cron(args: Vec<String>) {
     let task_name = args.get(2).expect("需要指定任务名称");
 
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    if !task::registry().contains(task_name) {
+        eprintln!(
+            "未知任务: {}, 可用任务: {}",
+            task_name,
+            task::registry().names().join(", ")
+        );
+        std::process::exit(1);
+    }
+
     // 定时任务不需要监听端口这部分配置无需关注
     let (_, app_config) = config::init().await;
 
-    // 将任务名称注册到匹配条件中
-    match task_name.as_str() {
-        "question-upload" => task::question::upload(&app_config).await,
-        _ => {
-            eprintln!("未知任务: {}", task_name);
-            std::process::exit(1);
-        }
+    if let Err(e) = task::run_waiting(&app_config, &[task_name.as_str()]).await {
+        error!("Run task {} failed err: {}", task_name, e);
     }
 }
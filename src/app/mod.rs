@@ -2,3 +2,4 @@ pub mod cron;
 pub mod config;
 pub mod route;
 pub mod web;
+pub mod worker;
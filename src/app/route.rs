@@ -1,7 +1,9 @@
 use actix_web::web;
 
 use crate::api::{
-    chapter_knowledge, edit, file, other_dict, paper, question, question_cate, task, text, textbook,
+    chapter_knowledge, chapter_mapping, edit, file, knowledge_graph, other_dict, paper, question,
+    question_cate, question_errata, question_knowledge, question_similar, review, review_comment,
+    task, text, textbook,
 };
 
 /// web 服务路由配置
@@ -19,7 +21,9 @@ pub fn question(cfg: &mut web::ServiceConfig) {
         .service(question::info)
         .service(question::list)
         .service(question::similar)
-        .service(question::delete);
+        .service(question::delete)
+        .service(question::clone)
+        .service(question::move_to);
 }
 
 // 编辑问题, 考虑到冲突将其拆分到尽可能小的片段
@@ -34,6 +38,9 @@ pub fn textbook(cfg: &mut web::ServiceConfig) {
         .service(textbook::list_children)
         .service(textbook::add)
         .service(textbook::edit)
+        .service(textbook::sort)
+        .service(textbook::dependency)
+        .service(textbook::archive)
         .service(textbook::delete);
 }
 
@@ -41,25 +48,104 @@ pub fn textbook(cfg: &mut web::ServiceConfig) {
 pub fn chapter_knowledge(cfg: &mut web::ServiceConfig) {
     cfg.service(chapter_knowledge::add)
         .service(chapter_knowledge::list)
-        .service(chapter_knowledge::remove);
+        .service(chapter_knowledge::remove)
+        .service(chapter_knowledge::batch_bind)
+        .service(chapter_knowledge::batch_unbind)
+        .service(chapter_knowledge::coverage);
+}
+
+// 不同版本教材章节对应关系
+pub fn chapter_mapping(cfg: &mut web::ServiceConfig) {
+    cfg.service(chapter_mapping::add)
+        .service(chapter_mapping::remove)
+        .service(chapter_mapping::matches);
+}
+
+// 知识点前置关系
+pub fn knowledge_graph(cfg: &mut web::ServiceConfig) {
+    cfg.service(knowledge_graph::add)
+        .service(knowledge_graph::remove)
+        .service(knowledge_graph::prerequisites)
+        .service(knowledge_graph::unlocks)
+        .service(knowledge_graph::export_json)
+        .service(knowledge_graph::export_dot);
 }
 
 // 教材题型
+// 题目关联的知识点
+pub fn question_knowledge(cfg: &mut web::ServiceConfig) {
+    cfg.service(question_knowledge::bind)
+        .service(question_knowledge::list);
+}
+
+// 变式题关联管理
+pub fn question_similar(cfg: &mut web::ServiceConfig) {
+    cfg.service(question_similar::link)
+        .service(question_similar::unlink)
+        .service(question_similar::reparent)
+        .service(question_similar::promote)
+        .service(question_similar::tree)
+        .service(question_similar::suggest)
+        .service(question_similar::accept);
+}
+
+// 题目审核
+pub fn review(cfg: &mut web::ServiceConfig) {
+    cfg.service(review::queue)
+        .service(review::claim)
+        .service(review::release)
+        .service(review::batch);
+}
+
+// 题目勘误
+pub fn question_errata(cfg: &mut web::ServiceConfig) {
+    cfg.service(question_errata::add)
+        .service(question_errata::list)
+        .service(question_errata::mine)
+        .service(question_errata::info)
+        .service(question_errata::handle);
+}
+
+// 审核评论
+pub fn review_comment(cfg: &mut web::ServiceConfig) {
+    cfg.service(review_comment::add)
+        .service(review_comment::resolve)
+        .service(review_comment::remove)
+        .service(review_comment::list);
+}
+
 pub fn question_cate(cfg: &mut web::ServiceConfig) {
     cfg.service(question_cate::list)
         .service(question_cate::add)
-        .service(question_cate::remove);
+        .service(question_cate::remove)
+        .service(question_cate::edit)
+        .service(question_cate::sort)
+        .service(question_cate::move_to)
+        .service(question_cate::merge_preview)
+        .service(question_cate::merge);
 }
 
 // 教材其它字典
 pub fn textbook_dict(cfg: &mut web::ServiceConfig) {
     cfg.service(other_dict::add)
         .service(other_dict::remove)
-        .service(other_dict::list);
+        .service(other_dict::list)
+        .service(other_dict::effective)
+        .service(other_dict::effective_by_type)
+        .service(other_dict::edit)
+        .service(other_dict::sort)
+        .service(other_dict::usage)
+        .service(other_dict::merge);
 }
 
 pub fn task(cfg: &mut web::ServiceConfig) {
-    cfg.service(task::add).service(task::list);
+    cfg.service(task::add)
+        .service(task::list)
+        .service(task::retry)
+        .service(task::cancel)
+        .service(task::delete)
+        .service(task::history)
+        .service(task::events);
 }
 
 pub fn paper(cfg: &mut web::ServiceConfig) {
@@ -70,5 +156,6 @@ pub fn paper(cfg: &mut web::ServiceConfig) {
 }
 
 pub fn text(cfg: &mut web::ServiceConfig) {
-    cfg.service(text::question_snippet);
+    cfg.service(text::question_snippet)
+        .service(text::question_preview);
 }
@@ -23,7 +23,14 @@ pub async fn run_web() -> std::io::Result<()> {
             .service(web::scope("/edit").configure(route::edit))
             .service(web::scope("/textbook").configure(route::textbook))
             .service(web::scope("/chapter-knowledge").configure(route::chapter_knowledge))
+            .service(web::scope("/chapter-mapping").configure(route::chapter_mapping))
+            .service(web::scope("/knowledge-graph").configure(route::knowledge_graph))
             .service(web::scope("/question-cate").configure(route::question_cate))
+            .service(web::scope("/question-knowledge").configure(route::question_knowledge))
+            .service(web::scope("/question-similar").configure(route::question_similar))
+            .service(web::scope("/review").configure(route::review))
+            .service(web::scope("/review-comment").configure(route::review_comment))
+            .service(web::scope("/question-errata").configure(route::question_errata))
             .service(web::scope("/other/dict").configure(route::textbook_dict))
             .service(web::scope("/task").configure(route::task))
             .service(web::scope("/paper").configure(route::paper))
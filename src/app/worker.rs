@@ -0,0 +1,244 @@
+use crate::AppConfig;
+use crate::app::config;
+use crate::model::task::{NewTask, TASK_CHANNEL, Task};
+use crate::task;
+use log::{error, info, warn};
+use serde_json::Value;
+use sqlx::postgres::PgListener;
+use std::time::Duration;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+/// 常驻执行进程入口
+/// 启动方式类似:
+/// ./open-tiku-api worker // 执行所有注册的任务
+/// ./open-tiku-api worker question-upload // 只执行指定的任务, 多个使用空格分隔
+/// 新任务通过数据库通知唤醒, 同时定时轮询兜底, 收到 SIGTERM 后不再领取新任务, 等待执行中的任务完成后退出
+
+// 定时任务
+struct Schedule {
+    kind: String,
+    interval: Duration,
+    next_at: Instant,
+}
+
+// 解析定时任务配置, 格式为 任务类型名称=间隔秒数, 多个使用逗号分隔
+fn parse_schedule(text: &str, kinds: &[&str]) -> Vec<Schedule> {
+    let mut schedules = vec![];
+    for item in text
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let Some((kind, secs)) = item.split_once('=') else {
+            error!("定时任务配置格式错误: {}", item);
+            continue;
+        };
+        let kind = kind.trim();
+        let secs = match secs.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => secs,
+            _ => {
+                error!("定时任务 {} 间隔秒数错误: {}", kind, secs);
+                continue;
+            }
+        };
+        if !kinds.contains(&kind) {
+            error!("定时任务 {} 没有注册或者不在当前进程执行的任务中", kind);
+            continue;
+        }
+        // 定时任务没有参数, 参数不能为空的任务不能定时执行
+        if let Err(e) = task::registry().check(kind, &Value::Object(Default::default())) {
+            error!("定时任务 {} 不能使用空参数执行: {}", kind, e);
+            continue;
+        }
+
+        info!("Schedule task {} every {} seconds", kind, secs);
+        let interval = Duration::from_secs(secs);
+        schedules.push(Schedule {
+            kind: kind.to_string(),
+            interval,
+            next_at: Instant::now() + interval,
+        });
+    }
+
+    schedules
+}
+
+// 最坏的情况每个执行中的任务都在事务中占用一个连接, 同时还有一个预留给心跳和进度的连接
+// 另外一个用于领取任务, 恢复超时任务和定时调度
+fn min_connections(concurrency: usize) -> u32 {
+    concurrency as u32 * 2 + 1
+}
+
+// 监听新任务通知, 使用单独的连接, 不占用连接池
+async fn connect_listener(database_url: &str) -> Option<PgListener> {
+    let mut listener = match PgListener::connect(database_url).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Connect task listener err: {}, fallback to polling", e);
+            return None;
+        }
+    };
+    if let Err(e) = listener.listen(TASK_CHANNEL).await {
+        warn!("Listen task channel err: {}, fallback to polling", e);
+        return None;
+    }
+
+    Some(listener)
+}
+
+// 等待新任务通知, 没有监听时一直等待
+async fn recv(listener: &mut Option<PgListener>) {
+    match listener {
+        Some(listener) => {
+            if let Err(e) = listener.recv().await {
+                // 连接断开后下次调用会自动重连, 避免连续失败时空转
+                error!("Receive task notification err: {}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// 领取任务直到达到同时执行的数量
+async fn fill(
+    app_conf: &AppConfig,
+    kinds: &[&str],
+    worker_id: &str,
+    concurrency: usize,
+    running: &mut JoinSet<()>,
+) {
+    while running.len() < concurrency {
+        // 先预留任务使用的连接, 领取后心跳不会因为连接池用完而超时
+        let Ok(conn) = task::reserve_conn(app_conf).await else {
+            break;
+        };
+        match task::claim_next(app_conf, kinds, worker_id).await {
+            Ok(Some(task_info)) => {
+                let app_conf = app_conf.clone();
+                let worker_id = worker_id.to_string();
+                running.spawn(async move {
+                    task::run_claimed(&app_conf, task_info, &worker_id, conn).await;
+                });
+            }
+            // 领取失败已经记录日志, 等待下一次唤醒
+            Ok(None) | Err(_) => break,
+        }
+    }
+}
+
+// 添加到期的定时任务
+async fn enqueue_due(app_conf: &AppConfig, schedules: &mut [Schedule]) {
+    let now = Instant::now();
+    for schedule in schedules
+        .iter_mut()
+        .filter(|schedule| schedule.next_at <= now)
+    {
+        schedule.next_at = now + schedule.interval;
+
+        let new_task = NewTask {
+            kind: schedule.kind.clone(),
+            name: format!("定时任务 {}", schedule.kind),
+            params: Value::Object(Default::default()),
+            ..Default::default()
+        };
+        match Task::insert_scheduled(&app_conf.db, &new_task, schedule.interval.as_secs_f64()).await
+        {
+            Ok(Some(id)) => info!("Schedule task {} added id: {}", schedule.kind, id),
+            Ok(None) => info!(
+                "Schedule task {} already added by other worker",
+                schedule.kind
+            ),
+            Err(e) => error!("Schedule task {} add err: {}", schedule.kind, e),
+        }
+    }
+}
+
+pub async fn run_worker(args: Vec<String>) {
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+
+    // 指定任务类型时只执行这些任务
+    let names = task::registry().names();
+    let kinds: Vec<&str> = if args.len() > 2 {
+        args[2..].iter().map(String::as_str).collect()
+    } else {
+        names.clone()
+    };
+    if let Some(kind) = kinds.iter().find(|kind| !task::registry().contains(kind)) {
+        eprintln!("未知任务: {}, 可用任务: {}", kind, names.join(", "));
+        std::process::exit(1);
+    }
+
+    let mut env_config = config::load_env();
+    let concurrency = env_config.worker_concurrency.max(1);
+    // 连接池太小时执行中的任务会互相等待连接, 心跳超时后被其它进程重复执行
+    let min = min_connections(concurrency);
+    if env_config.database_max_connections < min {
+        warn!(
+            "DATABASE_MAX_CONNECTIONS {} is too small for {} concurrent tasks, use {}",
+            env_config.database_max_connections, concurrency, min
+        );
+        env_config.database_max_connections = min;
+    }
+    let app_config = config::init_app(&env_config).await;
+
+    let worker_id = task::worker_id();
+    let mut schedules = parse_schedule(&env_config.worker_schedule, &kinds);
+    let mut listener = connect_listener(&env_config.database_url).await;
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Unable to listen SIGTERM");
+    let mut poll_ticker =
+        tokio::time::interval(Duration::from_secs(env_config.worker_poll_secs.max(1)));
+    let mut running: JoinSet<()> = JoinSet::new();
+
+    info!(
+        "Worker {} started, kinds: {}, concurrency: {}",
+        worker_id,
+        kinds.join(", "),
+        concurrency
+    );
+
+    loop {
+        fill(&app_config, &kinds, &worker_id, concurrency, &mut running).await;
+
+        let next_schedule = schedules.iter().map(|schedule| schedule.next_at).min();
+        tokio::select! {
+            _ = sigterm.recv() => {
+                info!("Worker {} received SIGTERM", worker_id);
+                break;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Worker {} received SIGINT", worker_id);
+                break;
+            }
+            // 轮询时顺便恢复心跳超时的任务
+            _ = poll_ticker.tick() => {
+                let _ = task::recover_expired(&app_config, &kinds).await;
+            }
+            _ = recv(&mut listener) => {}
+            Some(res) = running.join_next(), if !running.is_empty() => {
+                if let Err(e) = res {
+                    error!("Worker {} task join err: {}", worker_id, e);
+                }
+            }
+            _ = tokio::time::sleep_until(next_schedule.unwrap_or_else(Instant::now)), if next_schedule.is_some() => {
+                enqueue_due(&app_config, &mut schedules).await;
+            }
+        }
+    }
+
+    // 不再领取新任务, 等待执行中的任务完成
+    info!(
+        "Worker {} stopping, waiting for {} running tasks",
+        worker_id,
+        running.len()
+    );
+    while let Some(res) = running.join_next().await {
+        if let Err(e) = res {
+            error!("Worker {} task join err: {}", worker_id, e);
+        }
+    }
+    info!("Worker {} stopped", worker_id);
+}
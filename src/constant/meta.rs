@@ -4,10 +4,12 @@ pub const IMAGE_NAME: &str = "images";
 pub const FILE_NAME: &str = "files";
 /// 最大文件大小（字节）
 pub const MAX_IMAGE_SIZE: usize = 1 * 1024 * 1024;
+/// 其它文件最大大小（字节）, 题目压缩包包含图片
+pub const MAX_FILE_SIZE: usize = 20 * 1024 * 1024;
 /// 允许的文件扩展名
 pub const ALLOW_IMAGE_EXTENSION: [&str; 4] = ["jpg", "jpeg", "png", "gif"];
-/// 允许的其它文件扩展名
-pub const ALLOW_FILE_EXTENSION: [&str; 1] = ["md"];
+/// 允许的其它文件扩展名, zip 为包含文档和图片的题目压缩包
+pub const ALLOW_FILE_EXTENSION: [&str; 2] = ["md", "zip"];
 /// 图片名称存储长度
 pub const IMAGE_NAME_LEN: usize = 10;
 /// 临时管理员标识
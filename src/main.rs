@@ -8,6 +8,8 @@ mod util;
 
 use crate::app::cron::run_cron;
 use crate::app::web::run_web;
+use crate::app::worker::run_worker;
+use crate::util::mail::Mailer;
 use serde::Deserialize;
 use sqlx::PgPool;
 use std::env;
@@ -19,13 +21,58 @@ struct EnvConfig {
     server_host: String,
     server_port: u16,
     meta_path: String,
+    #[serde(default = "default_max_connections")]
+    database_max_connections: u32, // 数据库连接池大小
+    #[serde(default = "default_worker_concurrency")]
+    worker_concurrency: usize, // 常驻执行进程同时执行的任务数量
+    #[serde(default = "default_worker_poll_secs")]
+    worker_poll_secs: u64, // 常驻执行进程轮询间隔, 没有收到通知时也会定时检查
+    #[serde(default)]
+    worker_schedule: String, // 定时任务, 格式为 任务类型名称=间隔秒数, 多个使用逗号分隔
+    #[serde(default)]
+    smtp_host: String, // 邮件服务地址, 为空时不发送任务结果邮件
+    #[serde(default = "default_smtp_port")]
+    smtp_port: u16,
+    #[serde(default = "default_smtp_tls")]
+    smtp_tls: String, // 加密方式 none starttls tls
+    #[serde(default)]
+    smtp_username: String,
+    #[serde(default)]
+    smtp_password: String,
+    #[serde(default)]
+    smtp_from: String, // 发件人, 例如 题库 <noreply@example.com>
+    #[serde(default)]
+    mail_lang: String, // 邮件内容语言 zh en, 默认中文
+    #[serde(default)]
+    mail_task_url: String, // 邮件中的任务链接, {id} 替换为任务主键
+}
+
+fn default_max_connections() -> u32 {
+    2
+}
+
+fn default_worker_concurrency() -> usize {
+    1
+}
+
+fn default_worker_poll_secs() -> u64 {
+    60
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_smtp_tls() -> String {
+    "starttls".to_string()
 }
 
 // 应用配置
 #[derive(Clone)]
 pub struct AppConfig {
-    db: PgPool,        // 数据库连接池
-    meta_path: String, // 元数据存储根目录
+    db: PgPool,             // 数据库连接池
+    meta_path: String,      // 元数据存储根目录
+    mailer: Option<Mailer>, // 邮件发送, 没有配置时为空
 }
 
 #[actix_web::main]
@@ -40,6 +87,13 @@ async fn main() -> std::io::Result<()> {
         return Ok(());
     }
 
+    // 常驻执行进程, 监听新任务并执行定时任务
+    // ./open-tiku-api worker [...](任务类型名称, 为空时执行所有注册的任务)
+    if args.len() > 1 && args[1] == "worker" {
+        run_worker(args).await;
+        return Ok(());
+    }
+
     // 默认启动 web 服务
     run_web().await
 }
@@ -1,5 +1,5 @@
 use crate::api::chapter_knowledge::CreateChapterKnowledgeReq;
-use sqlx::{FromRow, PgPool};
+use sqlx::{Executor, FromRow, PgPool, Postgres, QueryBuilder, Transaction};
 
 /// 章节节点和知识点类名称关联关系-目前是一对一的关系
 
@@ -10,6 +10,15 @@ pub struct ChapterKnowledge {
     pub knowledge_id: i32,
 }
 
+// 绑定关系下的题目数量
+#[derive(FromRow)]
+pub struct ChapterKnowledgeCount {
+    pub id: i32,
+    pub chapter_id: i32,
+    pub knowledge_id: i32,
+    pub question_count: i64,
+}
+
 impl ChapterKnowledge {
     // 保存关联关系
     pub async fn insert(
@@ -47,15 +56,26 @@ impl ChapterKnowledge {
     }
 
     // 通过章节小节或者知识点小类获取所有的关联关系
-    pub async fn find_by_ids(pool: &PgPool, ids: Vec<i32>) -> Result<Vec<Self>, sqlx::Error> {
+    pub async fn find_by_ids<'e, E>(executor: E, ids: Vec<i32>) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         sqlx::query_as::<_, Self>(
             "SELECT * FROM chapter_knowledge WHERE knowledge_id = ANY($1) OR chapter_id = ANY($1)",
         )
         .bind(ids)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await
     }
 
+    // 根据主键查询
+    pub async fn find_by_id(pool: &PgPool, id: i32) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM chapter_knowledge WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
     // 查看是否已关联
     pub async fn find_unique(
         pool: &PgPool,
@@ -71,18 +91,57 @@ impl ChapterKnowledge {
         .await
     }
 
-    // 通过章节或者知识点查找关联信息
-    pub async fn find_by_chapter_or_knowledge_id(
+    // 根据主键批量删除关联关系
+    pub async fn tx_delete_by_ids(
+        tx: &mut Transaction<'_, Postgres>,
+        ids: &[i32],
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM chapter_knowledge WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 批量保存关联关系, 已存在的跳过, 返回新增的数量
+    pub async fn batch_insert(pool: &PgPool, pairs: Vec<(i32, i32)>) -> Result<u64, sqlx::Error> {
+        if pairs.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder =
+            QueryBuilder::new("INSERT INTO chapter_knowledge (chapter_id, knowledge_id) ");
+
+        query_builder.push_values(pairs, |mut b, (chapter_id, knowledge_id)| {
+            b.push_bind(chapter_id).push_bind(knowledge_id);
+        });
+
+        query_builder.push(" ON CONFLICT (chapter_id, knowledge_id) DO NOTHING");
+
+        let result = query_builder.build().execute(pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    // 绑定关系下指定状态的题目数量
+    pub async fn count_questions_by_ids(
         pool: &PgPool,
-        chapter_or_knowledge_id: i32,
-    ) -> Result<Vec<Self>, sqlx::Error> {
-        sqlx::query_as::<_, Self>(
-            "SELECT id, chapter_id, knowledge_id 
-                 FROM chapter_knowledge
-                 WHERE chapter_id = $1 OR knowledge_id = $1
-                ",
+        ids: &[i32],
+        status: i16,
+    ) -> Result<Vec<ChapterKnowledgeCount>, sqlx::Error> {
+        sqlx::query_as::<_, ChapterKnowledgeCount>(
+            r#"
+            SELECT ck.id, ck.chapter_id, ck.knowledge_id, COUNT(q.id) AS question_count
+            FROM chapter_knowledge ck
+            LEFT JOIN question_cate qc ON qc.related_id = ck.id
+            LEFT JOIN question q ON q.question_cate_id = qc.id AND q.status = $2
+            WHERE ck.id = ANY($1)
+            GROUP BY ck.id, ck.chapter_id, ck.knowledge_id
+            ORDER BY ck.id
+            "#,
         )
-        .bind(chapter_or_knowledge_id)
+        .bind(ids)
+        .bind(status)
         .fetch_all(pool)
         .await
     }
@@ -0,0 +1,96 @@
+use sqlx::{Executor, FromRow, PgPool, Postgres};
+
+/// 不同版本教材章节之间的对应关系, 关系没有方向, source_id 始终是较小的标识
+
+#[derive(FromRow)]
+pub struct ChapterMapping {
+    pub id: i32,
+    pub source_id: i32,
+    pub target_id: i32,
+    pub confidence: i16,
+    pub note: Option<String>,
+}
+
+impl ChapterMapping {
+    // 关系另一端的章节
+    pub fn other_id(&self, chapter_id: i32) -> i32 {
+        if self.source_id == chapter_id {
+            self.target_id
+        } else {
+            self.source_id
+        }
+    }
+
+    // 保存对应关系, 已存在时更新匹配程度和说明
+    pub async fn insert(
+        pool: &PgPool,
+        chapter_id: i32,
+        other_id: i32,
+        confidence: i16,
+        note: Option<String>,
+    ) -> Result<i32, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO chapter_mapping (source_id, target_id, confidence, note)
+            VALUES (LEAST($1, $2), GREATEST($1, $2), $3, $4)
+            ON CONFLICT (source_id, target_id) DO UPDATE SET
+                confidence = EXCLUDED.confidence,
+                note = EXCLUDED.note
+            RETURNING id
+            "#,
+        )
+        .bind(chapter_id)
+        .bind(other_id)
+        .bind(confidence)
+        .bind(note)
+        .fetch_one(pool)
+        .await
+    }
+
+    // 删除对应关系
+    pub async fn delete(pool: &PgPool, chapter_id: i32, other_id: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM chapter_mapping WHERE source_id = LEAST($1, $2) AND target_id = GREATEST($1, $2)",
+        )
+        .bind(chapter_id)
+        .bind(other_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 章节的所有对应关系, 按匹配程度倒序
+    pub async fn find_by_chapter_id(
+        pool: &PgPool,
+        chapter_id: i32,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT id, source_id, target_id, confidence, note
+            FROM chapter_mapping
+            WHERE source_id = $1 OR target_id = $1
+            ORDER BY confidence DESC, id
+            "#,
+        )
+        .bind(chapter_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 任意一端在给定节点列表中的对应关系数量
+    pub async fn count_by_textbook_ids<'e, E>(
+        executor: E,
+        textbook_ids: &[i32],
+    ) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM chapter_mapping WHERE source_id = ANY($1) OR target_id = ANY($1)",
+        )
+        .bind(textbook_ids)
+        .fetch_one(executor)
+        .await
+    }
+}
@@ -0,0 +1,197 @@
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction};
+
+/// 知识点前置关系, knowledge_id 需要先掌握 prerequisite_id
+
+// 递归查询时防止异常数据成环导致无限递归
+const MAX_DISTANCE: i32 = 64;
+
+#[allow(dead_code)]
+#[derive(FromRow)]
+pub struct KnowledgePrerequisite {
+    pub id: i32,
+    pub knowledge_id: i32,
+    pub prerequisite_id: i32,
+}
+
+// 递归查询得到的知识点, distance 为相隔的层数, 1 为直接关联
+#[derive(FromRow)]
+pub struct KnowledgeRelation {
+    pub id: i32,
+    pub label: String,
+    pub distance: i32,
+}
+
+impl KnowledgePrerequisite {
+    // 添加前置关系, 已存在时不重复添加
+    pub async fn insert<'e, E>(
+        executor: E,
+        knowledge_id: i32,
+        prerequisite_id: i32,
+    ) -> Result<i32, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let id: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO knowledge_prerequisite (knowledge_id, prerequisite_id)
+            VALUES ($1, $2)
+            ON CONFLICT (knowledge_id, prerequisite_id) DO NOTHING
+            RETURNING id
+            "#,
+        )
+        .bind(knowledge_id)
+        .bind(prerequisite_id)
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(id.unwrap_or(0))
+    }
+
+    // 删除前置关系
+    pub async fn delete(
+        pool: &PgPool,
+        knowledge_id: i32,
+        prerequisite_id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM knowledge_prerequisite WHERE knowledge_id = $1 AND prerequisite_id = $2",
+        )
+        .bind(knowledge_id)
+        .bind(prerequisite_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 两端都在给定节点列表中的所有前置关系, 导出图使用
+    pub async fn find_by_ids(pool: &PgPool, ids: &[i32]) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT id, knowledge_id, prerequisite_id
+            FROM knowledge_prerequisite
+            WHERE knowledge_id = ANY($1) AND prerequisite_id = ANY($1)
+            ORDER BY id
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 事务级别的锁, 添加前置关系时串行执行成环检查和添加, 前置关系可以跨教材所以使用同一个锁
+    pub async fn tx_lock(tx: &mut Transaction<'_, Postgres>) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('knowledge_prerequisite'))")
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 检查 prerequisite_id 是否已经是 knowledge_id 直接或间接的前置知识点
+    /// 添加 a -> b 之前检查 b 是否已经(间接)依赖 a, 如果返回 true 说明会形成环
+    /// 不限制层数, UNION 去重后已经访问过的节点不会重复递归
+    pub async fn is_prerequisite<'e, E>(
+        executor: E,
+        knowledge_id: i32,
+        prerequisite_id: i32,
+    ) -> Result<bool, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, bool>(
+            r#"
+            WITH RECURSIVE pre AS (
+                SELECT prerequisite_id AS id
+                FROM knowledge_prerequisite
+                WHERE knowledge_id = $1
+                UNION
+                SELECT kp.prerequisite_id
+                FROM knowledge_prerequisite kp
+                JOIN pre ON kp.knowledge_id = pre.id
+            )
+            SELECT EXISTS (SELECT 1 FROM pre WHERE id = $2)
+            "#,
+        )
+        .bind(knowledge_id)
+        .bind(prerequisite_id)
+        .fetch_one(executor)
+        .await
+    }
+
+    // 所有直接或间接的前置知识点
+    pub async fn find_prerequisites(
+        pool: &PgPool,
+        knowledge_id: i32,
+    ) -> Result<Vec<KnowledgeRelation>, sqlx::Error> {
+        sqlx::query_as::<_, KnowledgeRelation>(
+            r#"
+            WITH RECURSIVE pre AS (
+                SELECT prerequisite_id AS id, 1 AS distance
+                FROM knowledge_prerequisite
+                WHERE knowledge_id = $1
+                UNION
+                SELECT kp.prerequisite_id, pre.distance + 1
+                FROM knowledge_prerequisite kp
+                JOIN pre ON kp.knowledge_id = pre.id
+                WHERE pre.distance < $2
+            )
+            SELECT t.id, t.label, MIN(pre.distance) AS distance
+            FROM pre
+            JOIN textbook t ON t.id = pre.id
+            GROUP BY t.id, t.label
+            ORDER BY distance, t.id
+            "#,
+        )
+        .bind(knowledge_id)
+        .bind(MAX_DISTANCE)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 所有直接或间接依赖当前知识点的后续知识点
+    pub async fn find_unlocks(
+        pool: &PgPool,
+        prerequisite_id: i32,
+    ) -> Result<Vec<KnowledgeRelation>, sqlx::Error> {
+        sqlx::query_as::<_, KnowledgeRelation>(
+            r#"
+            WITH RECURSIVE next AS (
+                SELECT knowledge_id AS id, 1 AS distance
+                FROM knowledge_prerequisite
+                WHERE prerequisite_id = $1
+                UNION
+                SELECT kp.knowledge_id, next.distance + 1
+                FROM knowledge_prerequisite kp
+                JOIN next ON kp.prerequisite_id = next.id
+                WHERE next.distance < $2
+            )
+            SELECT t.id, t.label, MIN(next.distance) AS distance
+            FROM next
+            JOIN textbook t ON t.id = next.id
+            GROUP BY t.id, t.label
+            ORDER BY distance, t.id
+            "#,
+        )
+        .bind(prerequisite_id)
+        .bind(MAX_DISTANCE)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 任意一端在给定节点列表中的前置关系数量
+    pub async fn count_by_textbook_ids<'e, E>(
+        executor: E,
+        textbook_ids: &[i32],
+    ) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM knowledge_prerequisite WHERE knowledge_id = ANY($1) OR prerequisite_id = ANY($1)",
+        )
+        .bind(textbook_ids)
+        .fetch_one(executor)
+        .await
+    }
+}
@@ -8,3 +8,9 @@ pub mod task;
 pub mod paper;
 pub mod paper_group;
 pub mod paper_question;
+pub mod knowledge_prerequisite;
+pub mod chapter_mapping;
+pub mod question_knowledge;
+pub mod review_comment;
+pub mod question_errata;
+pub mod task_history;
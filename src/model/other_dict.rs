@@ -1,5 +1,5 @@
 use crate::api::other_dict::CreateTextbookDictReq;
-use sqlx::{FromRow, PgPool};
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction};
 
 /// 教材其它字典
 
@@ -11,6 +11,16 @@ pub struct TextbookDict {
     pub item_value: String,
     pub sort_order: i32,
     pub is_select: bool,
+    pub is_hidden: bool,
+}
+
+// 沿祖先链查询得到的字典项, distance 为来源节点相隔的层数, 0 为当前节点
+#[derive(FromRow, Clone)]
+pub struct InheritedDict {
+    #[sqlx(flatten)]
+    pub dict: TextbookDict,
+    pub source_label: String,
+    pub distance: i32,
 }
 
 impl TextbookDict {
@@ -18,17 +28,18 @@ impl TextbookDict {
     pub async fn insert(pool: &PgPool, req: CreateTextbookDictReq) -> Result<i32, sqlx::Error> {
         let id: i32 = sqlx::query_scalar(
             r#"
-        INSERT INTO textbook_dict (id, textbook_id, type_code, item_value, sort_order, is_select)
+        INSERT INTO textbook_dict (id, textbook_id, type_code, item_value, sort_order, is_select, is_hidden)
         VALUES (
             COALESCE(NULLIF($1, 0), nextval('textbook_dict_id_seq')),
-            $2, $3, $4, $5, $6
+            $2, $3, $4, $5, $6, $7
         )
         ON CONFLICT (id) DO UPDATE SET
             textbook_id = EXCLUDED.textbook_id,
             type_code = EXCLUDED.type_code,
             item_value = EXCLUDED.item_value,
             sort_order = EXCLUDED.sort_order,
-            is_select = EXCLUDED.is_select
+            is_select = EXCLUDED.is_select,
+            is_hidden = EXCLUDED.is_hidden
         RETURNING id
         "#,
         )
@@ -38,6 +49,7 @@ impl TextbookDict {
         .bind(req.item_value)
         .bind(req.sort_order)
         .bind(req.is_select)
+        .bind(req.is_hidden)
         .fetch_one(pool)
         .await?;
 
@@ -68,7 +80,7 @@ impl TextbookDict {
     ) -> Result<Vec<Self>, sqlx::Error> {
         sqlx::query_as::<_, Self>(
             r#"
-            SELECT id, textbook_id, type_code, item_value, sort_order, is_select 
+            SELECT id, textbook_id, type_code, item_value, sort_order, is_select, is_hidden
             FROM textbook_dict 
             WHERE textbook_id = $1 AND type_code = $2
             ORDER BY sort_order
@@ -80,6 +92,106 @@ impl TextbookDict {
         .await
     }
 
+    // 当前节点及所有祖先节点上的字典项, 同名字典项按距离由近到远排列, type_code 为空时查询所有类型
+    pub async fn find_by_ancestors(
+        pool: &PgPool,
+        textbook_id: i32,
+        type_code: Option<&str>,
+    ) -> Result<Vec<InheritedDict>, sqlx::Error> {
+        sqlx::query_as::<_, InheritedDict>(
+            r#"
+            WITH RECURSIVE chain AS (
+                SELECT id, parent_id, label, 0 AS distance
+                FROM textbook
+                WHERE id = $1
+                UNION ALL
+                SELECT t.id, t.parent_id, t.label, chain.distance + 1
+                FROM textbook t
+                JOIN chain ON t.id = chain.parent_id
+                WHERE chain.distance < 64
+            )
+            SELECT d.id, d.textbook_id, d.type_code, d.item_value, d.sort_order, d.is_select,
+                   d.is_hidden, chain.label AS source_label, chain.distance
+            FROM textbook_dict d
+            JOIN chain ON d.textbook_id = chain.id
+            WHERE $2::VARCHAR IS NULL OR d.type_code = $2
+            ORDER BY d.type_code, d.item_value, chain.distance
+            "#,
+        )
+        .bind(textbook_id)
+        .bind(type_code)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 根据主键查询
+    pub async fn find_by_id(pool: &PgPool, id: i32) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM textbook_dict WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    // 根据主键批量查询
+    pub async fn find_by_ids(pool: &PgPool, ids: &[i32]) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM textbook_dict WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 修改字典项的值, 排序和是否选择题
+    pub async fn update(
+        pool: &PgPool,
+        id: i32,
+        item_value: &str,
+        sort_order: i32,
+        is_select: bool,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE textbook_dict SET item_value = $2, sort_order = $3, is_select = $4 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(item_value)
+        .bind(sort_order)
+        .bind(is_select)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 修改排序
+    pub async fn update_sort_order<'e, E>(
+        executor: E,
+        id: i32,
+        sort_order: i32,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query("UPDATE textbook_dict SET sort_order = $2 WHERE id = $1")
+            .bind(id)
+            .bind(sort_order)
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 事务中删除字典项
+    pub async fn tx_delete(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM textbook_dict WHERE id = $1")
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
     // 删除特定字典项
     pub async fn delete(pool: &PgPool, id: i32) -> Result<u64, sqlx::Error> {
         let result = sqlx::query!("DELETE FROM textbook_dict WHERE id = $1", id)
@@ -88,4 +200,76 @@ impl TextbookDict {
 
         Ok(result.rows_affected())
     }
+
+    // 多个教材节点下的字典数量
+    pub async fn count_by_textbook_ids<'e, E>(
+        executor: E,
+        textbook_ids: &[i32],
+    ) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM textbook_dict WHERE textbook_id = ANY($1)",
+        )
+        .bind(textbook_ids)
+        .fetch_one(executor)
+        .await
+    }
+
+    // 多个教材节点下与新节点上同名的字典, 返回 (字典标识, 新节点上同名字典标识)
+    pub async fn tx_find_duplicates(
+        tx: &mut Transaction<'_, Postgres>,
+        textbook_ids: &[i32],
+        target_id: i32,
+    ) -> Result<Vec<(i32, i32)>, sqlx::Error> {
+        sqlx::query_as::<_, (i32, i32)>(
+            r#"
+            SELECT d.id, t.id
+            FROM textbook_dict d
+            JOIN textbook_dict t
+              ON t.textbook_id = $2
+             AND t.type_code = d.type_code
+             AND t.item_value = d.item_value
+            WHERE d.textbook_id = ANY($1)
+            "#,
+        )
+        .bind(textbook_ids)
+        .bind(target_id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    // 将多个教材节点下的字典改挂到新的节点, 同名字典只改挂一个, 新节点已存在同名字典的跳过
+    // 跳过的字典随节点一起删除, 删除前需要先替换题目中的引用
+    pub async fn tx_update_textbook_id(
+        tx: &mut Transaction<'_, Postgres>,
+        textbook_ids: &[i32],
+        target_id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE textbook_dict AS d
+            SET textbook_id = $2
+            WHERE d.id IN (
+                SELECT DISTINCT ON (type_code, item_value) id
+                FROM textbook_dict
+                WHERE textbook_id = ANY($1)
+                ORDER BY type_code, item_value, id
+              )
+              AND NOT EXISTS (
+                SELECT 1 FROM textbook_dict t
+                WHERE t.textbook_id = $2
+                  AND t.type_code = d.type_code
+                  AND t.item_value = d.item_value
+              )
+            "#,
+        )
+        .bind(textbook_ids)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -1,7 +1,7 @@
 use crate::api::paper::PaperListReq;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool, Postgres, Transaction, Type, query_as, query_scalar};
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction, Type, query_as, query_scalar};
 
 /// 试卷相关
 
@@ -37,6 +37,7 @@ pub enum PaperStatus {
     Pending = 1,   // 1: 待审核
     Published = 2, // 2: 已发布
     Rejected = 3,  // 3: 被拒绝
+    Archived = 4,  // 4: 已归档, 所属教材节点被删除后软删除
 }
 
 impl PaperStatus {
@@ -46,6 +47,7 @@ impl PaperStatus {
             1 => "待审核".to_string(),
             2 => "已发布".to_string(),
             3 => "被拒绝".to_string(),
+            4 => "已归档".to_string(),
             _ => "未知状态".to_string(),
         }
     }
@@ -252,4 +254,58 @@ impl Paper {
 
         Ok(papers)
     }
+
+    // 关联在多个节点下的试卷数量
+    pub async fn count_by_related_ids<'e, E>(
+        executor: E,
+        related_ids: &[i32],
+    ) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        query_scalar::<_, i64>("SELECT COUNT(*) FROM paper WHERE related_id = ANY($1)")
+            .bind(related_ids)
+            .fetch_one(executor)
+            .await
+    }
+
+    // 将多个节点下的试卷改挂到新的节点
+    pub async fn tx_update_related_id(
+        tx: &mut Transaction<'_, Postgres>,
+        related_ids: &[i32],
+        target_id: i32,
+        target_name: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE paper
+            SET related_id = $2, related_name = $3, updated_at = NOW()
+            WHERE related_id = ANY($1)
+            "#,
+        )
+        .bind(related_ids)
+        .bind(target_id)
+        .bind(target_name)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 批量更新多个节点下的试卷状态, 归档时使用
+    pub async fn tx_update_status_by_related_ids(
+        tx: &mut Transaction<'_, Postgres>,
+        related_ids: &[i32],
+        status: i16,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE paper SET status = $2, updated_at = NOW() WHERE related_id = ANY($1)",
+        )
+        .bind(related_ids)
+        .bind(status)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -3,7 +3,7 @@ use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
-use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Transaction, Type};
+use sqlx::{Executor, FromRow, PgPool, Postgres, QueryBuilder, Transaction, Type};
 
 /// 题目
 
@@ -24,6 +24,20 @@ pub enum QuestionStatus {
     Pending = 1,   // 1: 待审核
     Published = 2, // 2: 已发布
     Rejected = 3,  // 3: 被拒绝
+    Archived = 4,  // 4: 已归档, 所属教材节点被删除后软删除
+}
+
+impl QuestionStatus {
+    pub fn desc(code: i16) -> String {
+        match code {
+            0 => "草稿".to_string(),
+            1 => "待审核".to_string(),
+            2 => "已发布".to_string(),
+            3 => "被拒绝".to_string(),
+            4 => "已归档".to_string(),
+            _ => "未知状态".to_string(),
+        }
+    }
 }
 
 // 解题分析
@@ -82,6 +96,15 @@ pub struct Question {
     pub updated_at: DateTime<Utc>,
 }
 
+// 题目审核领取信息
+#[derive(FromRow)]
+pub struct ReviewClaim {
+    pub id: i64,
+    pub status: i16,
+    pub review_claim_id: Option<i64>,
+    pub review_claim_at: Option<DateTime<Utc>>,
+}
+
 impl Question {
     // 添加题目-根据主键判断是新增还是更新
     pub async fn simple_insert(pool: &PgPool, req: CreateQuestionReq) -> Result<i64, sqlx::Error> {
@@ -268,7 +291,16 @@ impl Question {
             .await
     }
 
-    // 题型下题目数量
+    // 通过id列表获取详情
+    pub async fn find_by_ids(pool: &PgPool, ids: &[i64]) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM question WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 题型下题目数量, knowledge_id 包含子孙知识点节点
+    #[allow(clippy::too_many_arguments)]
     pub async fn count_by_cate_and_type(
         pool: &PgPool,
         cate_id: i32,
@@ -278,17 +310,27 @@ impl Question {
         title_val: Option<String>,
         tag_ids: Option<Vec<i32>>,
         dimension_ids: Option<Vec<i32>>,
+        knowledge_id: Option<i32>,
     ) -> Result<i64, sqlx::Error> {
         sqlx::query_scalar::<_, i64>(
             r#"
             SELECT COUNT(*) FROM question 
             WHERE question_cate_id = $1
               AND status = $2
+              AND revision_of = 0
               AND ($3 IS NULL OR question_type_id = $3)
               AND ($4 IS NULL OR id = ANY($4))
               AND ($5 IS NULL OR content_plain LIKE '%' || $5 || '%')
               AND ($6 IS NULL OR question_tag_ids @> $7)
               AND ($8 IS NULL OR question_dimension_ids @> $9)
+              AND ($10::INT IS NULL OR id IN (
+                WITH RECURSIVE sub AS (
+                    SELECT id FROM textbook WHERE id = $10
+                    UNION ALL
+                    SELECT t.id FROM textbook t JOIN sub ON t.parent_id = sub.id
+                )
+                SELECT qk.question_id FROM question_knowledge qk JOIN sub ON sub.id = qk.knowledge_id
+              ))
             "#,
         )
         .bind(cate_id)
@@ -300,11 +342,13 @@ impl Question {
         .bind(tag_ids.map(Json))
         .bind(dimension_ids.as_ref().map(|_| true))
         .bind(dimension_ids.map(Json))
+        .bind(knowledge_id)
         .fetch_one(pool)
         .await
     }
 
-    // 题型下题目列表
+    // 题型下题目列表, knowledge_id 包含子孙知识点节点
+    #[allow(clippy::too_many_arguments)]
     pub async fn list_by_cate_and_type(
         pool: &PgPool,
         cate_id: i32,
@@ -314,6 +358,7 @@ impl Question {
         title_val: Option<String>,
         tag_ids: Option<Vec<i32>>,
         dimension_ids: Option<Vec<i32>>,
+        knowledge_id: Option<i32>,
         limit: i32,
         offset: i32,
     ) -> Result<Vec<Self>, sqlx::Error> {
@@ -323,11 +368,20 @@ impl Question {
             FROM question
             WHERE question_cate_id = $1
               AND status = $2
+              AND revision_of = 0
               AND ($3 IS NULL OR question_type_id = $3)
               AND ($4 IS NULL OR id = ANY($4))
               AND ($5 IS NULL OR content_plain LIKE '%' || $5 || '%')
               AND ($6 IS NULL OR question_tag_ids @> $7)
               AND ($8 IS NULL OR question_dimension_ids @> $9)
+              AND ($12::INT IS NULL OR id IN (
+                WITH RECURSIVE sub AS (
+                    SELECT id FROM textbook WHERE id = $12
+                    UNION ALL
+                    SELECT t.id FROM textbook t JOIN sub ON t.parent_id = sub.id
+                )
+                SELECT qk.question_id FROM question_knowledge qk JOIN sub ON sub.id = qk.knowledge_id
+              ))
             ORDER BY id DESC
             LIMIT $10 OFFSET $11
             "#,
@@ -343,10 +397,186 @@ impl Question {
         .bind(dimension_ids.map(Json))
         .bind(limit)
         .bind(offset)
+        .bind(knowledge_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 推荐变式题的候选题目, 同一个题型或者关联了相同知识点的题目, 排除指定题目, 已归档的题目和修订稿
+    pub async fn find_similar_candidates(
+        pool: &PgPool,
+        cate_id: i32,
+        knowledge_ids: &[i32],
+        exclude_ids: &[i64],
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT *
+            FROM question
+            WHERE (question_cate_id = $1
+                OR id IN (SELECT question_id FROM question_knowledge WHERE knowledge_id = ANY($2)))
+              AND id <> ALL($3)
+              AND status <> $4
+              AND revision_of = 0
+            ORDER BY id DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(cate_id)
+        .bind(knowledge_ids)
+        .bind(exclude_ids)
+        .bind(QuestionStatus::Archived as i16)
+        .bind(limit)
         .fetch_all(pool)
         .await
     }
 
+    // 多个题型下的题目数量
+    pub async fn count_by_cate_ids<'e, E>(executor: E, cate_ids: &[i32]) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM question WHERE question_cate_id = ANY($1)",
+        )
+        .bind(cate_ids)
+        .fetch_one(executor)
+        .await
+    }
+
+    // 批量更新多个题型下的题目状态, 归档时使用
+    pub async fn tx_update_status_by_cate_ids(
+        tx: &mut Transaction<'_, Postgres>,
+        cate_ids: &[i32],
+        status: i16,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE question SET status = $2, updated_at = NOW() WHERE question_cate_id = ANY($1)",
+        )
+        .bind(cate_ids)
+        .bind(status)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 将题型下的题目改挂到新的题型, 合并题型时使用
+    pub async fn tx_update_cate_id(
+        tx: &mut Transaction<'_, Postgres>,
+        cate_id: i32,
+        target_id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE question SET question_cate_id = $2, updated_at = NOW() WHERE question_cate_id = $1",
+        )
+        .bind(cate_id)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 字典项被题目引用的数量, 包含题目类型, 标签和核心素养
+    pub async fn count_by_dict_ids(
+        pool: &PgPool,
+        dict_ids: &[i32],
+    ) -> Result<Vec<(i32, i64)>, sqlx::Error> {
+        sqlx::query_as::<_, (i32, i64)>(
+            r#"
+            SELECT d.id, COUNT(q.id)
+            FROM UNNEST($1::INT[]) AS d(id)
+            LEFT JOIN question q
+                ON q.question_type_id = d.id
+                OR q.question_tag_ids @> jsonb_build_array(d.id)
+                OR q.question_dimension_ids @> jsonb_build_array(d.id)
+            GROUP BY d.id
+            "#,
+        )
+        .bind(dict_ids)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 修改题目的题型和字典项, 移动题目时使用
+    pub async fn tx_update_cate_and_dict(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+        cate_id: i32,
+        type_id: i32,
+        tag_ids: Vec<i32>,
+        dimension_ids: Vec<i32>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question SET
+                question_cate_id = $2,
+                question_type_id = $3,
+                question_tag_ids = $4,
+                question_dimension_ids = $5,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(cate_id)
+        .bind(type_id)
+        .bind(Json(tag_ids))
+        .bind(Json(dimension_ids))
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 将题目中引用的字典项替换为新的字典项, 标签和核心素养数组替换后去重并保持原有顺序
+    pub async fn tx_replace_dict_id(
+        tx: &mut Transaction<'_, Postgres>,
+        source_id: i32,
+        target_id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question SET
+                question_type_id = CASE WHEN question_type_id = $1 THEN $2 ELSE question_type_id END,
+                question_tag_ids = CASE WHEN question_tag_ids @> jsonb_build_array($1) THEN (
+                    SELECT COALESCE(jsonb_agg(v ORDER BY ord), '[]'::jsonb)
+                    FROM (
+                        SELECT DISTINCT ON (v) v, ord
+                        FROM (
+                            SELECT CASE WHEN e.v = to_jsonb($1) THEN to_jsonb($2) ELSE e.v END AS v, e.ord
+                            FROM jsonb_array_elements(question_tag_ids) WITH ORDINALITY AS e(v, ord)
+                        ) mapped
+                        ORDER BY v, ord
+                    ) uniq
+                ) ELSE question_tag_ids END,
+                question_dimension_ids = CASE WHEN question_dimension_ids @> jsonb_build_array($1) THEN (
+                    SELECT COALESCE(jsonb_agg(v ORDER BY ord), '[]'::jsonb)
+                    FROM (
+                        SELECT DISTINCT ON (v) v, ord
+                        FROM (
+                            SELECT CASE WHEN e.v = to_jsonb($1) THEN to_jsonb($2) ELSE e.v END AS v, e.ord
+                            FROM jsonb_array_elements(question_dimension_ids) WITH ORDINALITY AS e(v, ord)
+                        ) mapped
+                        ORDER BY v, ord
+                    ) uniq
+                ) ELSE question_dimension_ids END,
+                updated_at = NOW()
+            WHERE question_type_id = $1
+               OR question_tag_ids @> jsonb_build_array($1)
+               OR question_dimension_ids @> jsonb_build_array($1)
+            "#,
+        )
+        .bind(source_id)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     // 题型下是否存在题目
     pub async fn exist_by_cate_id(pool: &PgPool, cate_id: i32) -> Result<bool, sqlx::Error> {
         // EXISTS 返回布尔值
@@ -362,38 +592,302 @@ impl Question {
         Ok(exists)
     }
 
-    // 更新状态
-    pub async fn update_status_by_id(
+    // 从指定的状态更新为新的状态, 领取审核且没有超时的题目不能更新, 审核通过和拒绝使用 tx_review_by_id
+    pub async fn update_status_from(
         pool: &PgPool,
         id: i64,
+        from: &[i16],
         status: i16,
-        approve_id: i64,
-        reject_reason: Option<String>,
+        lock_secs: f64,
     ) -> Result<u64, sqlx::Error> {
-        // 1. 获取当前 UTC 时间用于更新 approve_at
-        let now = Utc::now();
+        let result = sqlx::query(
+            r#"
+            UPDATE question
+            SET status = $3,
+                review_claim_id = NULL,
+                review_claim_at = NULL,
+                updated_at = NOW()
+            WHERE id = $1
+              AND status = ANY($2)
+              AND (review_claim_id IS NULL OR review_claim_at < NOW() - make_interval(secs => $4))
+            "#,
+        )
+        .bind(id)
+        .bind(from)
+        .bind(status)
+        .bind(lock_secs)
+        .execute(pool)
+        .await?;
 
+        Ok(result.rows_affected())
+    }
+
+    /// 待审核队列, 按创建时间从早到晚
+    /// 排除被其他审核人领取并且还在锁定时间内的题目, root_id 不为空时只查询该教材节点下的题目
+    pub async fn find_review_queue(
+        pool: &PgPool,
+        reviewer_id: i64,
+        root_id: Option<i32>,
+        lock_secs: f64,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            WITH RECURSIVE sub AS (
+                SELECT id FROM textbook WHERE id = $2
+                UNION ALL
+                SELECT t.id FROM textbook t JOIN sub ON t.parent_id = sub.id
+            )
+            SELECT *
+            FROM question
+            WHERE status = $1
+              AND (review_claim_id IS NULL OR review_claim_id = $3
+                OR review_claim_at < NOW() - make_interval(secs => $4))
+              AND ($2::INT IS NULL OR question_cate_id IN (
+                SELECT qc.id
+                FROM question_cate qc
+                JOIN chapter_knowledge ck ON ck.id = qc.related_id
+                WHERE ck.chapter_id IN (SELECT id FROM sub) OR ck.knowledge_id IN (SELECT id FROM sub)
+              ))
+            ORDER BY created_at, id
+            LIMIT $5
+            "#,
+        )
+        .bind(QuestionStatus::Pending as i16)
+        .bind(root_id)
+        .bind(reviewer_id)
+        .bind(lock_secs)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 领取待审核的题目, 已经被其他审核人领取并且还在锁定时间内的不能领取
+    pub async fn claim_review(
+        pool: &PgPool,
+        id: i64,
+        reviewer_id: i64,
+        lock_secs: f64,
+    ) -> Result<u64, sqlx::Error> {
         let result = sqlx::query(
             r#"
-        UPDATE question
-        SET status = $2, 
-            approve_id = $3, 
-            reject_reason = $4, 
-            approve_at = $5
-        WHERE id = $1
-        "#,
+            UPDATE question
+            SET review_claim_id = $2, review_claim_at = NOW()
+            WHERE id = $1
+              AND status = $3
+              AND (review_claim_id IS NULL OR review_claim_id = $2
+                OR review_claim_at < NOW() - make_interval(secs => $4))
+            "#,
+        )
+        .bind(id)
+        .bind(reviewer_id)
+        .bind(QuestionStatus::Pending as i16)
+        .bind(lock_secs)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 释放自己领取的题目
+    pub async fn release_review(
+        pool: &PgPool,
+        id: i64,
+        reviewer_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question
+            SET review_claim_id = NULL, review_claim_at = NULL
+            WHERE id = $1 AND review_claim_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(reviewer_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 审核待审核的题目, 被其他审核人领取并且还在锁定时间内的不能审核, 审核后释放领取
+    pub async fn tx_review_by_id(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+        status: i16,
+        reviewer_id: i64,
+        reject_reason: Option<String>,
+        lock_secs: f64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question
+            SET status = $2,
+                approve_id = $3,
+                reject_reason = $4,
+                approve_at = NOW(),
+                review_claim_id = NULL,
+                review_claim_at = NULL,
+                updated_at = NOW()
+            WHERE id = $1
+              AND status = $5
+              AND (review_claim_id IS NULL OR review_claim_id = $3
+                OR review_claim_at < NOW() - make_interval(secs => $6))
+            "#,
         )
         .bind(id)
         .bind(status)
-        .bind(approve_id)
+        .bind(reviewer_id)
         .bind(reject_reason)
-        .bind(now)
-        .execute(pool)
+        .bind(QuestionStatus::Pending as i16)
+        .bind(lock_secs)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 锁定题目, 同一个题目的修订稿操作依次执行
+    pub async fn tx_lock_by_id(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM question WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await
+    }
+
+    // 题目还没有提交的修订稿, 草稿和被拒绝的都可以继续修改
+    pub async fn tx_find_open_revision_id(
+        tx: &mut Transaction<'_, Postgres>,
+        question_id: i64,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT id
+            FROM question
+            WHERE revision_of = $1 AND status IN ($2, $3)
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(question_id)
+        .bind(QuestionStatus::Draft as i16)
+        .bind(QuestionStatus::Rejected as i16)
+        .fetch_optional(&mut **tx)
+        .await
+    }
+
+    // 修订稿对应的原题, 不是修订稿时返回 None
+    pub async fn tx_find_revision_of(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT revision_of FROM question WHERE id = $1 AND revision_of > 0")
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await
+    }
+
+    // 将修订稿的内容更新到原题并归档修订稿, 题型, 作者和审核信息保持原题的
+    // 原题已经归档时不更新, 返回 0
+    pub async fn tx_apply_revision(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+        revision_of: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question o
+            SET question_type_id = r.question_type_id,
+                question_tag_ids = r.question_tag_ids,
+                question_dimension_ids = r.question_dimension_ids,
+                source = r.source,
+                original_name = r.original_name,
+                title = r.title,
+                content_plain = r.content_plain,
+                comment = r.comment,
+                difficulty_level = r.difficulty_level,
+                images = r.images,
+                options = r.options,
+                options_layout = r.options_layout,
+                answer = r.answer,
+                knowledge = r.knowledge,
+                analysis = r.analysis,
+                process = r.process,
+                steps = r.steps,
+                remark = r.remark,
+                remark_ext = r.remark_ext,
+                updated_at = NOW()
+            FROM question r
+            WHERE r.id = $1 AND o.id = $2 AND o.status <> $3
+            "#,
+        )
+        .bind(id)
+        .bind(revision_of)
+        .bind(QuestionStatus::Archived as i16)
+        .execute(&mut **tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(0);
+        }
+
+        // 内容已经在原题中, 修订稿不再作为单独的题目展示
+        sqlx::query(
+            "UPDATE question SET status = $2, applied_at = NOW(), updated_at = NOW() WHERE id = $1",
+        )
+        .bind(id)
+        .bind(QuestionStatus::Archived as i16)
+        .execute(&mut **tx)
         .await?;
 
         Ok(result.rows_affected())
     }
 
+    // 修订稿是否已经审核通过并更新到原题, 只有更新到原题时才记录更新时间, 其它方式归档的修订稿不算
+    pub async fn is_revision_applied(pool: &PgPool, id: i64) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM question
+                WHERE id = $1 AND revision_of > 0 AND applied_at IS NOT NULL
+            )
+            "#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+    }
+
+    // 标记修订稿对应的原题
+    pub async fn tx_update_revision_of(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+        revision_of: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE question SET revision_of = $2 WHERE id = $1")
+            .bind(id)
+            .bind(revision_of)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 题目的审核领取信息
+    pub async fn find_review_claims(
+        pool: &PgPool,
+        ids: &[i64],
+    ) -> Result<Vec<ReviewClaim>, sqlx::Error> {
+        sqlx::query_as::<_, ReviewClaim>(
+            "SELECT id, status, review_claim_id, review_claim_at FROM question WHERE id = ANY($1)",
+        )
+        .bind(ids)
+        .fetch_all(pool)
+        .await
+    }
+
     // 母题下面变式题数量
     pub async fn count_similar_by_params(
         pool: &PgPool,
@@ -411,6 +905,7 @@ impl Question {
             INNER JOIN question_similar qs ON q.id = qs.child_id
             WHERE qs.question_id = $1
               AND q.status = $2
+              AND q.revision_of = 0
               AND q.question_cate_id = $3
               AND ($4 IS NULL OR q.question_type_id = $4)
               AND ($5 IS NULL OR q.question_tag_ids @> $6)
@@ -448,6 +943,7 @@ impl Question {
             INNER JOIN question_similar qs ON q.id = qs.child_id
             WHERE qs.question_id = $1
               AND q.status = $2
+              AND q.revision_of = 0
               AND q.question_cate_id = $3
               AND ($4 IS NULL OR q.question_type_id = $4)
               AND ($5 IS NULL OR q.question_tag_ids @> $6)
@@ -471,9 +967,12 @@ impl Question {
     }
 
     /// 根据 ID 删除记录
-    pub async fn delete(pool: &PgPool, id: i64) -> Result<u64, sqlx::Error> {
+    pub async fn delete<'e, E>(executor: E, id: i64) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let result = sqlx::query!("DELETE FROM question WHERE id = $1", id)
-            .execute(pool)
+            .execute(executor)
             .await?;
         Ok(result.rows_affected())
     }
@@ -1,5 +1,5 @@
 use crate::api::question_cate::CreateQuestionCateReq;
-use sqlx::{FromRow, PgPool};
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction};
 
 /// 题型
 
@@ -53,13 +53,108 @@ impl QuestionCate {
     }
 
     // 通过关联标识获取题型列表
-    pub async fn find_all_by_related_ids(
-        pool: &PgPool,
+    pub async fn find_all_by_related_ids<'e, E>(
+        executor: E,
         related_ids: Vec<i32>,
-    ) -> Result<Vec<Self>, sqlx::Error> {
+    ) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         sqlx::query_as::<_, Self>("SELECT * FROM question_cate WHERE related_id = ANY($1)")
             .bind(related_ids)
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await
     }
+
+    // 根据主键批量删除
+    pub async fn tx_delete_by_ids(
+        tx: &mut Transaction<'_, Postgres>,
+        ids: &[i32],
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM question_cate WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&mut **tx)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // 根据主键批量删除没有题目的题型, 还有题目的题型保留, 题目仍然指向有效的题型
+    pub async fn tx_delete_unused_by_ids(
+        tx: &mut Transaction<'_, Postgres>,
+        ids: &[i32],
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM question_cate c
+            WHERE c.id = ANY($1)
+              AND NOT EXISTS (SELECT 1 FROM question q WHERE q.question_cate_id = c.id)
+            "#,
+        )
+        .bind(ids)
+        .execute(&mut **tx)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    // 根据主键查询
+    pub async fn find_by_id(pool: &PgPool, id: i32) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM question_cate WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    // 修改名称, 题型标识跟随名称一起更新
+    pub async fn update_label(pool: &PgPool, id: i32, label: &str) -> Result<u64, sqlx::Error> {
+        let key = format!("{:x}", md5::compute(label))[..10].to_string();
+
+        let result = sqlx::query("UPDATE question_cate SET label = $2, key = $3 WHERE id = $1")
+            .bind(id)
+            .bind(label)
+            .bind(&key)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 修改排序
+    pub async fn update_sort_order<'e, E>(
+        executor: E,
+        id: i32,
+        sort_order: i32,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query("UPDATE question_cate SET sort_order = $2 WHERE id = $1")
+            .bind(id)
+            .bind(sort_order)
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 移动到新的关联标识下, 排在最后
+    pub async fn update_related_id(
+        pool: &PgPool,
+        id: i32,
+        related_id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question_cate SET
+                related_id = $2,
+                sort_order = (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM question_cate WHERE related_id = $2)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(related_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
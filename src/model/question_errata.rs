@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+/// 题目勘误, 使用者反馈题目中的错误
+
+// 勘误处理状态
+#[derive(PartialEq)]
+#[repr(i16)]
+pub enum ErrataStatus {
+    Open = 1,     // 1: 待处理
+    Accepted = 2, // 2: 已采纳, 生成修订稿
+    Rejected = 3, // 3: 已驳回
+    Fixed = 4,    // 4: 已修复
+}
+
+impl ErrataStatus {
+    pub fn desc(code: i16) -> String {
+        match code {
+            1 => "待处理".to_string(),
+            2 => "已采纳".to_string(),
+            3 => "已驳回".to_string(),
+            4 => "已修复".to_string(),
+            _ => "未知状态".to_string(),
+        }
+    }
+}
+
+#[derive(FromRow)]
+#[allow(dead_code)]
+pub struct QuestionErrata {
+    pub id: i64,
+    pub question_id: i64,
+    pub field: Option<String>,      // 出错的字段
+    pub content: String,            // 错误描述
+    pub suggestion: Option<String>, // 修改建议
+    pub reporter_id: i64,           // 反馈人
+    pub status: i16,                // 处理状态
+    pub reply: Option<String>,      // 处理意见
+    pub handler_id: i64,            // 处理人
+    pub handled_at: Option<DateTime<Utc>>,
+    pub revision_id: i64, // 采纳后生成的修订稿
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl QuestionErrata {
+    pub async fn insert(
+        pool: &PgPool,
+        question_id: i64,
+        field: Option<String>,
+        content: &str,
+        suggestion: Option<String>,
+        reporter_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO question_errata (question_id, field, content, suggestion, reporter_id, status)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#,
+        )
+        .bind(question_id)
+        .bind(field)
+        .bind(content)
+        .bind(suggestion)
+        .bind(reporter_id)
+        .bind(ErrataStatus::Open as i16)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: i64) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM question_errata WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // 勘误数量, 条件为空时不限制
+    pub async fn count(
+        pool: &PgPool,
+        status: Option<i16>,
+        question_id: Option<i64>,
+        reporter_id: Option<i64>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM question_errata
+            WHERE ($1::SMALLINT IS NULL OR status = $1)
+              AND ($2::BIGINT IS NULL OR question_id = $2)
+              AND ($3::BIGINT IS NULL OR reporter_id = $3)
+            "#,
+        )
+        .bind(status)
+        .bind(question_id)
+        .bind(reporter_id)
+        .fetch_one(pool)
+        .await
+    }
+
+    // 勘误列表, 先反馈的排在前面
+    pub async fn list(
+        pool: &PgPool,
+        status: Option<i16>,
+        question_id: Option<i64>,
+        reporter_id: Option<i64>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT *
+            FROM question_errata
+            WHERE ($1::SMALLINT IS NULL OR status = $1)
+              AND ($2::BIGINT IS NULL OR question_id = $2)
+              AND ($3::BIGINT IS NULL OR reporter_id = $3)
+            ORDER BY created_at, id
+            LIMIT $4 OFFSET $5
+            "#,
+        )
+        .bind(status)
+        .bind(question_id)
+        .bind(reporter_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 更新处理状态, 只有原状态一致时才更新, 防止重复处理
+    pub async fn tx_update_status(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+        from_status: i16,
+        status: i16,
+        reply: Option<String>,
+        handler_id: i64,
+        revision_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE question_errata
+            SET status = $3,
+                reply = COALESCE($4, reply),
+                handler_id = $5,
+                handled_at = NOW(),
+                revision_id = CASE WHEN $6 > 0 THEN $6 ELSE revision_id END,
+                updated_at = NOW()
+            WHERE id = $1 AND status = $2
+            "#,
+        )
+        .bind(id)
+        .bind(from_status)
+        .bind(status)
+        .bind(reply)
+        .bind(handler_id)
+        .bind(revision_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
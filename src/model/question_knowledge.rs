@@ -0,0 +1,106 @@
+use sqlx::{Executor, FromRow, PgPool, Postgres, QueryBuilder, Transaction};
+
+/// 题目和知识点节点的多对多关联
+
+// 题目关联的知识点, 带节点名称
+#[allow(dead_code)]
+#[derive(FromRow)]
+pub struct QuestionKnowledgeNode {
+    pub question_id: i64,
+    pub knowledge_id: i32,
+    pub label: String,
+}
+
+pub struct QuestionKnowledge;
+
+impl QuestionKnowledge {
+    // 批量添加关联, 已存在的忽略
+    pub async fn tx_batch_insert(
+        tx: &mut Transaction<'_, Postgres>,
+        pairs: Vec<(i64, i32)>,
+    ) -> Result<u64, sqlx::Error> {
+        if pairs.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder =
+            QueryBuilder::new("INSERT INTO question_knowledge (question_id, knowledge_id) ");
+        query_builder.push_values(pairs, |mut b, (question_id, knowledge_id)| {
+            b.push_bind(question_id).push_bind(knowledge_id);
+        });
+        query_builder.push(" ON CONFLICT (question_id, knowledge_id) DO NOTHING");
+
+        let result = query_builder.build().execute(&mut **tx).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 删除题目所有的知识点关联
+    pub async fn tx_delete_by_question_id(
+        tx: &mut Transaction<'_, Postgres>,
+        question_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM question_knowledge WHERE question_id = $1")
+            .bind(question_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 复制题目的知识点关联到另一个题目
+    pub async fn tx_copy(
+        tx: &mut Transaction<'_, Postgres>,
+        from_id: i64,
+        to_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO question_knowledge (question_id, knowledge_id)
+            SELECT $2, knowledge_id FROM question_knowledge WHERE question_id = $1
+            ON CONFLICT (question_id, knowledge_id) DO NOTHING
+            "#,
+        )
+        .bind(from_id)
+        .bind(to_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 多个题目关联的知识点列表
+    pub async fn find_by_question_ids(
+        pool: &PgPool,
+        question_ids: &[i64],
+    ) -> Result<Vec<QuestionKnowledgeNode>, sqlx::Error> {
+        sqlx::query_as::<_, QuestionKnowledgeNode>(
+            r#"
+            SELECT qk.question_id, qk.knowledge_id, t.label
+            FROM question_knowledge qk
+            JOIN textbook t ON t.id = qk.knowledge_id
+            WHERE qk.question_id = ANY($1)
+            ORDER BY qk.question_id, qk.id
+            "#,
+        )
+        .bind(question_ids)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 多个知识点节点下关联的题目数量
+    pub async fn count_by_knowledge_ids<'e, E>(
+        executor: E,
+        knowledge_ids: &[i32],
+    ) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM question_knowledge WHERE knowledge_id = ANY($1)",
+        )
+        .bind(knowledge_ids)
+        .fetch_one(executor)
+        .await
+    }
+}
@@ -1,7 +1,12 @@
 use chrono::{DateTime, Utc};
-use sqlx::{FromRow, PgPool, QueryBuilder, Transaction};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Transaction};
 
 /// 变式题
+/// 变式题可以继续有自己的变式题, 组成一棵树, 每个题目最多只有一个父题
+
+// 递归查询时防止异常数据成环导致无限递归
+const MAX_DEPTH: i32 = 64;
+
 #[allow(dead_code)]
 #[derive(FromRow)]
 pub struct QuestionSimilar {
@@ -60,4 +65,168 @@ impl QuestionSimilar {
         query_builder.build().execute(&mut **tx).await?;
         Ok(())
     }
+
+    // 事务中建立题目关联
+    pub async fn tx_insert(
+        tx: &mut Transaction<'_, Postgres>,
+        question_id: i64,
+        child_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO question_similar (question_id, child_id)
+            VALUES ($1, $2)
+            ON CONFLICT (question_id, child_id) DO NOTHING
+            "#,
+        )
+        .bind(question_id)
+        .bind(child_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 解除题目关联
+    pub async fn delete(
+        pool: &PgPool,
+        question_id: i64,
+        child_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM question_similar WHERE question_id = $1 AND child_id = $2")
+                .bind(question_id)
+                .bind(child_id)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 解除题目和父题的关联
+    pub async fn tx_delete_by_child_id(
+        tx: &mut Transaction<'_, Postgres>,
+        child_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM question_similar WHERE child_id = $1")
+            .bind(child_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 解除题目作为父题和变式题的所有关联, 删除题目时使用
+    pub async fn tx_delete_by_question_id(
+        tx: &mut Transaction<'_, Postgres>,
+        question_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM question_similar WHERE question_id = $1 OR child_id = $1")
+                .bind(question_id)
+                .execute(&mut **tx)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 将题目的所有变式题改挂到新的父题下, 排除新的父题自己
+    pub async fn tx_update_parent(
+        tx: &mut Transaction<'_, Postgres>,
+        question_id: i64,
+        target_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE question_similar SET question_id = $2 WHERE question_id = $1 AND child_id <> $2",
+        )
+        .bind(question_id)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 题目的父题
+    pub async fn find_parent_id(pool: &PgPool, child_id: i64) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT question_id FROM question_similar WHERE child_id = $1 ORDER BY id LIMIT 1",
+        )
+        .bind(child_id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    // 题目所在变式题树的根节点母题
+    pub async fn find_root_id(pool: &PgPool, id: i64) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            WITH RECURSIVE up AS (
+                SELECT $1::BIGINT AS id, 0 AS depth
+                UNION
+                SELECT qs.question_id, up.depth + 1
+                FROM question_similar qs
+                JOIN up ON qs.child_id = up.id
+                WHERE up.depth < $2
+            )
+            SELECT id FROM up ORDER BY depth DESC LIMIT 1
+            "#,
+        )
+        .bind(id)
+        .bind(MAX_DEPTH)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// 检查 id 是否是 ancestor_id 直接或间接的变式题
+    /// 把 a 挂到 b 下面之前检查 b 是否是 a 的变式题, 如果返回 true 说明会形成环
+    pub async fn is_descendant(
+        pool: &PgPool,
+        ancestor_id: i64,
+        id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>(
+            r#"
+            WITH RECURSIVE down AS (
+                SELECT child_id AS id, 1 AS depth
+                FROM question_similar
+                WHERE question_id = $1
+                UNION
+                SELECT qs.child_id, down.depth + 1
+                FROM question_similar qs
+                JOIN down ON qs.question_id = down.id
+                WHERE down.depth < $3
+            )
+            SELECT EXISTS (SELECT 1 FROM down WHERE id = $2)
+            "#,
+        )
+        .bind(ancestor_id)
+        .bind(id)
+        .bind(MAX_DEPTH)
+        .fetch_one(pool)
+        .await
+    }
+
+    // 母题下所有层级的变式题关联
+    pub async fn find_tree(pool: &PgPool, root_id: i64) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            WITH RECURSIVE down AS (
+                SELECT id, question_id, child_id, created_at, 1 AS depth
+                FROM question_similar
+                WHERE question_id = $1
+                UNION
+                SELECT qs.id, qs.question_id, qs.child_id, qs.created_at, down.depth + 1
+                FROM question_similar qs
+                JOIN down ON qs.question_id = down.child_id
+                WHERE down.depth < $2
+            )
+            SELECT id, question_id, child_id, created_at FROM down ORDER BY id
+            "#,
+        )
+        .bind(root_id)
+        .bind(MAX_DEPTH)
+        .fetch_all(pool)
+        .await
+    }
 }
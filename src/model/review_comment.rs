@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+/// 审核评论, 题目和试卷共用
+
+// 评论对象
+#[repr(i16)]
+pub enum CommentTarget {
+    Question = 1, // 1: 题目
+    Paper = 2,    // 2: 试卷
+}
+
+#[derive(FromRow)]
+#[allow(dead_code)]
+pub struct ReviewComment {
+    pub id: i64,
+    pub target_type: i16,
+    pub target_id: i64,
+    pub parent_id: i64,         // 0 表示第一层评论
+    pub anchor: Option<String>, // 评论的字段
+    pub author_id: i64,         // 评论人
+    pub content: String,        // 评论内容
+    pub is_resolved: bool,      // 是否已解决
+    pub resolved_id: i64,       // 解决人
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 未解决的评论数量
+#[derive(FromRow)]
+pub struct UnresolvedCount {
+    pub target_id: i64,
+    pub count: i64,
+}
+
+impl ReviewComment {
+    pub async fn insert(
+        pool: &PgPool,
+        target_type: i16,
+        target_id: i64,
+        parent_id: i64,
+        anchor: Option<String>,
+        author_id: i64,
+        content: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO review_comment (target_type, target_id, parent_id, anchor, author_id, content)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#,
+        )
+        .bind(target_type)
+        .bind(target_id)
+        .bind(parent_id)
+        .bind(anchor)
+        .bind(author_id)
+        .bind(content)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: i64) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM review_comment WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // 对象的所有评论, 按时间先后
+    pub async fn find_by_target(
+        pool: &PgPool,
+        target_type: i16,
+        target_id: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT *
+            FROM review_comment
+            WHERE target_type = $1 AND target_id = $2
+            ORDER BY created_at, id
+            "#,
+        )
+        .bind(target_type)
+        .bind(target_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    // 更新第一层评论的解决状态
+    pub async fn update_resolved(
+        pool: &PgPool,
+        id: i64,
+        is_resolved: bool,
+        operator_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE review_comment
+            SET is_resolved = $2,
+                resolved_id = CASE WHEN $2 THEN $3 ELSE 0 END,
+                resolved_at = CASE WHEN $2 THEN NOW() ELSE NULL END,
+                updated_at = NOW()
+            WHERE id = $1 AND parent_id = 0
+            "#,
+        )
+        .bind(id)
+        .bind(is_resolved)
+        .bind(operator_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 删除评论以及下面的回复
+    pub async fn delete(pool: &PgPool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM review_comment WHERE id = $1 OR parent_id = $1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 删除对象的所有评论
+    pub async fn tx_delete_by_target(
+        tx: &mut Transaction<'_, Postgres>,
+        target_type: i16,
+        target_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM review_comment WHERE target_type = $1 AND target_id = $2")
+                .bind(target_type)
+                .bind(target_id)
+                .execute(&mut **tx)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 多个对象未解决的第一层评论数量
+    pub async fn count_unresolved(
+        pool: &PgPool,
+        target_type: i16,
+        target_ids: &[i64],
+    ) -> Result<Vec<UnresolvedCount>, sqlx::Error> {
+        sqlx::query_as::<_, UnresolvedCount>(
+            r#"
+            SELECT target_id, COUNT(*) AS count
+            FROM review_comment
+            WHERE target_type = $1 AND target_id = ANY($2) AND parent_id = 0 AND NOT is_resolved
+            GROUP BY target_id
+            "#,
+        )
+        .bind(target_type)
+        .bind(target_ids)
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool, Type};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction, Type};
 
 /// 任务管理
 
 #[derive(FromRow)]
+#[allow(dead_code)]
 pub struct Task {
     pub id: i64,
     pub question_cate_id: i64,
@@ -15,24 +18,54 @@ pub struct Task {
     pub author_id: i64,
     pub status: i16,
     pub result: Option<String>,
+    pub kind: String,                // 任务类型名称, 对应注册的任务
+    pub params: Json<Value>,         // 任务参数
+    pub output: Option<Json<Value>>, // 任务结构化结果
+    pub worker_id: Option<String>,   // 执行进程标识
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub heartbeat_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub attempts: i32,                                    // 已执行次数
+    pub run_after: Option<chrono::DateTime<chrono::Utc>>, // 重试等待
+    pub cancel_requested: bool,                           // 执行中的任务请求取消
+    pub progress_phase: String,                           // 执行进度, 当前阶段
+    pub progress_done: i32,                               // 已处理数量
+    pub progress_total: i32,                              // 总数量, 为 0 时表示数量未知
     // 创建更新时间
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+// 新建任务, 题目上传以外的任务不需要题型教材等字段, 使用默认值
+#[derive(Default)]
+pub struct NewTask {
+    pub kind: String,
+    pub task_type: i16,
+    pub name: String,
+    pub author_id: i64,
+    pub params: Value,
+    pub question_cate_id: i64,
+    pub textbook_id: i32,
+    pub url: String,
+    pub email: String,
+}
+
+// 新任务通知的频道, 内容为任务类型名称
+pub const TASK_CHANNEL: &str = "task_created";
+
 #[derive(Serialize, Deserialize, Type, PartialEq)]
 #[repr(i16)]
 pub enum TaskType {
     UploadQuestion = 1, // 题目上传
 }
 
-#[derive(Serialize, Deserialize, Type, PartialEq)]
+#[derive(Serialize, Deserialize, Type, PartialEq, Clone, Copy)]
 #[repr(i16)]
 pub enum TaskStatus {
-    Waiting = 1, // 待处理
-    Running = 2, // 处理中
-    Success = 3, // 处理成功
-    Failed = 10, // 处理失败
+    Waiting = 1,   // 待处理
+    Running = 2,   // 处理中
+    Success = 3,   // 处理成功
+    Failed = 10,   // 处理失败
+    Canceled = 11, // 已取消
 }
 
 impl TaskStatus {
@@ -42,61 +75,112 @@ impl TaskStatus {
             2 => "处理中",
             3 => "处理成功",
             10 => "处理失败",
+            11 => "已取消",
             _ => "未知状态",
         }
     }
 }
 
 impl Task {
-    pub async fn insert(
-        pool: &PgPool,
-        question_cate_id: i64,
-        task_type: i16,
-        name: &String,
-        author_id: i64,
-        url: &String,
-        email: &String,
-        textbook_id: i32,
-    ) -> Result<i64, sqlx::Error> {
+    // 添加任务, 同时通知常驻的执行进程
+    pub async fn insert(pool: &PgPool, task: &NewTask) -> Result<i64, sqlx::Error> {
         sqlx::query_scalar(
             r#"
-        INSERT INTO task (question_cate_id, task_type, name, url, author_id, status, email, textbook_id)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        RETURNING id
+        WITH row AS (
+            INSERT INTO task (question_cate_id, task_type, name, url, author_id, status, email, textbook_id, kind, params)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id, kind
+        )
+        SELECT row.id, pg_notify($11, row.kind) FROM row
         "#,
         )
-            .bind(question_cate_id)
-            .bind(task_type)
-            .bind(name)
-            .bind(url)
-            .bind(author_id)
-            .bind(TaskStatus::Waiting as i16)
-            .bind(email)
-            .bind(textbook_id)
-            .fetch_one(pool)
-            .await
+        .bind(task.question_cate_id)
+        .bind(task.task_type)
+        .bind(&task.name)
+        .bind(&task.url)
+        .bind(task.author_id)
+        .bind(TaskStatus::Waiting as i16)
+        .bind(&task.email)
+        .bind(task.textbook_id)
+        .bind(&task.kind)
+        .bind(Json(&task.params))
+        .bind(TASK_CHANNEL)
+        .fetch_one(pool)
+        .await
     }
 
-    pub async fn update_by_id(
+    // 定时添加任务, 同一类型在间隔时间内已经添加过的不再添加
+    // 多个执行进程同时调度时通过按类型的事务级咨询锁依次检查, 只添加一次
+    pub async fn insert_scheduled(
         pool: &PgPool,
-        id: &i64,
+        task: &NewTask,
+        interval_secs: f64,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        // 拿到锁后的查询能看到其它进程已经提交的任务
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('task_schedule:' || $1))")
+            .bind(&task.kind)
+            .execute(&mut *tx)
+            .await?;
+
+        let id: Option<i64> = sqlx::query_scalar(
+            r#"
+        WITH row AS (
+            INSERT INTO task (name, author_id, status, kind, params)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT EXISTS (
+                SELECT 1 FROM task WHERE kind = $4 AND created_at > NOW() - make_interval(secs => $6)
+            )
+            RETURNING id, kind
+        )
+        SELECT row.id, pg_notify($7, row.kind) FROM row
+        "#,
+        )
+        .bind(&task.name)
+        .bind(task.author_id)
+        .bind(TaskStatus::Waiting as i16)
+        .bind(&task.kind)
+        .bind(Json(&task.params))
+        .bind(interval_secs)
+        .bind(TASK_CHANNEL)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    // 任务执行完成, 同时保存可读结果和结构化结果
+    // 只有仍由当前进程持有的任务才更新, 超时被重新领取的任务不覆盖
+    pub async fn finish<'e, E>(
+        executor: E,
+        id: i64,
+        worker_id: &str,
         status: i16,
         result: String,
-    ) -> Result<u64, sqlx::Error> {
+        output: Option<Value>,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let result = sqlx::query(
             r#"
         UPDATE task
-        SET status = $2, result = $3, updated_at = NOW()
-        WHERE id = $1
+        SET status = $3, result = $4, output = $5, heartbeat_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND worker_id = $2 AND status = $6
         "#,
         )
         .bind(id)
+        .bind(worker_id)
         .bind(status)
         .bind(result)
-        .execute(pool)
+        .bind(output.map(Json))
+        .bind(TaskStatus::Running as i16)
+        .execute(executor)
         .await?;
 
-        // rows_affected() 返回受影响的行数
         Ok(result.rows_affected())
     }
 
@@ -149,18 +233,326 @@ impl Task {
         .await
     }
 
-    // 所有待执行的任务列表
-    pub async fn get_waiting_list(pool: &PgPool, task_type: i16) -> Result<Vec<Self>, sqlx::Error> {
+    // 领取一个待执行的任务, 先创建的先执行
+    // 已经被其他进程锁定的行直接跳过, 多个进程同时领取不会拿到同一个任务
+    pub async fn claim_next(
+        pool: &PgPool,
+        kinds: &[&str],
+        worker_id: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
         sqlx::query_as::<_, Self>(
             r#"
-        SELECT *
-        FROM task
-        WHERE status = 1
-        AND task_type = $1
+        UPDATE task
+        SET status = $3, worker_id = $2, attempts = attempts + 1,
+            progress_phase = '', progress_done = 0, progress_total = 0,
+            started_at = NOW(), heartbeat_at = NOW(), updated_at = NOW()
+        WHERE id = (
+            SELECT id
+            FROM task
+            WHERE status = $4
+            AND kind = ANY($1)
+            AND (run_after IS NULL OR run_after <= NOW())
+            ORDER BY id
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
         "#,
         )
-        .bind(task_type)
+        .bind(kinds)
+        .bind(worker_id)
+        .bind(TaskStatus::Running as i16)
+        .bind(TaskStatus::Waiting as i16)
+        .fetch_optional(pool)
+        .await
+    }
+
+    // 更新执行进度, 只更新当前进程持有的执行中任务
+    pub async fn update_progress<'e, E>(
+        executor: E,
+        id: i64,
+        worker_id: &str,
+        phase: &str,
+        done: i32,
+        total: i32,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+        UPDATE task
+        SET progress_phase = $3, progress_done = $4, progress_total = $5, updated_at = NOW()
+        WHERE id = $1 AND worker_id = $2 AND status = $6
+        "#,
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(phase)
+        .bind(done)
+        .bind(total)
+        .bind(TaskStatus::Running as i16)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 执行中的任务心跳, 返回是否请求取消, 任务已经不由当前进程持有时返回空
+    pub async fn heartbeat<'e, E>(
+        executor: E,
+        id: i64,
+        worker_id: &str,
+    ) -> Result<Option<bool>, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar(
+            r#"
+        UPDATE task
+        SET heartbeat_at = NOW()
+        WHERE id = $1 AND worker_id = $2 AND status = $3
+        RETURNING cancel_requested
+        "#,
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(TaskStatus::Running as i16)
+        .fetch_optional(executor)
+        .await
+    }
+
+    // 心跳超时的执行中任务重新等待执行, 执行进程异常退出时任务不会一直停在执行中
+    // 已经请求取消的直接取消, 执行次数达到上限的置为失败, 返回任务主键和新的状态
+    pub async fn recover_expired(
+        pool: &PgPool,
+        kinds: &[&str],
+        lease_secs: f64,
+        max_attempts: i32,
+    ) -> Result<Vec<(i64, i16)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+        UPDATE task
+        SET status = CASE
+                WHEN cancel_requested THEN $6
+                WHEN attempts >= $5 THEN $7
+                ELSE $2
+            END,
+            worker_id = NULL,
+            result = CASE
+                WHEN cancel_requested THEN '已取消'
+                WHEN attempts >= $5 THEN '执行超时, 已达到最大执行次数'
+                ELSE '执行超时, 重新等待执行'
+            END,
+            updated_at = NOW()
+        WHERE status = $3
+        AND kind = ANY($1)
+        AND COALESCE(heartbeat_at, updated_at) < NOW() - make_interval(secs => $4)
+        RETURNING id, status
+        "#,
+        )
+        .bind(kinds)
+        .bind(TaskStatus::Waiting as i16)
+        .bind(TaskStatus::Running as i16)
+        .bind(lease_secs)
+        .bind(max_attempts)
+        .bind(TaskStatus::Canceled as i16)
+        .bind(TaskStatus::Failed as i16)
         .fetch_all(pool)
         .await
     }
+
+    pub async fn find_by_id(pool: &PgPool, id: i64) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM task WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // 失败或者取消的任务重新等待执行, 延迟秒数大于 0 时在延迟之后才能领取
+    pub async fn tx_retry(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+        delay_secs: f64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+        UPDATE task
+        SET status = $2,
+            run_after = CASE WHEN $3 > 0 THEN NOW() + make_interval(secs => $3) ELSE NULL END,
+            cancel_requested = FALSE,
+            worker_id = NULL,
+            result = NULL,
+            output = NULL,
+            updated_at = NOW()
+        WHERE id = $1 AND status IN ($4, $5)
+        "#,
+        )
+        .bind(id)
+        .bind(TaskStatus::Waiting as i16)
+        .bind(delay_secs)
+        .bind(TaskStatus::Failed as i16)
+        .bind(TaskStatus::Canceled as i16)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 取消待执行的任务
+    pub async fn tx_cancel_waiting(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+        UPDATE task
+        SET status = $2, result = '已取消', updated_at = NOW()
+        WHERE id = $1 AND status = $3
+        "#,
+        )
+        .bind(id)
+        .bind(TaskStatus::Canceled as i16)
+        .bind(TaskStatus::Waiting as i16)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 执行中的任务请求取消, 由执行进程心跳时检查后停止
+    pub async fn tx_request_cancel(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+        UPDATE task
+        SET cancel_requested = TRUE, updated_at = NOW()
+        WHERE id = $1 AND status = $2
+        "#,
+        )
+        .bind(id)
+        .bind(TaskStatus::Running as i16)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 删除没有在执行的任务
+    pub async fn tx_delete(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM task WHERE id = $1 AND status <> $2")
+            .bind(id)
+            .bind(TaskStatus::Running as i16)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 关联在教材节点或者题型下的任务数量
+    pub async fn count_by_textbook_or_cate<'e, E>(
+        executor: E,
+        textbook_ids: &[i32],
+        question_cate_ids: &[i64],
+    ) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM task WHERE textbook_id = ANY($1) OR question_cate_id = ANY($2)",
+        )
+        .bind(textbook_ids)
+        .bind(question_cate_ids)
+        .fetch_one(executor)
+        .await
+    }
+
+    // 题型下的任务数量
+    pub async fn count_by_cate_id(
+        pool: &PgPool,
+        question_cate_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM task WHERE question_cate_id = $1")
+            .bind(question_cate_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    // 将题型下的任务改挂到新的题型, 合并题型时使用
+    pub async fn tx_update_cate_id(
+        tx: &mut Transaction<'_, Postgres>,
+        question_cate_id: i64,
+        target_id: i64,
+    ) -> Result<u64, sqlx::Error> {
+        // 上传参数里也记录了题型, 两边一起改, 避免任务执行时仍然写入旧题型
+        let result = sqlx::query(
+            r#"
+            UPDATE task
+            SET question_cate_id = $2,
+                params = CASE WHEN params ? 'questionCateId'
+                    THEN jsonb_set(params, '{questionCateId}', to_jsonb($2)) ELSE params END,
+                updated_at = NOW()
+            WHERE question_cate_id = $1 OR (params->>'questionCateId')::bigint = $1
+            "#,
+        )
+        .bind(question_cate_id)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 将教材节点下的任务改挂到新的节点
+    pub async fn tx_update_textbook_id(
+        tx: &mut Transaction<'_, Postgres>,
+        textbook_ids: &[i32],
+        target_id: i32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE task
+            SET textbook_id = $2,
+                params = CASE WHEN params ? 'textbookId'
+                    THEN jsonb_set(params, '{textbookId}', to_jsonb($2)) ELSE params END,
+                updated_at = NOW()
+            WHERE textbook_id = ANY($1) OR (params->>'textbookId')::int = ANY($1)
+            "#,
+        )
+        .bind(textbook_ids)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 题型被删除后, 还未执行的任务直接置为失败, 避免把题目写入已经不存在的题型
+    pub async fn tx_fail_waiting_by_cate(
+        tx: &mut Transaction<'_, Postgres>,
+        question_cate_ids: &[i64],
+        result: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE task
+            SET status = $2, result = $3, updated_at = NOW()
+            WHERE (question_cate_id = ANY($1) OR (params->>'questionCateId')::bigint = ANY($1))
+              AND status = $4
+            "#,
+        )
+        .bind(question_cate_ids)
+        .bind(TaskStatus::Failed as i16)
+        .bind(result)
+        .bind(TaskStatus::Waiting as i16)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
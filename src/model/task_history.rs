@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, FromRow, PgPool, Postgres};
+
+/// 任务操作记录
+
+// 操作类型
+pub const ACTION_CREATE: &str = "create";
+pub const ACTION_START: &str = "start";
+pub const ACTION_SUCCESS: &str = "success";
+pub const ACTION_FAILED: &str = "failed";
+pub const ACTION_CANCELED: &str = "canceled";
+pub const ACTION_RECOVER: &str = "recover";
+pub const ACTION_RETRY: &str = "retry";
+pub const ACTION_CANCEL: &str = "cancel";
+pub const ACTION_DELETE: &str = "delete";
+
+#[allow(dead_code)]
+#[derive(FromRow)]
+pub struct TaskHistory {
+    pub id: i64,
+    pub task_id: i64,
+    pub action: String,
+    pub operator_id: i64,          // 操作人, 0 表示执行进程
+    pub worker_id: Option<String>, // 执行进程标识
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TaskHistory {
+    pub async fn insert<'e, E>(
+        executor: E,
+        task_id: i64,
+        action: &str,
+        operator_id: i64,
+        worker_id: Option<&str>,
+        message: Option<&str>,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO task_history (task_id, action, operator_id, worker_id, message)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(task_id)
+        .bind(action)
+        .bind(operator_id)
+        .bind(worker_id)
+        .bind(message)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // 任务的操作记录, 按时间先后
+    pub async fn find_by_task_id(pool: &PgPool, task_id: i64) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT * FROM task_history WHERE task_id = $1 ORDER BY created_at, id",
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await
+    }
+}
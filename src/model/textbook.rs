@@ -1,5 +1,5 @@
 use crate::api::textbook::CreateTextbookReq;
-use sqlx::{Executor, FromRow, PgPool, Postgres};
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction};
 
 // 教材信息
 // 如果要支持事务和非事务的方式查询, 可以参考这个写法, 实际上大部分是不需要关注事务的
@@ -106,11 +106,32 @@ impl Textbook {
         .await
     }
 
+    /// 仅修改排序
+    pub async fn update_sort_order<'e, E>(
+        executor: E,
+        id: i32,
+        sort_order: i32,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query("UPDATE textbook SET sort_order = $2 WHERE id = $1")
+            .bind(id)
+            .bind(sort_order)
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// 删除记录
     /// 返回 Result<()> 或受影响的行数
-    pub async fn delete(pool: &PgPool, id: i32) -> Result<u64, sqlx::Error> {
+    pub async fn delete<'e, E>(executor: E, id: i32) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let result = sqlx::query!("DELETE FROM textbook WHERE id = $1", id)
-            .execute(pool)
+            .execute(executor)
             .await?;
 
         Ok(result.rows_affected())
@@ -124,14 +145,11 @@ impl Textbook {
             .await
     }
 
-    /// 场景：在指定目录下根据 parent_id 查找一条数据
-    pub async fn find_one_by_parent_id(
-        pool: &PgPool,
-        parent_id: i32,
-    ) -> Result<Option<Self>, sqlx::Error> {
-        sqlx::query_as::<_, Self>("SELECT * FROM textbook WHERE parent_id = $1 LIMIT 1")
-            .bind(parent_id)
-            .fetch_optional(pool)
+    /// 场景：批量根据 id 查找
+    pub async fn find_by_ids(pool: &PgPool, ids: &[i32]) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM textbook WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(pool)
             .await
     }
 
@@ -181,10 +199,13 @@ impl Textbook {
     }
 
     // 获取父级标识下面的层级, 没有控制层级
-    pub async fn find_all_by_parent_id(
-        pool: &PgPool,
+    pub async fn find_all_by_parent_id<'e, E>(
+        executor: E,
         root_id: i32,
-    ) -> Result<Vec<Self>, sqlx::Error> {
+    ) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         // 使用 WITH RECURSIVE 进行递归查询
         let rows = sqlx::query_as::<_, Self>(
             r#"
@@ -205,12 +226,88 @@ impl Textbook {
         "#,
         )
         .bind(root_id)
-        .fetch_all(pool)
+        .fetch_all(executor)
         .await?;
 
         Ok(rows)
     }
 
+    // 锁定节点及其所有子孙节点, 删除和归档时防止并发新增子节点或者移动节点
+    pub async fn tx_lock_subtree(
+        tx: &mut Transaction<'_, Postgres>,
+        id: i32,
+    ) -> Result<Vec<i32>, sqlx::Error> {
+        sqlx::query_scalar::<_, i32>(
+            r#"
+            WITH RECURSIVE tree AS (
+                SELECT id FROM textbook WHERE id = $1
+                UNION ALL
+                SELECT t.id FROM textbook t INNER JOIN tree ON t.parent_id = tree.id
+            )
+            SELECT id FROM textbook WHERE id IN (SELECT id FROM tree) ORDER BY id FOR UPDATE
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut **tx)
+        .await
+    }
+
+    // 节点所在树的根节点
+    pub async fn find_root_id(pool: &PgPool, id: i32) -> Result<Option<i32>, sqlx::Error> {
+        sqlx::query_scalar::<_, i32>(
+            r#"
+            WITH RECURSIVE chain AS (
+                SELECT id, parent_id FROM textbook WHERE id = $1
+                UNION ALL
+                SELECT t.id, t.parent_id FROM textbook t JOIN chain ON t.id = chain.parent_id
+            )
+            SELECT id FROM chain WHERE parent_id IS NULL
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    // 节点所在的版本菜单, 即向上第一个路径类型不是 path_type 的节点
+    // 祖先都是该路径类型时返回最上层的节点
+    pub async fn find_edition_root_id(
+        pool: &PgPool,
+        id: i32,
+        path_type: &str,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        sqlx::query_scalar::<_, i32>(
+            r#"
+            WITH RECURSIVE chain AS (
+                SELECT id, parent_id, path_type, 0 AS level FROM textbook WHERE id = $1
+                UNION ALL
+                SELECT t.id, t.parent_id, t.path_type, chain.level + 1
+                FROM textbook t JOIN chain ON t.id = chain.parent_id
+            )
+            SELECT id FROM chain
+            ORDER BY path_type = $2, CASE WHEN path_type = $2 THEN -level ELSE level END
+            LIMIT 1
+            "#,
+        )
+        .bind(id)
+        .bind(path_type)
+        .fetch_optional(pool)
+        .await
+    }
+
+    // 某个路径类型的所有节点
+    pub async fn find_all_by_path_type(
+        pool: &PgPool,
+        path_type: &str,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT * FROM textbook WHERE path_type = $1 ORDER BY path_depth, sort_order",
+        )
+        .bind(path_type)
+        .fetch_all(pool)
+        .await
+    }
+
     /// 新的父节点是否是后代
     /// 检查 potential_parent_id 是否是当前 target_id 的子孙节点
     /// 如果返回 true，说明会形成环，禁止更新
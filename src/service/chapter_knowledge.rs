@@ -1,15 +1,21 @@
 use crate::api::chapter_knowledge::{
-    ChapterKnowledgeResp, CreateChapterKnowledgeReq, RemoveChapterKnowledgeReq,
+    BatchChapterKnowledgeReq, BatchChapterKnowledgeResp, ChapterKnowledgeCoverageResp,
+    ChapterKnowledgeResp, CoverageBindingResp, CoverageNodeResp, CreateChapterKnowledgeReq,
+    RemoveChapterKnowledgeReq,
 };
 
+use crate::constant::textbook::{MAX_DEPTH, PATH_TYPE_CHAPTER, PATH_TYPE_KNOWLEDGE};
 use crate::model::chapter_knowledge::ChapterKnowledge;
+use crate::model::question::QuestionStatus;
 use crate::model::question_cate::QuestionCate;
+use crate::model::textbook::Textbook;
 
 use crate::AppConfig;
 use actix_web::web;
 use log::error;
 use sqlx::PgPool;
 
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind};
 
 // 查询唯一绑定关系是否一存在
@@ -127,3 +133,197 @@ pub async fn remove(
 
     Ok(res > 0)
 }
+
+// 批量参数检查, 两边都不能为空
+fn check_batch_req(req: &BatchChapterKnowledgeReq) -> Result<(), Error> {
+    if req.chapter_ids.is_empty() || req.chapter_ids.iter().any(|id| *id <= 0) {
+        return Err(Error::new(ErrorKind::Other, "章节标识为空"));
+    }
+    if req.knowledge_ids.is_empty() || req.knowledge_ids.iter().any(|id| *id <= 0) {
+        return Err(Error::new(ErrorKind::Other, "考点标识为空"));
+    }
+    Ok(())
+}
+
+// 批量绑定关联关系, 可重复调用
+pub async fn batch_bind(
+    app_conf: web::Data<AppConfig>,
+    req: BatchChapterKnowledgeReq,
+) -> Result<BatchChapterKnowledgeResp, Error> {
+    check_batch_req(&req)?;
+
+    let mut pairs = Vec::with_capacity(req.chapter_ids.len() * req.knowledge_ids.len());
+    for chapter_id in &req.chapter_ids {
+        for knowledge_id in &req.knowledge_ids {
+            pairs.push((*chapter_id, *knowledge_id));
+        }
+    }
+
+    let affected = ChapterKnowledge::batch_insert(&app_conf.get_ref().db, pairs)
+        .await
+        .map_err(|err| {
+            error!("error batch adding chapter knowledge: {}", err);
+            Error::new(ErrorKind::Other, "添加失败")
+        })?;
+
+    Ok(BatchChapterKnowledgeResp {
+        affected,
+        skipped: Vec::new(),
+    })
+}
+
+// 批量解除关联关系, 可重复调用, 已关联题型的关系跟单个解绑一样不能解除
+pub async fn batch_unbind(
+    app_conf: web::Data<AppConfig>,
+    req: BatchChapterKnowledgeReq,
+) -> Result<BatchChapterKnowledgeResp, Error> {
+    check_batch_req(&req)?;
+
+    let db = &app_conf.get_ref().db;
+
+    let chapter_ids: HashSet<i32> = req.chapter_ids.iter().copied().collect();
+    let knowledge_ids: HashSet<i32> = req.knowledge_ids.iter().copied().collect();
+
+    let rows = ChapterKnowledge::find_by_ids(db, req.chapter_ids.clone())
+        .await
+        .map_err(|err| {
+            error!("error fetching chapter knowledge: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let rows: Vec<ChapterKnowledge> = rows
+        .into_iter()
+        .filter(|row| chapter_ids.contains(&row.chapter_id))
+        .filter(|row| knowledge_ids.contains(&row.knowledge_id))
+        .collect();
+    if rows.is_empty() {
+        return Ok(BatchChapterKnowledgeResp {
+            affected: 0,
+            skipped: Vec::new(),
+        });
+    }
+
+    let cates = QuestionCate::find_all_by_related_ids(db, rows.iter().map(|row| row.id).collect())
+        .await
+        .map_err(|err| {
+            error!("error fetching question cate: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let used_ids: HashSet<i32> = cates.iter().map(|row| row.related_id).collect();
+
+    let (skipped, removable): (Vec<ChapterKnowledge>, Vec<ChapterKnowledge>) =
+        rows.into_iter().partition(|row| used_ids.contains(&row.id));
+    let removable_ids: Vec<i32> = removable.iter().map(|row| row.id).collect();
+
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("Error beginning transaction: {}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    let affected = ChapterKnowledge::tx_delete_by_ids(&mut tx, &removable_ids)
+        .await
+        .map_err(|err| {
+            error!("error deleting chapter knowledge: {}", err);
+            Error::new(ErrorKind::Other, "删除失败")
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    Ok(BatchChapterKnowledgeResp {
+        affected,
+        skipped: skipped.into_iter().map(to_resp).collect(),
+    })
+}
+
+// 教材菜单下的绑定覆盖情况
+// 章节小节和知识点小类都在最后一层, 绑定的另一端可能不在当前菜单下
+pub async fn coverage(
+    app_conf: web::Data<AppConfig>,
+    root_id: i32,
+) -> Result<ChapterKnowledgeCoverageResp, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let root = Textbook::find_by_id(db, root_id).await.map_err(|err| {
+        error!("error fetching textbook: {}", err);
+        Error::new(ErrorKind::Other, "数据不存在")
+    })?;
+    let mut nodes = Textbook::find_all_by_parent_id(db, root_id)
+        .await
+        .map_err(|err| {
+            error!("error fetching textbook: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    nodes.insert(0, root);
+    nodes.retain(|row| row.path_depth == Some(MAX_DEPTH as i32));
+
+    let rows = ChapterKnowledge::find_by_ids(db, nodes.iter().map(|row| row.id).collect())
+        .await
+        .map_err(|err| {
+            error!("error fetching chapter knowledge: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let bound_chapter_ids: HashSet<i32> = rows.iter().map(|row| row.chapter_id).collect();
+    let bound_knowledge_ids: HashSet<i32> = rows.iter().map(|row| row.knowledge_id).collect();
+
+    let mut unbound_chapters = Vec::new();
+    let mut unbound_knowledge = Vec::new();
+    for node in &nodes {
+        let unbound = match node.path_type.as_str() {
+            PATH_TYPE_CHAPTER if !bound_chapter_ids.contains(&node.id) => &mut unbound_chapters,
+            PATH_TYPE_KNOWLEDGE if !bound_knowledge_ids.contains(&node.id) => {
+                &mut unbound_knowledge
+            }
+            _ => continue,
+        };
+        unbound.push(CoverageNodeResp {
+            id: node.id,
+            label: node.label.clone(),
+        });
+    }
+
+    // 绑定的另一端可能在其它菜单下, 名称需要单独查询
+    let mut labels: HashMap<i32, String> =
+        nodes.into_iter().map(|row| (row.id, row.label)).collect();
+    let other_ids: Vec<i32> = bound_chapter_ids
+        .iter()
+        .chain(bound_knowledge_ids.iter())
+        .filter(|id| !labels.contains_key(id))
+        .copied()
+        .collect();
+    if !other_ids.is_empty() {
+        let others = Textbook::find_by_ids(db, &other_ids).await.map_err(|err| {
+            error!("error fetching textbook: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+        labels.extend(others.into_iter().map(|row| (row.id, row.label)));
+    }
+
+    let ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    let counts =
+        ChapterKnowledge::count_questions_by_ids(db, &ids, QuestionStatus::Published as i16)
+            .await
+            .map_err(|err| {
+                error!("error counting chapter knowledge question: {}", err);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?;
+
+    let bindings = counts
+        .into_iter()
+        .map(|row| CoverageBindingResp {
+            id: row.id,
+            chapter_id: row.chapter_id,
+            chapter_label: labels.get(&row.chapter_id).cloned().unwrap_or_default(),
+            knowledge_id: row.knowledge_id,
+            knowledge_label: labels.get(&row.knowledge_id).cloned().unwrap_or_default(),
+            question_count: row.question_count,
+        })
+        .collect();
+
+    Ok(ChapterKnowledgeCoverageResp {
+        unbound_chapters,
+        unbound_knowledge,
+        bindings,
+    })
+}
@@ -0,0 +1,184 @@
+use crate::AppConfig;
+use crate::api::chapter_mapping::{
+    ChapterMappingResp, CreateChapterMappingReq, RemoveChapterMappingReq,
+};
+use crate::constant::textbook::PATH_TYPE_CHAPTER;
+use crate::model::chapter_knowledge::ChapterKnowledge;
+use crate::model::chapter_mapping::ChapterMapping;
+use crate::model::question_cate::QuestionCate;
+use crate::model::textbook::Textbook;
+use crate::service::question_cate;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+
+// 只有章节选题类型的节点才能建立对应关系
+async fn check_chapter_node(pool: &PgPool, id: i32) -> Result<Textbook, Error> {
+    let row = Textbook::find_by_id(pool, id).await.map_err(|e| {
+        error!("Error searching textbook: {:?}", e);
+        Error::new(ErrorKind::Other, format!("章节不存在: {}", id))
+    })?;
+
+    if row.path_type != PATH_TYPE_CHAPTER {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("{} 不是章节节点", row.label),
+        ));
+    }
+
+    Ok(row)
+}
+
+// 章节所在的版本菜单
+async fn edition_root_id(pool: &PgPool, id: i32) -> Result<Option<i32>, Error> {
+    Textbook::find_edition_root_id(pool, id, PATH_TYPE_CHAPTER)
+        .await
+        .map_err(|e| {
+            error!("Error searching textbook edition: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })
+}
+
+// 添加或者更新对应关系
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: CreateChapterMappingReq,
+) -> Result<i32, Error> {
+    if req.chapter_id == req.other_id {
+        return Err(Error::new(ErrorKind::Other, "不能对应自己"));
+    }
+
+    let confidence = req.confidence.unwrap_or(100);
+    if !(0..=100).contains(&confidence) {
+        return Err(Error::new(ErrorKind::Other, "匹配程度范围为 0-100"));
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let chapter = check_chapter_node(db, req.chapter_id).await?;
+    let other = check_chapter_node(db, req.other_id).await?;
+
+    // 只能对应其它版本的章节
+    if edition_root_id(db, chapter.id).await? == edition_root_id(db, other.id).await? {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("{} 和 {} 属于同一版本", chapter.label, other.label),
+        ));
+    }
+
+    let id = ChapterMapping::insert(db, req.chapter_id, req.other_id, confidence, req.note)
+        .await
+        .map_err(|e| {
+            error!("Error adding chapter mapping: {:?}", e);
+            Error::new(ErrorKind::Other, "添加失败")
+        })?;
+
+    Ok(id)
+}
+
+// 删除对应关系
+pub async fn remove(
+    app_conf: web::Data<AppConfig>,
+    req: RemoveChapterMappingReq,
+) -> Result<bool, Error> {
+    let row = ChapterMapping::delete(&app_conf.get_ref().db, req.chapter_id, req.other_id)
+        .await
+        .map_err(|e| {
+            error!("Error deleting chapter mapping: {:?}", e);
+            Error::new(ErrorKind::Other, "删除失败")
+        })?;
+
+    Ok(row > 0)
+}
+
+// 章节在其它版本中对应的章节, 以及对应章节通过知识点绑定关系挂载的题型列表
+pub async fn matches(
+    app_conf: web::Data<AppConfig>,
+    chapter_id: i32,
+    edition_id: i32,
+) -> Result<Vec<ChapterMappingResp>, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let mut rows = ChapterMapping::find_by_chapter_id(db, chapter_id)
+        .await
+        .map_err(|e| {
+            error!("Error searching chapter mapping: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    // 只保留指定版本菜单下的章节
+    if edition_id > 0 {
+        let edition_ids: HashSet<i32> = Textbook::find_all_by_parent_id(db, edition_id)
+            .await
+            .map_err(|e| {
+                error!("Error searching textbook: {:?}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?
+            .into_iter()
+            .map(|row| row.id)
+            .collect();
+        rows.retain(|row| edition_ids.contains(&row.other_id(chapter_id)));
+    }
+
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let other_ids: Vec<i32> = rows.iter().map(|row| row.other_id(chapter_id)).collect();
+
+    let labels: HashMap<i32, String> = Textbook::find_by_ids(db, &other_ids)
+        .await
+        .map_err(|e| {
+            error!("Error searching textbook: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .into_iter()
+        .map(|row| (row.id, row.label))
+        .collect();
+
+    // 章节 -> 绑定关系 -> 题型
+    let bridges = ChapterKnowledge::find_by_ids(db, other_ids.clone())
+        .await
+        .map_err(|e| {
+            error!("Error searching chapter knowledge: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let mut bridge_map: HashMap<i32, i32> = HashMap::with_capacity(bridges.len());
+    for bridge in &bridges {
+        if other_ids.contains(&bridge.chapter_id) {
+            bridge_map.insert(bridge.id, bridge.chapter_id);
+        }
+    }
+
+    let cates = QuestionCate::find_all_by_related_ids(db, bridge_map.keys().copied().collect())
+        .await
+        .map_err(|e| {
+            error!("Error searching question cate: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let mut cate_map: HashMap<i32, Vec<QuestionCate>> = HashMap::new();
+    for cate in cates {
+        if let Some(chapter) = bridge_map.get(&cate.related_id) {
+            cate_map.entry(*chapter).or_default().push(cate);
+        }
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let other_id = row.other_id(chapter_id);
+            let mut cates = cate_map.remove(&other_id).unwrap_or_default();
+            cates.sort_by_key(|cate| cate.sort_order);
+            ChapterMappingResp {
+                id: row.id,
+                chapter_id: other_id,
+                label: labels.get(&other_id).cloned().unwrap_or_default(),
+                confidence: row.confidence,
+                note: row.note,
+                question_cates: cates.into_iter().map(question_cate::to_resp).collect(),
+            }
+        })
+        .collect())
+}
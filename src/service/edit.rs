@@ -1,26 +1,81 @@
 use crate::AppConfig;
 use crate::api::edit::EditStatusReq;
-use crate::model::question::Question;
+use crate::model::question::{Question, QuestionStatus};
+use crate::service::review;
+use crate::util::local::to_local_datetime;
 use actix_web::web;
 use log::error;
 use std::io::{Error, ErrorKind};
 
-// 更新状态
+/// 更新状态
+/// 审核通过和拒绝按审核的规则执行, 只有待审核并且没有被其他审核人领取的题目可以审核, 修订稿审核通过后更新到原题
+/// 其余只能提交审核, 被拒绝后重新提交审核, 或者撤回没有被领取的待审核题目
 pub async fn status(app_conf: web::Data<AppConfig>, req: EditStatusReq) -> Result<bool, Error> {
-    let approve_id = 1;
+    if req.status == QuestionStatus::Published as i16
+        || req.status == QuestionStatus::Rejected as i16
+    {
+        let reviewer_id = req
+            .reviewer_id
+            .ok_or_else(|| Error::new(ErrorKind::Other, "审核需要填写审核人"))?;
+        return review::review(
+            &app_conf,
+            req.id,
+            req.status,
+            reviewer_id,
+            req.reject_reason,
+        )
+        .await;
+    }
 
-    let row = Question::update_status_by_id(
-        &app_conf.get_ref().db,
-        req.id,
-        req.status,
-        approve_id,
-        req.reject_reason,
-    )
-    .await
-    .map_err(|e| {
-        error!("Error while updating Status: {:?}", e);
-        Error::new(ErrorKind::Other, "更新失败")
-    })?;
+    let from = if req.status == QuestionStatus::Pending as i16 {
+        vec![
+            QuestionStatus::Draft as i16,
+            QuestionStatus::Rejected as i16,
+        ]
+    } else if req.status == QuestionStatus::Draft as i16 {
+        vec![QuestionStatus::Pending as i16]
+    } else {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("题目不能变更为{}", QuestionStatus::desc(req.status)),
+        ));
+    };
 
-    Ok(row > 0)
+    let db = &app_conf.get_ref().db;
+
+    let row = Question::update_status_from(db, req.id, &from, req.status, review::REVIEW_LOCK_SECS)
+        .await
+        .map_err(|e| {
+            error!("Error while updating Status: {:?}", e);
+            Error::new(ErrorKind::Other, "更新失败")
+        })?;
+    if row > 0 {
+        return Ok(true);
+    }
+
+    // 没有更新成功的查询具体原因
+    let claims = Question::find_review_claims(db, &[req.id])
+        .await
+        .map_err(|e| {
+            error!("question review claims err: {:?}", e);
+            Error::new(ErrorKind::Other, "更新失败")
+        })?;
+    let message = match claims.first() {
+        None => "题目不存在".to_string(),
+        Some(claim) if !from.contains(&claim.status) => format!(
+            "{}的题目不能变更为{}",
+            QuestionStatus::desc(claim.status),
+            QuestionStatus::desc(req.status)
+        ),
+        Some(claim) => format!(
+            "题目已被审核人 {} 于 {} 领取",
+            claim.review_claim_id.unwrap_or_default(),
+            claim
+                .review_claim_at
+                .map(to_local_datetime)
+                .unwrap_or_default()
+        ),
+    };
+
+    Err(Error::new(ErrorKind::Other, message))
 }
@@ -0,0 +1,212 @@
+use crate::AppConfig;
+use crate::api::knowledge_graph::{
+    KnowledgeGraphEdge, KnowledgeGraphNode, KnowledgeGraphResp, KnowledgePrerequisiteReq,
+    KnowledgeRelationResp,
+};
+use crate::constant::textbook::PATH_TYPE_KNOWLEDGE;
+use crate::model::knowledge_prerequisite::{KnowledgePrerequisite, KnowledgeRelation};
+use crate::model::textbook::Textbook;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::io::{Error, ErrorKind};
+
+fn to_relation_resp(row: KnowledgeRelation) -> KnowledgeRelationResp {
+    KnowledgeRelationResp {
+        id: row.id,
+        label: row.label,
+        distance: row.distance,
+    }
+}
+
+// 只有考点选题类型的节点才能建立前置关系
+async fn check_knowledge_node(pool: &PgPool, id: i32) -> Result<Textbook, Error> {
+    let row = Textbook::find_by_id(pool, id).await.map_err(|e| {
+        error!("Error searching textbook: {:?}", e);
+        Error::new(ErrorKind::Other, format!("知识点不存在: {}", id))
+    })?;
+
+    if row.path_type != PATH_TYPE_KNOWLEDGE {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("{} 不是知识点节点", row.label),
+        ));
+    }
+
+    Ok(row)
+}
+
+// 添加前置关系, 不允许成环
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: KnowledgePrerequisiteReq,
+) -> Result<i32, Error> {
+    if req.knowledge_id == req.prerequisite_id {
+        return Err(Error::new(ErrorKind::Other, "知识点不能依赖自己"));
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let knowledge = check_knowledge_node(db, req.knowledge_id).await?;
+    let prerequisite = check_knowledge_node(db, req.prerequisite_id).await?;
+
+    // 检查和添加在同一个事务中串行执行, 避免同时添加 a -> b 和 b -> a 时都通过检查
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("Error beginning transaction: {}", e);
+        Error::new(ErrorKind::Other, "添加失败")
+    })?;
+    KnowledgePrerequisite::tx_lock(&mut tx).await.map_err(|e| {
+        error!("Error locking knowledge prerequisite: {:?}", e);
+        Error::new(ErrorKind::Other, "添加失败")
+    })?;
+
+    // 前置知识点已经(间接)依赖当前知识点时会形成环
+    let exist =
+        KnowledgePrerequisite::is_prerequisite(&mut *tx, req.prerequisite_id, req.knowledge_id)
+            .await
+            .map_err(|e| {
+                error!("Error searching knowledge prerequisite: {:?}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?;
+    if exist {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "{} 已经依赖 {}, 不能形成环",
+                prerequisite.label, knowledge.label
+            ),
+        ));
+    }
+
+    let id = KnowledgePrerequisite::insert(&mut *tx, req.knowledge_id, req.prerequisite_id)
+        .await
+        .map_err(|e| {
+            error!("Error adding knowledge prerequisite: {:?}", e);
+            Error::new(ErrorKind::Other, "添加失败")
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "添加失败")
+    })?;
+
+    Ok(id)
+}
+
+// 删除前置关系
+pub async fn remove(
+    app_conf: web::Data<AppConfig>,
+    req: KnowledgePrerequisiteReq,
+) -> Result<bool, Error> {
+    let row = KnowledgePrerequisite::delete(
+        &app_conf.get_ref().db,
+        req.knowledge_id,
+        req.prerequisite_id,
+    )
+    .await
+    .map_err(|e| {
+        error!("Error deleting knowledge prerequisite: {:?}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    Ok(row > 0)
+}
+
+// 所有直接或间接的前置知识点
+pub async fn prerequisites(
+    app_conf: web::Data<AppConfig>,
+    knowledge_id: i32,
+) -> Result<Vec<KnowledgeRelationResp>, Error> {
+    let rows = KnowledgePrerequisite::find_prerequisites(&app_conf.get_ref().db, knowledge_id)
+        .await
+        .map_err(|e| {
+            error!("Error searching knowledge prerequisite: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(rows.into_iter().map(to_relation_resp).collect())
+}
+
+// 所有直接或间接依赖当前知识点的后续知识点
+pub async fn unlocks(
+    app_conf: web::Data<AppConfig>,
+    knowledge_id: i32,
+) -> Result<Vec<KnowledgeRelationResp>, Error> {
+    let rows = KnowledgePrerequisite::find_unlocks(&app_conf.get_ref().db, knowledge_id)
+        .await
+        .map_err(|e| {
+            error!("Error searching knowledge prerequisite: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(rows.into_iter().map(to_relation_resp).collect())
+}
+
+// 菜单及其子孙节点中所有知识点节点和它们之间的前置关系
+pub async fn export(
+    app_conf: web::Data<AppConfig>,
+    root_id: i32,
+) -> Result<KnowledgeGraphResp, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let root = Textbook::find_by_id(db, root_id).await.map_err(|e| {
+        error!("Error searching textbook: {:?}", e);
+        Error::new(ErrorKind::Other, "数据不存在")
+    })?;
+    let mut rows = Textbook::find_all_by_parent_id(db, root_id)
+        .await
+        .map_err(|e| {
+            error!("Error searching textbook: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    rows.insert(0, root);
+
+    let nodes: Vec<KnowledgeGraphNode> = rows
+        .into_iter()
+        .filter(|row| row.path_type == PATH_TYPE_KNOWLEDGE)
+        .map(|row| KnowledgeGraphNode {
+            id: row.id,
+            label: row.label,
+        })
+        .collect();
+
+    let ids: Vec<i32> = nodes.iter().map(|node| node.id).collect();
+    let edges = KnowledgePrerequisite::find_by_ids(db, &ids)
+        .await
+        .map_err(|e| {
+            error!("Error searching knowledge prerequisite: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .into_iter()
+        .map(|row| KnowledgeGraphEdge {
+            from: row.prerequisite_id,
+            to: row.knowledge_id,
+        })
+        .collect();
+
+    Ok(KnowledgeGraphResp { nodes, edges })
+}
+
+// 转为 graphviz dot 格式文本
+fn to_dot(graph: &KnowledgeGraphResp) -> String {
+    let mut dot = String::from("digraph knowledge {\n    rankdir=LR;\n");
+    for node in &graph.nodes {
+        dot.push_str(&format!(
+            "    {} [label=\"{}\"];\n",
+            node.id,
+            node.label.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+    for edge in &graph.edges {
+        dot.push_str(&format!("    {} -> {};\n", edge.from, edge.to));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// 导出 dot 格式, 直接返回文本方便可视化工具使用
+pub async fn export_dot(app_conf: web::Data<AppConfig>, root_id: i32) -> Result<String, Error> {
+    let graph = export(app_conf, root_id).await?;
+
+    Ok(to_dot(&graph))
+}
@@ -7,6 +7,7 @@ use crate::constant::meta;
 use crate::model::paper::{Paper, PaperStatus};
 use crate::model::paper_group::PaperGroup;
 use crate::model::paper_question::PaperQuestion;
+use crate::service::review_comment;
 use crate::util::local::to_local_datetime;
 use actix_web::web;
 use log::{error, info};
@@ -276,7 +277,10 @@ pub async fn info(app_conf: web::Data<AppConfig>, id: i64) -> Result<PaperResp,
     };
 
     // 4. 组装数据
-    Ok(to_resp(paper, paper_groups, paper_questions))
+    let mut resp = to_resp(paper, paper_groups, paper_questions);
+    review_comment::fill_paper_unresolved(db, std::slice::from_mut(&mut resp)).await?;
+
+    Ok(resp)
 }
 
 // 组装试卷详情返回
@@ -334,6 +338,7 @@ fn to_paper_resp(row: Paper) -> PaperResp {
         approve_id: row.approve_id,
         reject_reason: row.reject_reason,
         approve_at: None,
+        unresolved_comments: 0,
         remark: row.remark,
         count: row.count,
         groups: Vec::new(),
@@ -401,7 +406,8 @@ pub async fn list(
         })?;
 
     // 后续还要拼接状态等
-    let list: Vec<PaperResp> = papers.into_iter().map(to_paper_resp).collect();
+    let mut list: Vec<PaperResp> = papers.into_iter().map(to_paper_resp).collect();
+    review_comment::fill_paper_unresolved(db, &mut list).await?;
 
     Ok(PaperListResp {
         list,
@@ -413,15 +419,15 @@ pub async fn list(
 
 // 最新试卷
 pub async fn latest(app_conf: web::Data<AppConfig>, count: i64) -> Result<Vec<PaperResp>, Error> {
-    let papers = Paper::get_latest_papers(&app_conf.db, count)
-        .await
-        .map_err(|err| {
-            error!("Select paper list err: {}", err);
-            Error::new(ErrorKind::Other, "查询试卷列表失败")
-        })?;
+    let db = &app_conf.db;
+    let papers = Paper::get_latest_papers(db, count).await.map_err(|err| {
+        error!("Select paper list err: {}", err);
+        Error::new(ErrorKind::Other, "查询试卷列表失败")
+    })?;
 
     // 后续还要拼接状态等
-    let list: Vec<PaperResp> = papers.into_iter().map(to_paper_resp).collect();
+    let mut list: Vec<PaperResp> = papers.into_iter().map(to_paper_resp).collect();
+    review_comment::fill_paper_unresolved(db, &mut list).await?;
 
     Ok(list)
 }
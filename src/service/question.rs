@@ -1,15 +1,25 @@
 use crate::AppConfig;
 use crate::api::question::{
-    CreateQuestionReq, DeleteReq, QuestionBaseResp, QuestionExtraInfo, QuestionInfoResp,
-    QuestionListReq, QuestionListResp, QuestionSimilarListReq,
+    CloneQuestionReq, CloneQuestionResp, CreateQuestionReq, DeleteReq, MoveQuestionReq,
+    MoveQuestionResp, QuestionBaseResp, QuestionExtraInfo, QuestionInfoResp, QuestionListReq,
+    QuestionListResp, QuestionSimilarListReq,
 };
 use crate::constant::meta;
+use crate::model::chapter_knowledge::ChapterKnowledge;
+use crate::model::other_dict::TextbookDict;
 use crate::model::question::{Question, QuestionStatus};
+use crate::model::question_cate::QuestionCate;
+use crate::model::question_knowledge::QuestionKnowledge;
 use crate::model::question_similar::QuestionSimilar;
+use crate::model::review_comment::{CommentTarget, ReviewComment};
+use crate::service::{review_comment, textbook_dict};
 use crate::util::local::to_local_datetime;
 use actix_web::web;
 use log::error;
 use regex::Regex;
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 
 /// 将包含 LaTeX 的富文本标题转换为纯文本
@@ -58,7 +68,7 @@ pub async fn add(app_conf: web::Data<AppConfig>, mut req: CreateQuestionReq) ->
 }
 
 // 题目基本信息, 基本够列表使用
-fn to_base_resp(row: &Question) -> QuestionBaseResp {
+pub fn to_base_resp(row: &Question) -> QuestionBaseResp {
     QuestionBaseResp {
         id: row.id,
         question_cate_id: row.question_cate_id,
@@ -83,6 +93,7 @@ fn to_base_resp(row: &Question) -> QuestionBaseResp {
         } else {
             None
         },
+        unresolved_comments: 0,
         steps: row.steps.clone(),
         created_at: to_local_datetime(row.created_at),
         updated_at: to_local_datetime(row.updated_at),
@@ -117,7 +128,14 @@ pub async fn info(app_conf: web::Data<AppConfig>, id: i64) -> Result<QuestionInf
             Error::new(ErrorKind::Other, "查询失败")
         })?;
 
-    Ok(to_info_resp(row))
+    let mut resp = to_info_resp(row);
+    review_comment::fill_question_unresolved(
+        &app_conf.get_ref().db,
+        std::slice::from_mut(&mut resp.base_info),
+    )
+    .await?;
+
+    Ok(resp)
 }
 
 // 题目列表
@@ -139,6 +157,7 @@ pub async fn list(
         req.title_val.clone(),
         req.tag_ids.clone(),
         req.dimension_ids.clone(),
+        req.knowledge_id,
     )
     .await
     .map_err(|e| {
@@ -168,6 +187,7 @@ pub async fn list(
         req.title_val,
         req.tag_ids,
         req.dimension_ids,
+        req.knowledge_id,
         req.page_size,
         offset,
     )
@@ -178,12 +198,11 @@ pub async fn list(
     })?; // 必须加 ? 才能得到 Vec<Question>
 
     // 4. 转换并返回
+    let mut list: Vec<QuestionBaseResp> = list_data.iter().map(to_base_resp).collect();
+    review_comment::fill_question_unresolved(db, &mut list).await?;
+
     Ok(QuestionListResp {
-        // 使用 map().collect() 一行转换
-        list: list_data
-            .into_iter()
-            .map(|row| to_base_resp(&row))
-            .collect(),
+        list,
         page_no: req.page_no,
         page_size: req.page_size,
         total,
@@ -246,12 +265,11 @@ pub async fn similar(
     })?; // 必须加 ? 才能得到 Vec<Question>
 
     // 4. 转换并返回
+    let mut list: Vec<QuestionBaseResp> = list_data.iter().map(to_base_resp).collect();
+    review_comment::fill_question_unresolved(db, &mut list).await?;
+
     Ok(QuestionListResp {
-        // 使用 map().collect() 一行转换
-        list: list_data
-            .into_iter()
-            .map(|row| to_base_resp(&row))
-            .collect(),
+        list,
         page_no: req.page_no,
         page_size: req.page_size,
         total,
@@ -264,14 +282,361 @@ pub async fn delete(app_conf: web::Data<AppConfig>, req: DeleteReq) -> Result<bo
         return Err(Error::new(ErrorKind::Other, "题目标识为空"));
     }
 
-    let rows = Question::delete(&app_conf.db, req.id)
+    let db = &app_conf.db;
+
+    // 删除的题目如果是变式题, 它的变式题改挂到它的父题下, 否则成为独立的母题
+    let parent_id = QuestionSimilar::find_parent_id(db, req.id)
         .await
         .map_err(|err| {
-            error!("question delete by id err: {:?}", err);
+            error!("question similar find parent err: {:?}", err);
             Error::new(ErrorKind::Other, "查询失败")
         })?;
 
+    let mut tx = db.begin().await.map_err(|err| {
+        error!("question delete begin transaction err: {:?}", err);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    if let Some(parent_id) = parent_id {
+        QuestionSimilar::tx_update_parent(&mut tx, req.id, parent_id)
+            .await
+            .map_err(|err| {
+                error!("question similar update parent err: {:?}", err);
+                Error::new(ErrorKind::Other, "删除失败")
+            })?;
+    }
+    QuestionSimilar::tx_delete_by_question_id(&mut tx, req.id)
+        .await
+        .map_err(|err| {
+            error!("question similar delete err: {:?}", err);
+            Error::new(ErrorKind::Other, "删除失败")
+        })?;
+    ReviewComment::tx_delete_by_target(&mut tx, CommentTarget::Question as i16, req.id)
+        .await
+        .map_err(|err| {
+            error!("question review comment delete err: {:?}", err);
+            Error::new(ErrorKind::Other, "删除失败")
+        })?;
+
+    let rows = Question::delete(&mut *tx, req.id).await.map_err(|err| {
+        error!("question delete by id err: {:?}", err);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    tx.commit().await.map_err(|err| {
+        error!("question delete commit err: {:?}", err);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
     //todo 需校验只能删除自己的题目
 
     Ok(rows > 0)
 }
+
+// 题目引用的字典项映射到目标教材的字典项
+struct DictRemap {
+    map: HashMap<i32, i32>,
+    unmapped: Vec<String>,
+}
+
+impl DictRemap {
+    fn identity() -> Self {
+        Self {
+            map: HashMap::new(),
+            unmapped: vec![],
+        }
+    }
+
+    // 没有匹配上的保留原来的标识
+    fn get(&self, id: i32) -> i32 {
+        self.map.get(&id).copied().unwrap_or(id)
+    }
+
+    fn get_all(&self, ids: &Option<Json<Vec<i32>>>) -> Vec<i32> {
+        let mut res: Vec<i32> = vec![];
+        for id in ids.as_ref().map(|ids| ids.0.as_slice()).unwrap_or_default() {
+            let id = self.get(*id);
+            if !res.contains(&id) {
+                res.push(id);
+            }
+        }
+        res
+    }
+}
+
+// 目标题型对应的字典所在的教材节点, 没有指定时使用题型关联的章节
+async fn find_dict_textbook_id(
+    db: &PgPool,
+    cate_id: i32,
+    textbook_id: Option<i32>,
+) -> Result<i32, Error> {
+    let cate = QuestionCate::find_by_id(db, cate_id).await.map_err(|e| {
+        error!("question cate find by id err: {:?}", e);
+        Error::new(ErrorKind::Other, "目标题型不存在")
+    })?;
+    if let Some(textbook_id) = textbook_id {
+        return Ok(textbook_id);
+    }
+
+    let bridge = ChapterKnowledge::find_by_id(db, cate.related_id)
+        .await
+        .map_err(|e| {
+            error!("chapter knowledge find by id err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .ok_or_else(|| Error::new(ErrorKind::Other, "目标题型没有关联章节"))?;
+
+    Ok(bridge.chapter_id)
+}
+
+// 按字典类型和名称匹配目标教材节点实际生效的字典
+async fn load_dict_remap(
+    db: &PgPool,
+    rows: &[Question],
+    textbook_id: i32,
+) -> Result<DictRemap, Error> {
+    let mut ids: Vec<i32> = vec![];
+    for row in rows {
+        ids.push(row.question_type_id);
+        for list in [&row.question_tag_ids, &row.question_dimension_ids] {
+            ids.extend(list.as_ref().map(|ids| ids.0.clone()).unwrap_or_default());
+        }
+    }
+    ids.retain(|id| *id > 0);
+    ids.sort_unstable();
+    ids.dedup();
+
+    let source = TextbookDict::find_by_ids(db, &ids).await.map_err(|e| {
+        error!("textbook dict find by ids err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    let target: HashMap<(String, String), i32> =
+        textbook_dict::load_effective(db, textbook_id, None)
+            .await
+            .map_err(|e| {
+                error!("textbook dict load effective err: {:?}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?
+            .into_iter()
+            .map(|row| ((row.type_code, row.item_value), row.id))
+            .collect();
+
+    let mut remap = DictRemap::identity();
+    for row in source {
+        match target.get(&(row.type_code, row.item_value.clone())) {
+            Some(id) => {
+                remap.map.insert(row.id, *id);
+            }
+            None => remap.unmapped.push(row.item_value),
+        }
+    }
+
+    Ok(remap)
+}
+
+// 复制为草稿
+fn to_clone_req(row: Question, cate_id: i32, remap: &DictRemap) -> CreateQuestionReq {
+    CreateQuestionReq {
+        id: None,
+        question_cate_id: cate_id,
+        source_id: None,
+        question_type_id: remap.get(row.question_type_id),
+        question_tag_ids: Some(remap.get_all(&row.question_tag_ids)),
+        question_dimension_ids: Some(remap.get_all(&row.question_dimension_ids)),
+        author_id: Some(meta::TEMP_ADMIN_ID),
+        source: row.source,
+        original_name: row.original_name,
+        status: QuestionStatus::Draft as i16,
+        title: row.title,
+        content_plain: Some(row.content_plain),
+        comment: row.comment,
+        difficulty_level: row.difficulty_level,
+        images: row.images,
+        options: row.options,
+        options_layout: row.options_layout,
+        answer: row.answer,
+        knowledge: row.knowledge,
+        analysis: row.analysis,
+        process: row.process,
+        steps: row.steps,
+        remark: row.remark,
+        remark_ext: row.remark_ext,
+    }
+}
+
+fn tx_err(e: sqlx::Error) -> Error {
+    error!("question copy err: {:?}", e);
+    Error::new(ErrorKind::Other, "保存失败")
+}
+
+// 复制题目, 可以连同变式题一起复制, 变式题之间的层级关系和关联的知识点保持不变
+pub async fn clone(
+    app_conf: web::Data<AppConfig>,
+    req: CloneQuestionReq,
+) -> Result<CloneQuestionResp, Error> {
+    let db = &app_conf.db;
+
+    let source = Question::find_by_id(db, req.id).await.map_err(|e| {
+        error!("question get by id err: {:?}", e);
+        Error::new(ErrorKind::Other, "题目不存在")
+    })?;
+
+    let edges = if req.with_variants {
+        QuestionSimilar::find_tree(db, source.id)
+            .await
+            .map_err(|e| {
+                error!("question similar find tree err: {:?}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?
+    } else {
+        vec![]
+    };
+
+    let mut ids: Vec<i64> = vec![source.id];
+    ids.extend(edges.iter().map(|edge| edge.child_id));
+    let rows = Question::find_by_ids(db, &ids).await.map_err(|e| {
+        error!("question find by ids err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+
+    // 复制到其它题型时需要匹配目标教材的字典
+    let target_cate_id = req.target_cate_id.unwrap_or(source.question_cate_id);
+    let remap = if target_cate_id == source.question_cate_id && req.textbook_id.is_none() {
+        DictRemap::identity()
+    } else {
+        let textbook_id = find_dict_textbook_id(db, target_cate_id, req.textbook_id).await?;
+        load_dict_remap(db, &rows, textbook_id).await?
+    };
+
+    let knowledge = QuestionKnowledge::find_by_question_ids(db, &ids)
+        .await
+        .map_err(|e| {
+            error!("question knowledge find err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let mut tx = db.begin().await.map_err(tx_err)?;
+
+    let mut id_map: HashMap<i64, i64> = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let old_id = row.id;
+        let new_row = Question::tx_insert(&mut tx, to_clone_req(row, target_cate_id, &remap))
+            .await
+            .map_err(tx_err)?;
+        id_map.insert(old_id, new_row.id);
+    }
+
+    for edge in &edges {
+        if let (Some(question_id), Some(child_id)) =
+            (id_map.get(&edge.question_id), id_map.get(&edge.child_id))
+        {
+            QuestionSimilar::tx_insert(&mut tx, *question_id, *child_id)
+                .await
+                .map_err(tx_err)?;
+        }
+    }
+
+    let pairs = knowledge
+        .into_iter()
+        .filter_map(|row| {
+            id_map
+                .get(&row.question_id)
+                .map(|id| (*id, row.knowledge_id))
+        })
+        .collect();
+    QuestionKnowledge::tx_batch_insert(&mut tx, pairs)
+        .await
+        .map_err(tx_err)?;
+
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(CloneQuestionResp {
+        id: id_map.get(&source.id).copied().unwrap_or(0),
+        count: id_map.len(),
+        unmapped: remap.unmapped,
+    })
+}
+
+// 打开题目的修订稿, 已经有没提交的修订稿时直接使用, 没有时复制原题作为草稿交给原作者修改
+// 先锁定原题, 同时采纳同一个题目的多个勘误时只生成一个修订稿
+pub async fn tx_open_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    question_id: i64,
+) -> Result<i64, Error> {
+    let row = Question::tx_lock_by_id(tx, question_id)
+        .await
+        .map_err(|e| {
+            error!("question lock by id err: {:?}", e);
+            Error::new(ErrorKind::Other, "题目不存在")
+        })?;
+
+    let revision_id = Question::tx_find_open_revision_id(tx, question_id)
+        .await
+        .map_err(|e| {
+            error!("question find open revision err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    if let Some(id) = revision_id {
+        return Ok(id);
+    }
+
+    let (cate_id, author_id) = (row.question_cate_id, row.author_id);
+    let mut req = to_clone_req(row, cate_id, &DictRemap::identity());
+    req.author_id = Some(author_id);
+
+    let new_row = Question::tx_insert(tx, req).await.map_err(tx_err)?;
+    Question::tx_update_revision_of(tx, new_row.id, question_id)
+        .await
+        .map_err(tx_err)?;
+    QuestionKnowledge::tx_copy(tx, question_id, new_row.id)
+        .await
+        .map_err(tx_err)?;
+
+    Ok(new_row.id)
+}
+
+// 批量移动题目到其它题型
+pub async fn move_to(
+    app_conf: web::Data<AppConfig>,
+    req: MoveQuestionReq,
+) -> Result<MoveQuestionResp, Error> {
+    if req.ids.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "题目列表不能为空"));
+    }
+
+    let db = &app_conf.db;
+
+    let rows = Question::find_by_ids(db, &req.ids).await.map_err(|e| {
+        error!("question find by ids err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    if rows.len() != req.ids.len() {
+        return Err(Error::new(ErrorKind::Other, "部分题目不存在"));
+    }
+
+    let textbook_id = find_dict_textbook_id(db, req.target_cate_id, req.textbook_id).await?;
+    let remap = load_dict_remap(db, &rows, textbook_id).await?;
+
+    let mut tx = db.begin().await.map_err(tx_err)?;
+
+    let mut moved = 0;
+    for row in &rows {
+        moved += Question::tx_update_cate_and_dict(
+            &mut tx,
+            row.id,
+            req.target_cate_id,
+            remap.get(row.question_type_id),
+            remap.get_all(&row.question_tag_ids),
+            remap.get_all(&row.question_dimension_ids),
+        )
+        .await
+        .map_err(tx_err)?;
+    }
+
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(MoveQuestionResp {
+        moved,
+        unmapped: remap.unmapped,
+    })
+}
@@ -1,12 +1,19 @@
 use crate::AppConfig;
-use crate::api::question_cate::{CreateQuestionCateReq, QuestionCateResp};
+use crate::api::question_cate::{
+    CreateQuestionCateReq, MergeQuestionCateReq, MergeQuestionCateResp, MoveQuestionCateReq,
+    QuestionCateResp, SortQuestionCateReq, UpdateQuestionCateReq,
+};
+use crate::model::chapter_knowledge::ChapterKnowledge;
 use crate::model::question::Question;
 use crate::model::question_cate::QuestionCate;
+use crate::model::task::Task;
 use actix_web::web;
 use log::error;
+use sqlx::PgPool;
+use std::collections::HashSet;
 use std::io::{Error, ErrorKind};
 
-fn to_resp(row: QuestionCate) -> QuestionCateResp {
+pub fn to_resp(row: QuestionCate) -> QuestionCateResp {
     QuestionCateResp {
         id: row.id,
         related_id: row.related_id,
@@ -67,3 +74,213 @@ pub async fn remove(app_conf: web::Data<AppConfig>, id: i32) -> Result<bool, Err
 
     Ok(row > 0)
 }
+
+async fn find_cate(pool: &PgPool, id: i32) -> Result<QuestionCate, Error> {
+    QuestionCate::find_by_id(pool, id).await.map_err(|err| {
+        error!("error finding question cate: {}", err);
+        Error::new(ErrorKind::Other, format!("题型不存在: {}", id))
+    })
+}
+
+// 修改题型名称
+pub async fn edit(
+    app_conf: web::Data<AppConfig>,
+    req: UpdateQuestionCateReq,
+) -> Result<bool, Error> {
+    let label = req.label.trim();
+    if label.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "题型名称不能为空"));
+    }
+
+    let row = QuestionCate::update_label(&app_conf.get_ref().db, req.id, label)
+        .await
+        .map_err(|err| {
+            error!("error updating question cate: {}", err);
+            Error::new(ErrorKind::Other, "修改失败")
+        })?;
+
+    Ok(row > 0)
+}
+
+// 批量排序, 未出现在列表中的题型保持原有顺序排在后面
+pub async fn sort(app_conf: web::Data<AppConfig>, req: SortQuestionCateReq) -> Result<bool, Error> {
+    if req.ids.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "排序列表不能为空"));
+    }
+
+    let mut unique_ids = HashSet::with_capacity(req.ids.len());
+    for id in &req.ids {
+        if !unique_ids.insert(*id) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("排序列表存在重复题型: {}", id),
+            ));
+        }
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let mut siblings = QuestionCate::find_all_by_related_ids(db, vec![req.related_id])
+        .await
+        .map_err(|err| {
+            error!("error finding question cate: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    siblings.sort_by_key(|row| (row.sort_order, row.id));
+
+    let sibling_ids: HashSet<i32> = siblings.iter().map(|row| row.id).collect();
+    if let Some(id) = req.ids.iter().find(|id| !sibling_ids.contains(id)) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("题型 {} 不属于当前关联", id),
+        ));
+    }
+
+    let ids: Vec<i32> = req
+        .ids
+        .iter()
+        .copied()
+        .chain(
+            siblings
+                .iter()
+                .map(|row| row.id)
+                .filter(|id| !unique_ids.contains(id)),
+        )
+        .collect();
+
+    let mut tx = db.begin().await.map_err(|err| {
+        error!("error starting transaction: {}", err);
+        Error::new(ErrorKind::Other, "排序失败")
+    })?;
+
+    for (index, id) in ids.iter().enumerate() {
+        QuestionCate::update_sort_order(&mut *tx, *id, index as i32 + 1)
+            .await
+            .map_err(|err| {
+                error!("error updating question cate sort order: {}", err);
+                Error::new(ErrorKind::Other, "排序失败")
+            })?;
+    }
+
+    tx.commit().await.map_err(|err| {
+        error!("error committing transaction: {}", err);
+        Error::new(ErrorKind::Other, "排序失败")
+    })?;
+
+    Ok(true)
+}
+
+// 移动到其它章节和知识点关联下, 题目跟随题型一起移动
+pub async fn move_to(
+    app_conf: web::Data<AppConfig>,
+    req: MoveQuestionCateReq,
+) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let row = find_cate(db, req.id).await?;
+    if row.related_id == req.related_id {
+        return Ok(true);
+    }
+
+    let related = ChapterKnowledge::find_by_id(db, req.related_id)
+        .await
+        .map_err(|err| {
+            error!("error finding chapter knowledge: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    if related.is_none() {
+        return Err(Error::new(ErrorKind::Other, "章节和知识点关联不存在"));
+    }
+
+    let row = QuestionCate::update_related_id(db, req.id, req.related_id)
+        .await
+        .map_err(|err| {
+            error!("error moving question cate: {}", err);
+            Error::new(ErrorKind::Other, "移动失败")
+        })?;
+
+    Ok(row > 0)
+}
+
+async fn check_merge_req(pool: &PgPool, req: &MergeQuestionCateReq) -> Result<(), Error> {
+    if req.source_id == req.target_id {
+        return Err(Error::new(ErrorKind::Other, "不能合并到自己"));
+    }
+
+    find_cate(pool, req.source_id).await?;
+    find_cate(pool, req.target_id).await?;
+
+    Ok(())
+}
+
+// 合并预览
+pub async fn merge_preview(
+    app_conf: web::Data<AppConfig>,
+    req: MergeQuestionCateReq,
+) -> Result<MergeQuestionCateResp, Error> {
+    let db = &app_conf.get_ref().db;
+
+    check_merge_req(db, &req).await?;
+
+    let question_count = Question::count_by_cate_ids(db, &[req.source_id])
+        .await
+        .map_err(|err| {
+            error!("error counting question: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let task_count = Task::count_by_cate_id(db, req.source_id as i64)
+        .await
+        .map_err(|err| {
+            error!("error counting task: {}", err);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(MergeQuestionCateResp {
+        question_count,
+        task_count,
+    })
+}
+
+// 合并题型, 在同一个事务中改挂题目和任务并删除被合并的题型
+pub async fn merge(
+    app_conf: web::Data<AppConfig>,
+    req: MergeQuestionCateReq,
+) -> Result<MergeQuestionCateResp, Error> {
+    let db = &app_conf.get_ref().db;
+
+    check_merge_req(db, &req).await?;
+
+    let mut tx = db.begin().await.map_err(|err| {
+        error!("error starting transaction: {}", err);
+        Error::new(ErrorKind::Other, "合并失败")
+    })?;
+
+    let question_count = Question::tx_update_cate_id(&mut tx, req.source_id, req.target_id)
+        .await
+        .map_err(|err| {
+            error!("error moving question: {}", err);
+            Error::new(ErrorKind::Other, "合并失败")
+        })?;
+    let task_count = Task::tx_update_cate_id(&mut tx, req.source_id as i64, req.target_id as i64)
+        .await
+        .map_err(|err| {
+            error!("error moving task: {}", err);
+            Error::new(ErrorKind::Other, "合并失败")
+        })?;
+    QuestionCate::tx_delete_by_ids(&mut tx, &[req.source_id])
+        .await
+        .map_err(|err| {
+            error!("error deleting question cate: {}", err);
+            Error::new(ErrorKind::Other, "合并失败")
+        })?;
+
+    tx.commit().await.map_err(|err| {
+        error!("error committing transaction: {}", err);
+        Error::new(ErrorKind::Other, "合并失败")
+    })?;
+
+    Ok(MergeQuestionCateResp {
+        question_count: question_count as i64,
+        task_count: task_count as i64,
+    })
+}
@@ -0,0 +1,257 @@
+use crate::AppConfig;
+use crate::api::question_errata::{
+    CreateErrataReq, ErrataListReq, ErrataListResp, ErrataResp, HandleErrataReq, MyErrataListReq,
+};
+use crate::model::question::{Question, QuestionStatus};
+use crate::model::question_errata::{ErrataStatus, QuestionErrata};
+use crate::model::review_comment::CommentTarget;
+use crate::service::{question, review_comment};
+use crate::util::local::to_local_datetime;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::io::{Error, ErrorKind};
+
+// 错误描述最大长度
+const CONTENT_MAX_LEN: usize = 2000;
+
+fn to_resp(row: QuestionErrata) -> ErrataResp {
+    ErrataResp {
+        id: row.id,
+        question_id: row.question_id,
+        field: row.field,
+        content: row.content,
+        suggestion: row.suggestion,
+        reporter_id: row.reporter_id,
+        status: row.status,
+        status_desc: ErrataStatus::desc(row.status),
+        reply: row.reply,
+        handler_id: row.handler_id,
+        handled_at: row.handled_at.map(to_local_datetime),
+        revision_id: row.revision_id,
+        created_at: to_local_datetime(row.created_at),
+    }
+}
+
+// 去掉首尾空白, 空字符串当作没有填写
+fn trim_text(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+async fn find_errata(pool: &PgPool, id: i64) -> Result<QuestionErrata, Error> {
+    QuestionErrata::find_by_id(pool, id)
+        .await
+        .map_err(|e| {
+            error!("question errata find by id err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .ok_or_else(|| Error::new(ErrorKind::Other, "勘误不存在"))
+}
+
+// 提交勘误, 只有已发布的题目可以反馈
+pub async fn add(app_conf: web::Data<AppConfig>, req: CreateErrataReq) -> Result<i64, Error> {
+    let content = req.content.trim();
+    if content.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "错误描述不能为空"));
+    }
+    if content.chars().count() > CONTENT_MAX_LEN {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("错误描述不能超过 {} 个字", CONTENT_MAX_LEN),
+        ));
+    }
+    let field = review_comment::normalize_anchor(CommentTarget::Question as i16, req.field)?;
+
+    let db = &app_conf.get_ref().db;
+
+    let row = Question::find_by_id(db, req.question_id)
+        .await
+        .map_err(|e| {
+            error!("question get by id err: {:?}", e);
+            Error::new(ErrorKind::Other, "题目不存在")
+        })?;
+    if row.status != QuestionStatus::Published as i16 {
+        return Err(Error::new(ErrorKind::Other, "只能反馈已发布的题目"));
+    }
+
+    let id = QuestionErrata::insert(
+        db,
+        req.question_id,
+        field,
+        content,
+        trim_text(req.suggestion),
+        req.reporter_id,
+    )
+    .await
+    .map_err(|e| {
+        error!("question errata insert err: {:?}", e);
+        Error::new(ErrorKind::Other, "提交失败")
+    })?;
+
+    Ok(id)
+}
+
+async fn page(
+    pool: &PgPool,
+    status: Option<i16>,
+    question_id: Option<i64>,
+    reporter_id: Option<i64>,
+    page_no: i32,
+    page_size: i32,
+) -> Result<ErrataListResp, Error> {
+    let page_no = page_no.max(1);
+    let page_size = page_size.clamp(1, 100);
+
+    let total = QuestionErrata::count(pool, status, question_id, reporter_id)
+        .await
+        .map_err(|e| {
+            error!("question errata count err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let list = if total == 0 {
+        vec![]
+    } else {
+        QuestionErrata::list(
+            pool,
+            status,
+            question_id,
+            reporter_id,
+            page_size as i64,
+            ((page_no - 1) * page_size) as i64,
+        )
+        .await
+        .map_err(|e| {
+            error!("question errata list err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+    };
+
+    Ok(ErrataListResp {
+        list: list.into_iter().map(to_resp).collect(),
+        page_no,
+        page_size,
+        total,
+    })
+}
+
+// 处理队列, 默认只看待处理的
+pub async fn list(
+    app_conf: web::Data<AppConfig>,
+    req: ErrataListReq,
+) -> Result<ErrataListResp, Error> {
+    let status = req.status.unwrap_or(ErrataStatus::Open as i16);
+    page(
+        &app_conf.get_ref().db,
+        Some(status),
+        req.question_id,
+        None,
+        req.page_no,
+        req.page_size,
+    )
+    .await
+}
+
+// 反馈人自己的勘误
+pub async fn mine(
+    app_conf: web::Data<AppConfig>,
+    req: MyErrataListReq,
+) -> Result<ErrataListResp, Error> {
+    page(
+        &app_conf.get_ref().db,
+        req.status,
+        None,
+        Some(req.reporter_id),
+        req.page_no,
+        req.page_size,
+    )
+    .await
+}
+
+pub async fn info(app_conf: web::Data<AppConfig>, id: i64) -> Result<ErrataResp, Error> {
+    Ok(to_resp(find_errata(&app_conf.get_ref().db, id).await?))
+}
+
+/// 处理勘误
+/// 待处理的可以采纳或者驳回, 采纳时打开原题的修订稿, 驳回需要填写原因, 已采纳的修订稿审核发布后标记为已修复
+pub async fn handle(
+    app_conf: web::Data<AppConfig>,
+    req: HandleErrataReq,
+) -> Result<ErrataResp, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let row = find_errata(db, req.id).await?;
+    let reply = trim_text(req.reply);
+
+    let allowed = if req.status == ErrataStatus::Accepted as i16
+        || req.status == ErrataStatus::Rejected as i16
+    {
+        row.status == ErrataStatus::Open as i16
+    } else if req.status == ErrataStatus::Fixed as i16 {
+        row.status == ErrataStatus::Accepted as i16
+    } else {
+        return Err(Error::new(ErrorKind::Other, "处理状态错误"));
+    };
+    if !allowed {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "{}的勘误不能变更为{}",
+                ErrataStatus::desc(row.status),
+                ErrataStatus::desc(req.status)
+            ),
+        ));
+    }
+    if req.status == ErrataStatus::Rejected as i16 && reply.is_none() {
+        return Err(Error::new(ErrorKind::Other, "驳回需要填写原因"));
+    }
+    // 修订稿审核通过并更新到原题后才算修复
+    if req.status == ErrataStatus::Fixed as i16 {
+        let applied = Question::is_revision_applied(db, row.revision_id)
+            .await
+            .map_err(|e| {
+                error!("question find revision err: {:?}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?;
+        if !applied {
+            return Err(Error::new(ErrorKind::Other, "修订稿还没有审核发布"));
+        }
+    }
+
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("question errata begin err: {:?}", e);
+        Error::new(ErrorKind::Other, "处理失败")
+    })?;
+
+    let revision_id = if req.status == ErrataStatus::Accepted as i16 {
+        question::tx_open_revision(&mut tx, row.question_id).await?
+    } else {
+        0
+    };
+
+    let rows = QuestionErrata::tx_update_status(
+        &mut tx,
+        row.id,
+        row.status,
+        req.status,
+        reply,
+        req.handler_id,
+        revision_id,
+    )
+    .await
+    .map_err(|e| {
+        error!("question errata update status err: {:?}", e);
+        Error::new(ErrorKind::Other, "处理失败")
+    })?;
+    if rows == 0 {
+        return Err(Error::new(ErrorKind::Other, "勘误已被其他人处理"));
+    }
+
+    tx.commit().await.map_err(|e| {
+        error!("question errata commit err: {:?}", e);
+        Error::new(ErrorKind::Other, "处理失败")
+    })?;
+
+    info(app_conf, req.id).await
+}
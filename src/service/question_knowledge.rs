@@ -0,0 +1,278 @@
+use crate::AppConfig;
+use crate::api::question_knowledge::{QuestionKnowledgeReq, QuestionKnowledgeResp};
+use crate::constant::textbook::PATH_TYPE_KNOWLEDGE;
+use crate::model::chapter_knowledge::ChapterKnowledge;
+use crate::model::question::Question;
+use crate::model::question_cate::QuestionCate;
+use crate::model::question_knowledge::QuestionKnowledge;
+use crate::model::textbook::Textbook;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::io::{Error, ErrorKind};
+
+// 去掉空白和标点, 只保留用于比较的文字
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation() && !is_cjk_punctuation(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        '，' | '。' | '、' | '；' | '：' | '（' | '）' | '【' | '】' | '《' | '》' | '“' | '”'
+    )
+}
+
+/// 拆分知识点文本, 例如: 【求代数式的值，整体求值】
+/// 优先取中括号中的内容, 没有中括号时使用整段文本, 多个知识点之间用逗号, 顿号或分号分隔
+fn split_names(text: &str) -> Vec<String> {
+    let mut parts: Vec<&str> = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find('【') {
+        let after = &rest[start + '【'.len_utf8()..];
+        match after.find('】') {
+            Some(end) => {
+                parts.push(&after[..end]);
+                rest = &after[end + '】'.len_utf8()..];
+            }
+            None => {
+                parts.push(after);
+                rest = "";
+            }
+        }
+    }
+    if parts.is_empty() {
+        parts.push(text);
+    }
+
+    parts
+        .into_iter()
+        .flat_map(|part| part.split([',', '，', '、', ';', '；']))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !normalize(name).is_empty())
+        .collect()
+}
+
+/// 按名称模糊匹配知识点节点, 名称完全相同优先, 否则取相互包含且长度最接近的节点
+fn match_name(name: &str, candidates: &[(i32, String)]) -> Option<i32> {
+    let name = normalize(name);
+
+    if let Some((id, _)) = candidates.iter().find(|(_, label)| *label == name) {
+        return Some(*id);
+    }
+
+    candidates
+        .iter()
+        .filter(|(_, label)| label.contains(&name) || name.contains(label.as_str()))
+        .min_by_key(|(_, label)| label.chars().count().abs_diff(name.chars().count()))
+        .map(|(id, _)| *id)
+}
+
+// 导入题目时把知识点文本解析为知识点节点
+pub struct KnowledgeResolver {
+    candidates: Vec<(i32, String)>, // 节点标识和规范化后的名称
+}
+
+impl KnowledgeResolver {
+    /// 候选节点为题型绑定的知识点所在的整棵知识点树
+    /// 题型没有绑定知识点时使用所有知识点节点
+    pub async fn load(db: &PgPool, question_cate_id: i32) -> Result<Self, sqlx::Error> {
+        let mut root_id = None;
+        if let Ok(cate) = QuestionCate::find_by_id(db, question_cate_id).await
+            && let Some(bridge) = ChapterKnowledge::find_by_id(db, cate.related_id).await?
+        {
+            root_id = Textbook::find_root_id(db, bridge.knowledge_id).await?;
+        }
+
+        let rows = match root_id {
+            Some(root_id) => {
+                let mut rows = Textbook::find_all_by_parent_id(db, root_id).await?;
+                rows.push(Textbook::find_by_id(db, root_id).await?);
+                rows
+            }
+            None => Textbook::find_all_by_path_type(db, PATH_TYPE_KNOWLEDGE).await?,
+        };
+
+        // 层级越深越具体, 名称相同时优先匹配深层节点
+        let mut rows: Vec<Textbook> = rows
+            .into_iter()
+            .filter(|row| row.path_type == PATH_TYPE_KNOWLEDGE)
+            .collect();
+        rows.sort_by_key(|row| std::cmp::Reverse(row.path_depth.unwrap_or(0)));
+
+        // 规范化后为空的名称会被任意文本包含, 不能作为候选
+        Ok(Self {
+            candidates: rows
+                .into_iter()
+                .map(|row| (row.id, normalize(&row.label)))
+                .filter(|(_, label)| !label.is_empty())
+                .collect(),
+        })
+    }
+
+    /// 返回匹配上的节点标识和无法匹配的名称
+    pub fn resolve(&self, text: &str) -> (Vec<i32>, Vec<String>) {
+        let mut ids = vec![];
+        let mut unresolved = vec![];
+        for name in split_names(text) {
+            match match_name(&name, &self.candidates) {
+                Some(id) if !ids.contains(&id) => ids.push(id),
+                Some(_) => {}
+                None => unresolved.push(name),
+            }
+        }
+
+        (ids, unresolved)
+    }
+}
+
+// 保存题目关联的知识点, 覆盖原有的关联
+pub async fn bind(
+    app_conf: web::Data<AppConfig>,
+    req: QuestionKnowledgeReq,
+) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    Question::find_by_id(db, req.question_id)
+        .await
+        .map_err(|e| {
+            error!("Error searching question: {:?}", e);
+            Error::new(ErrorKind::Other, "题目不存在")
+        })?;
+
+    let rows = Textbook::find_by_ids(db, &req.knowledge_ids)
+        .await
+        .map_err(|e| {
+            error!("Error searching textbook: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    for id in &req.knowledge_ids {
+        match rows.iter().find(|row| row.id == *id) {
+            Some(row) if row.path_type == PATH_TYPE_KNOWLEDGE => {}
+            Some(row) => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("{} 不是知识点节点", row.label),
+                ));
+            }
+            None => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("知识点不存在: {}", id),
+                ));
+            }
+        }
+    }
+
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("Error beginning transaction: {:?}", e);
+        Error::new(ErrorKind::Other, "保存失败")
+    })?;
+
+    QuestionKnowledge::tx_delete_by_question_id(&mut tx, req.question_id)
+        .await
+        .map_err(|e| {
+            error!("Error deleting question knowledge: {:?}", e);
+            Error::new(ErrorKind::Other, "保存失败")
+        })?;
+    let pairs = req
+        .knowledge_ids
+        .iter()
+        .map(|id| (req.question_id, *id))
+        .collect();
+    QuestionKnowledge::tx_batch_insert(&mut tx, pairs)
+        .await
+        .map_err(|e| {
+            error!("Error adding question knowledge: {:?}", e);
+            Error::new(ErrorKind::Other, "保存失败")
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {:?}", e);
+        Error::new(ErrorKind::Other, "保存失败")
+    })?;
+
+    Ok(true)
+}
+
+// 题目关联的知识点列表
+pub async fn list(
+    app_conf: web::Data<AppConfig>,
+    question_id: i64,
+) -> Result<Vec<QuestionKnowledgeResp>, Error> {
+    let rows = QuestionKnowledge::find_by_question_ids(&app_conf.get_ref().db, &[question_id])
+        .await
+        .map_err(|e| {
+            error!("Error searching question knowledge: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| QuestionKnowledgeResp {
+            knowledge_id: row.knowledge_id,
+            label: row.label,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::service::question_knowledge::{match_name, normalize, split_names};
+
+    fn candidates() -> Vec<(i32, String)> {
+        [
+            (1, "代数式"),
+            (2, "代数式求值"),
+            (3, "整体求值"),
+            (4, "一元二次方程"),
+        ]
+        .into_iter()
+        .map(|(id, label)| (id, normalize(label)))
+        .collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" 求代数式的值（整体）。"), "求代数式的值整体");
+        assert_eq!(normalize("Sin, Cos"), "sincos");
+        assert_eq!(normalize("【，。】"), "");
+    }
+
+    #[test]
+    fn test_split_names() {
+        assert_eq!(
+            split_names("【求代数式的值，整体求值】"),
+            vec!["求代数式的值", "整体求值"]
+        );
+        assert_eq!(
+            split_names("考点: 【代数式】、【整体求值；配方法】"),
+            vec!["代数式", "整体求值", "配方法"]
+        );
+        assert_eq!(
+            split_names("代数式、 一元二次方程"),
+            vec!["代数式", "一元二次方程"]
+        );
+        // 只有标点的名称被忽略
+        assert_eq!(split_names("【代数式，、】"), vec!["代数式"]);
+        assert!(split_names("").is_empty());
+    }
+
+    #[test]
+    fn test_match_name() {
+        let candidates = candidates();
+
+        // 完全相同优先, 即使其它名称也包含它
+        assert_eq!(match_name("代数式", &candidates), Some(1));
+        assert_eq!(match_name("代数式求值。", &candidates), Some(2));
+        // 相互包含时取长度最接近的
+        assert_eq!(match_name("求值", &candidates), Some(3));
+        assert_eq!(match_name("代数式的求值", &candidates), Some(1));
+        assert_eq!(match_name("解一元二次方程", &candidates), Some(4));
+        // 没有匹配
+        assert_eq!(match_name("勾股定理", &candidates), None);
+    }
+}
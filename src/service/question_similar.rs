@@ -0,0 +1,381 @@
+use crate::AppConfig;
+use crate::api::question_similar::{
+    QuestionSimilarReq, QuestionSimilarTreeResp, SimilarFeatureResp, SimilarSuggestionResp,
+};
+use crate::model::question::Question;
+use crate::model::question_knowledge::QuestionKnowledge;
+use crate::model::question_similar::QuestionSimilar;
+use crate::service::question::to_plain_text;
+use crate::util::similarity;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+
+// 两个题目都存在并且不是同一个题目
+async fn check_pair(pool: &PgPool, question_id: i64, child_id: i64) -> Result<(), Error> {
+    if question_id == child_id {
+        return Err(Error::new(ErrorKind::Other, "不能关联自己"));
+    }
+
+    let rows = Question::find_by_ids(pool, &[question_id, child_id])
+        .await
+        .map_err(|e| {
+            error!("question find by ids err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    if rows.len() != 2 {
+        return Err(Error::new(ErrorKind::Other, "题目不存在"));
+    }
+
+    // 父题已经是变式题(间接)的变式题时会形成环
+    let exist = QuestionSimilar::is_descendant(pool, child_id, question_id)
+        .await
+        .map_err(|e| {
+            error!("question similar is descendant err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    if exist {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "父题已经是该题目的变式题, 不能形成环",
+        ));
+    }
+
+    Ok(())
+}
+
+async fn find_parent_id(pool: &PgPool, id: i64) -> Result<Option<i64>, Error> {
+    QuestionSimilar::find_parent_id(pool, id)
+        .await
+        .map_err(|e| {
+            error!("question similar find parent err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })
+}
+
+fn tx_err(e: sqlx::Error) -> Error {
+    error!("question similar update err: {:?}", e);
+    Error::new(ErrorKind::Other, "变式题关联修改失败")
+}
+
+// 关联变式题
+pub async fn link(app_conf: web::Data<AppConfig>, req: QuestionSimilarReq) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    check_pair(db, req.question_id, req.child_id).await?;
+
+    if let Some(parent_id) = find_parent_id(db, req.child_id).await? {
+        if parent_id == req.question_id {
+            return Ok(true);
+        }
+        return Err(Error::new(ErrorKind::Other, "变式题已经有父题, 请使用改挂"));
+    }
+
+    QuestionSimilar::insert(db, req.question_id, req.child_id)
+        .await
+        .map_err(|e| {
+            error!("question similar insert err: {:?}", e);
+            Error::new(ErrorKind::Other, "变式题关联失败")
+        })?;
+
+    Ok(true)
+}
+
+// 解除关联
+pub async fn unlink(
+    app_conf: web::Data<AppConfig>,
+    req: QuestionSimilarReq,
+) -> Result<bool, Error> {
+    let row = QuestionSimilar::delete(&app_conf.get_ref().db, req.question_id, req.child_id)
+        .await
+        .map_err(|e| {
+            error!("question similar delete err: {:?}", e);
+            Error::new(ErrorKind::Other, "解除关联失败")
+        })?;
+
+    Ok(row > 0)
+}
+
+// 改挂到新的父题下
+pub async fn reparent(
+    app_conf: web::Data<AppConfig>,
+    req: QuestionSimilarReq,
+) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    check_pair(db, req.question_id, req.child_id).await?;
+
+    let mut tx = db.begin().await.map_err(tx_err)?;
+    QuestionSimilar::tx_delete_by_child_id(&mut tx, req.child_id)
+        .await
+        .map_err(tx_err)?;
+    QuestionSimilar::tx_insert(&mut tx, req.question_id, req.child_id)
+        .await
+        .map_err(tx_err)?;
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(true)
+}
+
+/// 变式题提升为父题
+/// 提升后的题目接替原父题的位置, 原父题和其它变式题都挂到它下面, 它自己的变式题保持不变
+pub async fn promote(app_conf: web::Data<AppConfig>, id: i64) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let parent_id = find_parent_id(db, id)
+        .await?
+        .ok_or_else(|| Error::new(ErrorKind::Other, "题目已经是母题"))?;
+    let grandparent_id = find_parent_id(db, parent_id).await?;
+
+    let mut tx = db.begin().await.map_err(tx_err)?;
+
+    QuestionSimilar::tx_delete_by_child_id(&mut tx, id)
+        .await
+        .map_err(tx_err)?;
+    QuestionSimilar::tx_update_parent(&mut tx, parent_id, id)
+        .await
+        .map_err(tx_err)?;
+    if let Some(grandparent_id) = grandparent_id {
+        QuestionSimilar::tx_delete_by_child_id(&mut tx, parent_id)
+            .await
+            .map_err(tx_err)?;
+        QuestionSimilar::tx_insert(&mut tx, grandparent_id, id)
+            .await
+            .map_err(tx_err)?;
+    }
+    QuestionSimilar::tx_insert(&mut tx, id, parent_id)
+        .await
+        .map_err(tx_err)?;
+
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(true)
+}
+
+fn build_tree(
+    id: i64,
+    children_map: &HashMap<i64, Vec<i64>>,
+    question_map: &HashMap<i64, (String, i16)>,
+    visited: &mut HashSet<i64>,
+) -> QuestionSimilarTreeResp {
+    visited.insert(id);
+
+    let (title, status) = question_map.get(&id).cloned().unwrap_or_default();
+    let children = children_map
+        .get(&id)
+        .map(|ids| {
+            ids.iter()
+                .filter(|child| !visited.contains(child))
+                .copied()
+                .collect::<Vec<i64>>()
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .map(|child| build_tree(child, children_map, question_map, visited))
+        .collect();
+
+    QuestionSimilarTreeResp {
+        id,
+        title,
+        status,
+        children,
+    }
+}
+
+// 题目所在的整棵变式题树
+pub async fn tree(
+    app_conf: web::Data<AppConfig>,
+    id: i64,
+) -> Result<QuestionSimilarTreeResp, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let root_id = QuestionSimilar::find_root_id(db, id).await.map_err(|e| {
+        error!("question similar find root err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    let edges = QuestionSimilar::find_tree(db, root_id).await.map_err(|e| {
+        error!("question similar find tree err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+
+    let mut ids = vec![root_id];
+    let mut children_map: HashMap<i64, Vec<i64>> = HashMap::new();
+    for edge in edges {
+        ids.push(edge.child_id);
+        children_map
+            .entry(edge.question_id)
+            .or_default()
+            .push(edge.child_id);
+    }
+
+    let question_map: HashMap<i64, (String, i16)> = Question::find_by_ids(db, &ids)
+        .await
+        .map_err(|e| {
+            error!("question find by ids err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .into_iter()
+        .map(|row| (row.id, (row.title, row.status)))
+        .collect();
+    if !question_map.contains_key(&root_id) {
+        return Err(Error::new(ErrorKind::Other, "题目不存在"));
+    }
+
+    Ok(build_tree(
+        root_id,
+        &children_map,
+        &question_map,
+        &mut HashSet::new(),
+    ))
+}
+
+// 参与计算相似度的候选题目数量上限
+const SUGGEST_CANDIDATE_LIMIT: i64 = 1000;
+// 返回的推荐数量
+const SUGGEST_LIMIT: usize = 20;
+
+// 各项特征的权重, 合计为 1
+const WEIGHT_TEXT: f64 = 0.5;
+const WEIGHT_LATEX: f64 = 0.2;
+const WEIGHT_TYPE: f64 = 0.15;
+const WEIGHT_KNOWLEDGE: f64 = 0.15;
+
+fn plain_text(row: &Question) -> String {
+    if row.content_plain.is_empty() {
+        to_plain_text(&row.title)
+    } else {
+        row.content_plain.clone()
+    }
+}
+
+/// 推荐变式题
+/// 候选范围是同一个题型或者关联了相同知识点的题目, 排除已经在同一棵变式题树中的题目
+pub async fn suggest(
+    app_conf: web::Data<AppConfig>,
+    id: i64,
+) -> Result<Vec<SimilarSuggestionResp>, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let source = Question::find_by_id(db, id).await.map_err(|e| {
+        error!("question find by id err: {:?}", e);
+        Error::new(ErrorKind::Other, "题目不存在")
+    })?;
+
+    let source_knowledge: HashSet<i32> = QuestionKnowledge::find_by_question_ids(db, &[id])
+        .await
+        .map_err(|e| {
+            error!("question knowledge find err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .into_iter()
+        .map(|row| row.knowledge_id)
+        .collect();
+
+    // 同一棵变式题树中的题目不再推荐
+    let root_id = QuestionSimilar::find_root_id(db, id).await.map_err(|e| {
+        error!("question similar find root err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    let mut exclude_ids: Vec<i64> = QuestionSimilar::find_tree(db, root_id)
+        .await
+        .map_err(|e| {
+            error!("question similar find tree err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .into_iter()
+        .map(|row| row.child_id)
+        .collect();
+    exclude_ids.push(root_id);
+    exclude_ids.push(id);
+
+    let knowledge_ids: Vec<i32> = source_knowledge.iter().copied().collect();
+    let candidates = Question::find_similar_candidates(
+        db,
+        source.question_cate_id,
+        &knowledge_ids,
+        &exclude_ids,
+        SUGGEST_CANDIDATE_LIMIT,
+    )
+    .await
+    .map_err(|e| {
+        error!("question find similar candidates err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    if candidates.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let candidate_ids: Vec<i64> = candidates.iter().map(|row| row.id).collect();
+    let mut knowledge_map: HashMap<i64, HashSet<i32>> = HashMap::new();
+    for row in QuestionKnowledge::find_by_question_ids(db, &candidate_ids)
+        .await
+        .map_err(|e| {
+            error!("question knowledge find err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+    {
+        knowledge_map
+            .entry(row.question_id)
+            .or_default()
+            .insert(row.knowledge_id);
+    }
+
+    let source_text = plain_text(&source);
+    let source_latex = similarity::latex_structure(&source.title);
+
+    let mut res: Vec<SimilarSuggestionResp> = candidates
+        .into_iter()
+        .map(|row| {
+            let text_score = similarity::text_similarity(&source_text, &plain_text(&row));
+            let latex_score = similarity::latex_similarity(
+                &source_latex,
+                &similarity::latex_structure(&row.title),
+            );
+            let type_score =
+                if row.question_type_id > 0 && row.question_type_id == source.question_type_id {
+                    1.0
+                } else {
+                    0.0
+                };
+            let knowledge_score = knowledge_map
+                .get(&row.id)
+                .map(|ids| similarity::set_similarity(&source_knowledge, ids))
+                .unwrap_or(0.0);
+
+            let score = text_score * WEIGHT_TEXT
+                + latex_score * WEIGHT_LATEX
+                + type_score * WEIGHT_TYPE
+                + knowledge_score * WEIGHT_KNOWLEDGE;
+
+            let features = [
+                ("text", text_score),
+                ("latex", latex_score),
+                ("type", type_score),
+                ("knowledge", knowledge_score),
+            ]
+            .into_iter()
+            .filter(|(_, score)| *score > 0.0)
+            .map(|(name, score)| SimilarFeatureResp {
+                name: name.to_string(),
+                score: (score * 100.0).round() / 100.0,
+            })
+            .collect();
+
+            SimilarSuggestionResp {
+                id: row.id,
+                title: row.title,
+                question_type_id: row.question_type_id,
+                score: (score * 10000.0).round() / 100.0,
+                features,
+            }
+        })
+        .filter(|item| item.score > 0.0)
+        .collect();
+
+    res.sort_by(|a, b| b.score.total_cmp(&a.score).then(b.id.cmp(&a.id)));
+    res.truncate(SUGGEST_LIMIT);
+
+    Ok(res)
+}
@@ -1,202 +1,516 @@
 use crate::AppConfig;
 use crate::api::question::CreateQuestionReq;
-use crate::api::text::QuestionSnippetReq;
+use crate::api::text::{
+    QuestionPreviewItem, QuestionPreviewReq, QuestionPreviewResp, QuestionSnippetReq,
+};
 use crate::constant::meta;
 use crate::model::other_dict::TextbookDict;
 use crate::model::question::{Content, Question, QuestionOption, QuestionStatus};
+use crate::model::question_knowledge::QuestionKnowledge;
 use crate::model::question_similar::QuestionSimilar;
-use crate::model::task::{Task, TaskStatus, TaskType};
-use crate::service::question;
+use crate::service::question_knowledge::KnowledgeResolver;
+use crate::service::{question, textbook_dict};
+use crate::task::job::JobContext;
+use crate::task::question::{
+    ImportOutcome, QuestionImportFields, QuestionImportItem, QuestionUploadOutput,
+    QuestionUploadParams,
+};
 use crate::util::markdown_parse;
 use crate::util::markdown_parse::RawQuestion;
+use crate::util::{bundle, upload};
+use actix_web::web;
 use log::{error, info};
-use sqlx::PgPool;
+use rust_decimal::Decimal;
 use sqlx::types::Json;
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
 use std::collections::HashMap;
 use std::fs;
 use std::io::{Error, ErrorKind};
+use std::path::Path;
 
-// 批量题目上传
-pub async fn batch(app_conf: &AppConfig) -> Result<(), Error> {
-    let db = &app_conf.db;
+// 获取题型列表和标签列表, 当前节点没有定义时沿祖先节点继承
+async fn load_dict(db: &PgPool, textbook_id: i32, dict_type: &str) -> Vec<TextbookDict> {
+    match textbook_dict::load_effective(db, textbook_id, Some(dict_type)).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!(
+                "查询 textbook_id {} 的 {} 失败: {}",
+                textbook_id, dict_type, e
+            );
+            vec![]
+        }
+    }
+}
 
-    // 查询所有待执行的任务
-    let waiting_task_list = Task::get_waiting_list(db, TaskType::UploadQuestion as i16)
-        .await
-        .map_err(|e| {
-            error!("Get waiting task list err: {}", e);
-            Error::new(ErrorKind::Other, "查询失败")
-        })?;
-    if waiting_task_list.is_empty() {
-        info!("Waiting task list is empty");
-        return Ok(());
+// 导入时使用的题目类型, 标签, 知识点和图片
+struct ImportDict {
+    question_cate_id: i32,
+    question_type_list: Vec<TextbookDict>,
+    question_tag_list: Vec<TextbookDict>,
+    knowledge_resolver: KnowledgeResolver,
+    image_names: HashMap<String, String>, // 图片路径->存储后的名称, 保存之前名称和路径相同
+}
+
+// 解析上传的文件, zip 压缩包读取其中的文档和图片, 其它文件作为 markdown 文档
+fn parse_source(content: Vec<u8>) -> Result<(String, HashMap<String, Vec<u8>>), Error> {
+    if bundle::is_zip(&content) {
+        let bundle = bundle::read_bundle(&content)?;
+        return Ok((bundle.markdown, bundle.images));
     }
 
-    // 缓存题型类型列表
-    let mut question_type_cache: HashMap<i32, Vec<TextbookDict>> = HashMap::new();
-    // 缓存标签
-    let mut question_tag_cache: HashMap<i32, Vec<TextbookDict>> = HashMap::new();
-
-    // 遍历待处理的任务列表
-    for task_info in waiting_task_list {
-        let textbook_id = task_info.textbook_id;
-
-        // 获取题型类型列表
-        let question_type_list =
-            get_or_load_dict(db, textbook_id, "question_type", &mut question_type_cache).await;
-
-        // 获取标签类型列表
-        let question_tag_list =
-            get_or_load_dict(db, textbook_id, "question_tag", &mut question_tag_cache).await;
-
-        // 修改任务为执行中, 后续的失败不回滚, 而是继续更新状态
-        // 如果文件内容本身正常只是程序问题导致, 后续需要手动修改为待执行等待下一批次重新执行
-        // 先更新状态为 Running（可允许失败时跳过该任务）
-        let task_id = task_info.id;
-        let task_name = task_info.name.clone();
-        if let Err(e) =
-            Task::update_by_id(db, &task_id, TaskStatus::Running as i16, "".to_string()).await
-        {
-            error!(
-                "Update task id: {}, name {} status=Running err: {}",
-                task_id, task_name, e
-            );
-            // 不 return，继续下一个任务
-            continue;
+    let markdown = String::from_utf8(content)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "文档不是 UTF-8 编码"))?;
+    Ok((markdown, HashMap::new()))
+}
+
+// 题目中引用的本地图片
+fn raw_image_refs(raw: &RawQuestion) -> Vec<String> {
+    let mut refs: Vec<String> = vec![];
+    let texts = [&raw.stem, &raw.answer, &raw.analysis, &raw.detail];
+    for text in texts.into_iter().chain(raw.choices.iter()) {
+        for path in markdown_parse::image_refs(text) {
+            if !refs.contains(&path) {
+                refs.push(path);
+            }
         }
+    }
+    refs
+}
 
-        // 处理一个任务, 一个任务的事务是独立的
-        let task_name = task_info.name.clone();
-        info!("Process single task info process start: {}", task_name);
-        if let Err(e) = single(app_conf, task_info, &question_type_list, &question_tag_list).await {
-            error!("Process single task info err: {}", e);
-            // 更新当前任务执行失败, 数据库记录原因为捕获的错误信息, 实际的执行内容需要看脚本执行日志
-            if let Err(e) =
-                Task::update_by_id(db, &task_id, TaskStatus::Failed as i16, e.to_string()).await
-            {
-                error!(
-                    "Update task id: {}, name {} status=Failed err: {}",
-                    task_id, task_name, e
-                );
-                // 不 return，继续下一个任务
+// 保存可以添加的题目引用的图片, 返回图片路径->存储后的名称
+// 图片先于题目保存, 后续添加失败时图片不会删除
+async fn store_images(
+    meta_path: &str,
+    groups: &[(Prepared, Vec<Prepared>)],
+    images: &HashMap<String, Vec<u8>>,
+) -> Result<HashMap<String, String>, Error> {
+    let mut names: HashMap<String, String> = HashMap::new();
+    for (parent, children) in groups {
+        for prepared in std::iter::once(parent).chain(children.iter()) {
+            if !prepared.item.errors.is_empty() {
                 continue;
             }
-            continue;
+            for path in raw_image_refs(&prepared.raw) {
+                if names.contains_key(&path) {
+                    continue;
+                }
+                let Some(content) = images.get(&path) else {
+                    continue;
+                };
+                let name = upload::save_image(meta_path, &path, content).await?;
+                names.insert(path, name);
+            }
         }
-        info!("Process single task info process done: {}", task_name);
     }
 
-    info!("Waiting task list all done");
+    Ok(names)
+}
 
-    Ok(())
+// 选项和解析等内容中的图片列表, 没有图片时为空
+fn non_empty(images: Vec<String>) -> Option<Vec<String>> {
+    if images.is_empty() {
+        None
+    } else {
+        Some(images)
+    }
+}
+
+// 解析出的题目和处理结果
+struct Prepared {
+    raw: RawQuestion,
+    item: QuestionImportItem,
 }
 
-// 获取题型列表和标签列表
-async fn get_or_load_dict(
+// 查询导入使用的字典, 没有定义题目类型时记录文件相关的警告
+async fn load_import_dict(
     db: &PgPool,
+    question_cate_id: i32,
     textbook_id: i32,
-    dict_type: &str,
-    cache: &mut HashMap<i32, Vec<TextbookDict>>,
-) -> Vec<TextbookDict> {
-    if let Some(list) = cache.get(&textbook_id) {
-        list.clone()
-    } else {
-        let list = match TextbookDict::find_by_textbook_and_type(db, textbook_id, dict_type).await {
-            Ok(list) if !list.is_empty() => list,
-            Ok(_) => vec![],
-            Err(e) => {
-                error!(
-                    "查询 textbook_id {} 的 {} 失败: {}",
-                    textbook_id, dict_type, e
-                );
-                vec![]
-            }
-        };
-        let cloned_list = list.clone();
-        cache.insert(textbook_id, list);
-        cloned_list
+    warnings: &mut Vec<String>,
+) -> Result<ImportDict, Error> {
+    // 知识点文本解析为知识点节点的候选列表
+    let knowledge_resolver = KnowledgeResolver::load(db, question_cate_id)
+        .await
+        .map_err(|e| {
+            error!("Load knowledge resolver err: {}", e);
+            Error::new(ErrorKind::Other, "查询知识点失败")
+        })?;
+    let dict = ImportDict {
+        question_cate_id,
+        question_type_list: load_dict(db, textbook_id, "question_type").await,
+        question_tag_list: load_dict(db, textbook_id, "question_tag").await,
+        knowledge_resolver,
+        image_names: HashMap::new(),
+    };
+
+    if dict.question_type_list.is_empty() {
+        warnings.push(format!(
+            "教材节点 {} 及其祖先节点都没有定义题目类型, 题目类型将为空",
+            textbook_id
+        ));
     }
+
+    Ok(dict)
 }
 
-// 上传单个题目文件
-async fn single(
-    app_config: &AppConfig,
-    task_info: Task,
-    question_type_list: &Vec<TextbookDict>,
-    question_tag_list: &Vec<TextbookDict>,
-) -> Result<(), Error> {
+// 检查所有题目, 母题有问题时变式题一起跳过
+fn prepare_all(
+    all_questions: Vec<markdown_parse::Question>,
+    dict: &ImportDict,
+) -> Vec<(Prepared, Vec<Prepared>)> {
+    all_questions
+        .into_iter()
+        .map(|question_info| {
+            let parent = prepare(question_info.parent, true, dict);
+            let mut children: Vec<Prepared> = question_info
+                .children
+                .into_iter()
+                .map(|child| prepare(child, false, dict))
+                .collect();
+            if !parent.item.errors.is_empty() {
+                for child in children.iter_mut() {
+                    child.item.errors.push("所属母题无法添加".to_string());
+                    child.item.outcome = ImportOutcome::Skipped;
+                }
+            }
+            (parent, children)
+        })
+        .collect()
+}
+
+/// 上传单个题目文件
+/// 默认整个文件作为一个事务单位, 任意题目有问题都不添加
+/// 部分提交时每个母题及其变式题作为一个保存点, 有问题的题目跳过, 母题有问题时其变式题一起跳过
+pub async fn upload(
+    ctx: &JobContext<'_>,
+    params: &QuestionUploadParams,
+) -> Result<QuestionUploadOutput, Error> {
+    let app_config = ctx.app_conf;
+    let task_name = &ctx.task.name;
+    ctx.progress("读取文件", 0, 0).await;
+
     // 记录结果日志
     let mut result: Vec<String> = vec![];
+    // 文件相关的警告, 题目相关的警告记录在每个题目中
+    let mut warnings: Vec<String> = vec![];
 
     // 读取文件内容 url 字段存取的是文件名称, 路径需要系统设计补完整
     let file_path = format!(
         "{}/{}/{}",
         app_config.meta_path,
         meta::FILE_NAME,
-        task_info.url
+        params.url
     );
-    let content = fs::read_to_string(file_path)?;
+    let (content, images) = parse_source(fs::read(file_path)?)?;
 
     result.push("读取文件\n".to_string());
 
     let all_questions = markdown_parse::get_questions(&content)?;
     if all_questions.is_empty() {
-        error!("Task name: {} all questions is empty", task_info.name);
+        error!("Task name: {} all questions is empty", task_name);
         return Err(Error::new(
             ErrorKind::Other,
             "该文件没有读取到任何有效的题目",
         ));
     };
 
+    let mut dict = load_import_dict(
+        &app_config.db,
+        params.question_cate_id,
+        params.textbook_id,
+        &mut warnings,
+    )
+    .await?;
+    dict.image_names = images
+        .keys()
+        .map(|path| (path.clone(), path.clone()))
+        .collect();
+
+    // 先检查所有题目, 母题有问题时变式题一起跳过
+    ctx.progress("检查题目", 0, 0).await;
+    let mut groups = prepare_all(all_questions, &dict);
+
+    if !params.partial {
+        let errors: Vec<String> = groups
+            .iter()
+            .flat_map(|(parent, children)| std::iter::once(parent).chain(children.iter()))
+            .flat_map(|prepared| {
+                prepared
+                    .item
+                    .errors
+                    .iter()
+                    .map(|e| format!("{}: {}", position(&prepared.item), e))
+            })
+            .collect();
+        if !errors.is_empty() {
+            error!("Task name: {} has invalid questions", task_name);
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "有 {} 个问题, 没有添加任何题目:\n{}",
+                    errors.len(),
+                    errors.join("\n")
+                ),
+            ));
+        }
+    }
+
+    // 图片保存后题目中的引用替换为存储后的名称
+    if !images.is_empty() {
+        ctx.progress("保存图片", 0, 0).await;
+        dict.image_names = store_images(&app_config.meta_path, &groups, &images).await?;
+        result.push(format!("保存图片 {} 张\n", dict.image_names.len()));
+    }
+
     // 这部分更新使用事务
     let mut tx = app_config.db.begin().await.map_err(|e| {
         error!("Error beginning transaction: {}", e);
         Error::new(ErrorKind::Other, "启动事务失败")
     })?;
 
-    // 一个文件作为一个事务单位
-    for question_info in all_questions {
-        // 母题
-        let simple_parent_title = question_info.parent.title.clone();
-        let parent_req = to_req(
-            question_info.parent,
-            None,
-            &task_info,
-            &question_type_list,
-            &question_tag_list,
-        );
-        info!("Add parent question name: {} begin", simple_parent_title);
-        let parent = Question::tx_insert(&mut tx, parent_req)
-            .await
-            .map_err(|err| {
-                error!("Insert parent of question err: {}", err);
-                Error::new(ErrorKind::Other, "母题添加失败")
-            })?;
-
-        result.push(format!("添加 {}\n", simple_parent_title));
+    // 执行进度按题目数量计算, 包括变式题
+    let total: usize = groups.iter().map(|(_, children)| 1 + children.len()).sum();
+    let mut done = 0;
+    for (parent, children) in groups.iter_mut() {
+        ctx.progress("添加题目", done, total).await;
+        done += 1 + children.len();
 
-        // 变式题列表为空正常
-        if question_info.children.is_empty() {
+        if !parent.item.errors.is_empty() {
             continue;
         }
-        let mut children_req: Vec<CreateQuestionReq> = vec![];
-        for child in question_info.children {
-            let simple_child_title = child.title.clone();
-            let child_req = to_req(
-                child,
-                Some(parent.id),
-                &task_info,
-                &question_type_list,
-                &question_tag_list,
-            );
-            info!("Add child question name: {} begin", simple_child_title);
-            children_req.push(child_req);
+        if !params.partial {
+            tx_insert_group(&mut tx, &dict, parent, children).await?;
+            continue;
+        }
+
+        // 部分提交时单个母题失败只回滚到保存点
+        let mut savepoint = tx.begin().await.map_err(|e| {
+            error!("Error beginning savepoint: {}", e);
+            Error::new(ErrorKind::Other, "启动事务失败")
+        })?;
+        match tx_insert_group(&mut savepoint, &dict, parent, children).await {
+            Ok(_) => savepoint.commit().await.map_err(|e| {
+                error!("Error releasing savepoint: {}", e);
+                Error::new(ErrorKind::Other, "提交事务失败")
+            })?,
+            Err(e) => {
+                savepoint.rollback().await.map_err(|e| {
+                    error!("Error rolling back savepoint: {}", e);
+                    Error::new(ErrorKind::Other, "回滚事务失败")
+                })?;
+                for prepared in std::iter::once(parent).chain(children.iter_mut()) {
+                    if prepared.item.errors.is_empty() {
+                        prepared.item.outcome = ImportOutcome::Failed;
+                        prepared.item.question_id = None;
+                        prepared.item.errors.push(e.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    ctx.progress("提交事务", total, total).await;
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "提交事务失败")
+    })?;
 
-            result.push(format!("添加 {}\n", simple_child_title));
+    // 添加的题目数量和变式题关联数量
+    let mut question_count = 0;
+    let mut similar_count = 0;
+    let mut skipped_count = 0;
+    let mut items: Vec<QuestionImportItem> = vec![];
+    for (parent, children) in groups {
+        for prepared in std::iter::once(parent).chain(children) {
+            let item = prepared.item;
+            for warning in &item.warnings {
+                warnings.push(format!("{}: {}", position(&item), warning));
+            }
+            if item.outcome == ImportOutcome::Added {
+                question_count += 1;
+                if !item.is_parent {
+                    similar_count += 1;
+                }
+                result.push(format!("添加 {}\n", item.title));
+            } else {
+                skipped_count += 1;
+                result.push(format!("跳过 {}\n", item.title));
+                for e in &item.errors {
+                    warnings.push(format!("{} 已跳过: {}", position(&item), e));
+                }
+            }
+            items.push(item);
         }
+    }
+
+    result.push("文件处理完成\n".to_string());
+
+    Ok(QuestionUploadOutput {
+        question_count,
+        similar_count,
+        logs: result,
+        warnings,
+        skipped_count,
+        items,
+    })
+}
+
+// 题目在文件中的位置, 用于警告和错误信息
+fn position(item: &QuestionImportItem) -> String {
+    format!(
+        "{} (第 {}-{} 行)",
+        item.title, item.line_start, item.line_end
+    )
+}
+
+// 检查解析出的题目字段, 记录警告和无法添加的原因
+fn prepare(raw: RawQuestion, is_parent: bool, dict: &ImportDict) -> Prepared {
+    let mut warnings: Vec<String> = vec![];
+    let mut errors: Vec<String> = vec![];
+
+    for tag in &raw.unknown_tags {
+        warnings.push(format!("无法识别的加粗标签: {}", tag));
+    }
+
+    if raw.stem.is_empty() {
+        errors.push("没有解析到题干".to_string());
+    }
+    if raw.answer.is_empty() {
+        warnings.push("缺少参考答案".to_string());
+    }
+
+    let difficulty = markdown_parse::get_difficulty_level(&raw.difficulty_level);
+    if raw.difficulty_level.is_empty() {
+        warnings.push("缺少难度, 默认为 1".to_string());
+    } else if difficulty == Decimal::from(1) && raw.difficulty_level != "1" {
+        warnings.push(format!("难度 {} 无法识别, 默认为 1", raw.difficulty_level));
+    }
+
+    // 题目类型列表为空时已经记录文件相关的警告
+    let (question_type_id, options) = get_question_type_and_options(&raw, &dict.question_type_list);
+    if !dict.question_type_list.is_empty() {
+        let matched = dict
+            .question_type_list
+            .iter()
+            .find(|item| item.id == question_type_id);
+        let used = matched
+            .map(|item| format!("使用 {}", item.item_value))
+            .unwrap_or_else(|| "题目类型将为空".to_string());
+        if raw.question_type.is_empty() {
+            warnings.push(format!("缺少题目类型, {}", used));
+        } else if !matched.is_some_and(|item| item.item_value.contains(raw.question_type.as_str()))
+        {
+            warnings.push(format!("题目类型 {} 未匹配, {}", raw.question_type, used));
+        }
+        if matched.is_some_and(|item| item.is_select)
+            && options.is_none_or(|options| options.is_empty())
+        {
+            warnings.push("选择题没有解析到选项".to_string());
+        }
+    }
+
+    // 压缩包中没有的图片保留原有的引用
+    for path in raw_image_refs(&raw) {
+        if !dict.image_names.contains_key(&path) {
+            warnings.push(format!("图片 {} 不存在", path));
+        }
+    }
+
+    // 无法匹配的知识点不影响添加
+    let (knowledge_ids, unresolved) = dict.knowledge_resolver.resolve(&raw.knowledge);
+    if !unresolved.is_empty() {
+        warnings.push(format!("未匹配到知识点: {}", unresolved.join("、")));
+    }
+
+    let item = QuestionImportItem {
+        title: raw.title.clone(),
+        is_parent,
+        line_start: raw.line_start,
+        line_end: raw.line_end,
+        fields: QuestionImportFields {
+            stem: raw.stem.clone(),
+            choices: raw.choices.clone(),
+            difficulty_level: raw.difficulty_level.clone(),
+            difficulty,
+            stage: raw.stage.clone(),
+            question_type: raw.question_type.clone(),
+            question_type_id,
+            knowledge: raw.knowledge.clone(),
+            knowledge_ids,
+            answer: raw.answer.clone(),
+            analysis: raw.analysis.clone(),
+            detail: raw.detail.clone(),
+        },
+        outcome: if errors.is_empty() {
+            ImportOutcome::Valid
+        } else {
+            ImportOutcome::Skipped
+        },
+        question_id: None,
+        warnings,
+        errors,
+    };
+
+    Prepared { raw, item }
+}
+
+// 添加母题及其没有问题的变式题, 并关联知识点和变式题
+async fn tx_insert_group(
+    tx: &mut Transaction<'_, Postgres>,
+    dict: &ImportDict,
+    parent: &mut Prepared,
+    children: &mut [Prepared],
+) -> Result<(), Error> {
+    // 母题
+    let parent_req = to_req(
+        &parent.raw,
+        None,
+        dict.question_cate_id,
+        &dict.question_type_list,
+        &dict.question_tag_list,
+        &dict.image_names,
+    );
+    info!("Add parent question name: {} begin", parent.item.title);
+    let parent_id = Question::tx_insert(tx, parent_req)
+        .await
+        .map_err(|err| {
+            error!("Insert parent of question err: {}", err);
+            Error::new(ErrorKind::Other, "母题添加失败")
+        })?
+        .id;
+    parent.item.question_id = Some(parent_id);
+    parent.item.outcome = ImportOutcome::Added;
+
+    let mut knowledge_pairs: Vec<(i64, i32)> = parent
+        .item
+        .fields
+        .knowledge_ids
+        .iter()
+        .map(|knowledge_id| (parent_id, *knowledge_id))
+        .collect();
+
+    // 变式题列表为空正常
+    let mut children: Vec<&mut Prepared> = children
+        .iter_mut()
+        .filter(|child| child.item.errors.is_empty())
+        .collect();
+    if !children.is_empty() {
+        let children_req: Vec<CreateQuestionReq> = children
+            .iter()
+            .map(|child| {
+                to_req(
+                    &child.raw,
+                    Some(parent_id),
+                    dict.question_cate_id,
+                    &dict.question_type_list,
+                    &dict.question_tag_list,
+                    &dict.image_names,
+                )
+            })
+            .collect();
 
         // 得到所有添加的变式题主键列表
-        let children_ids = Question::tx_batch_insert(&mut tx, children_req)
+        let children_ids = Question::tx_batch_insert(tx, children_req)
             .await
             .map_err(|err| {
                 error!("Batch insert child of question err: {}", err);
@@ -204,48 +518,48 @@ async fn single(
             })?;
         info!("Add all child question end");
 
-        info!("Add relation parent child question begin");
+        for (child, child_id) in children.iter_mut().zip(children_ids.iter()) {
+            child.item.question_id = Some(*child_id);
+            child.item.outcome = ImportOutcome::Added;
+            knowledge_pairs.extend(
+                child
+                    .item
+                    .fields
+                    .knowledge_ids
+                    .iter()
+                    .map(|knowledge_id| (*child_id, *knowledge_id)),
+            );
+        }
+
+        // 关联母题和变式题对应关系
         let similar_pairs: Vec<(i64, i64)> = children_ids
             .into_iter()
-            .map(|child| (parent.id, child))
+            .map(|child_id| (parent_id, child_id))
             .collect();
-
-        // 关联母题和变式题对应关系
-        QuestionSimilar::batch_insert(&mut tx, similar_pairs)
+        QuestionSimilar::batch_insert(tx, similar_pairs)
             .await
             .map_err(|e| {
                 error!("Batch insert child of question similar relation err: {}", e);
                 Error::new(ErrorKind::Other, "母题和变式题关联失败")
             })?;
-        info!("Add relation parent child question end");
-
-        result.push("关联母题和变式题\n".to_string());
-
-        info!("Add parent question name: {} end", simple_parent_title);
     }
 
-    tx.commit().await.map_err(|e| {
-        error!("Error committing transaction: {}", e);
-        Error::new(ErrorKind::Other, "提交事务失败")
-    })?;
+    tx_insert_knowledge(tx, knowledge_pairs).await?;
+    info!("Add parent question name: {} end", parent.item.title);
 
-    result.push("文件处理完成\n".to_string());
+    Ok(())
+}
 
-    // 更新任务列表为执行成功
-    if let Err(e) = Task::update_by_id(
-        &app_config.db,
-        &task_info.id,
-        TaskStatus::Success as i16,
-        result.join("\n"),
-    )
-    .await
-    {
-        error!(
-            "Task done, but update task id: {}, name {} status=Failed err: {}",
-            task_info.id, task_info.name, e
-        );
-        // 这次更新失败不做任何处理, 需要关注这类日志
-    }
+async fn tx_insert_knowledge(
+    tx: &mut Transaction<'_, Postgres>,
+    pairs: Vec<(i64, i32)>,
+) -> Result<(), Error> {
+    QuestionKnowledge::tx_batch_insert(tx, pairs)
+        .await
+        .map_err(|e| {
+            error!("Batch insert question knowledge err: {}", e);
+            Error::new(ErrorKind::Other, "题目关联知识点失败")
+        })?;
 
     Ok(())
 }
@@ -291,14 +605,35 @@ fn get_question_type_and_options(
 
 // 通过 markdown 文档文本内容转为请求体
 fn to_req(
-    raw: RawQuestion,
+    raw: &RawQuestion,
     parent_id: Option<i64>,
-    task_info: &Task,
+    question_cate_id: i32,
     question_type_list: &[TextbookDict],
     question_tag_list: &[TextbookDict],
+    image_names: &HashMap<String, String>,
 ) -> CreateQuestionReq {
     let (question_type_id, options) = get_question_type_and_options(&raw, question_type_list);
 
+    // 图片引用替换为存储后的名称, 同时记录到对应内容的图片列表
+    let (stem, images) = markdown_parse::rewrite_images(&raw.stem, image_names);
+    let options = options.map(|Json(options)| {
+        Json(
+            options
+                .into_iter()
+                .map(|mut option| {
+                    let (content, images) =
+                        markdown_parse::rewrite_images(&option.content, image_names);
+                    option.content = content;
+                    option.images = non_empty(images);
+                    option
+                })
+                .collect(),
+        )
+    });
+    let (answer, _) = markdown_parse::rewrite_images(&raw.answer, image_names);
+    let (analysis, analysis_images) = markdown_parse::rewrite_images(&raw.analysis, image_names);
+    let (detail, detail_images) = markdown_parse::rewrite_images(&raw.detail, image_names);
+
     // 查找变式题标签
     let question_tag_info = question_tag_list
         .iter()
@@ -311,7 +646,7 @@ fn to_req(
 
     CreateQuestionReq {
         id: None,
-        question_cate_id: task_info.question_cate_id as i32,
+        question_cate_id,
         source_id: parent_id,
         question_type_id,
         question_tag_ids,
@@ -320,22 +655,22 @@ fn to_req(
         source: "".to_string(),
         original_name: "".to_string(),
         status: QuestionStatus::Draft as i16,
-        title: raw.stem.clone(),
-        content_plain: Some(question::to_plain_text(&raw.stem)),
+        content_plain: Some(question::to_plain_text(&stem)),
+        title: stem,
         comment: None,
         difficulty_level: markdown_parse::get_difficulty_level(&raw.difficulty_level),
-        images: None,
+        images: non_empty(images).map(Json),
         options,
         options_layout: Some(1),
-        answer: Some(raw.answer),
-        knowledge: Some(raw.knowledge),
+        answer: Some(answer),
+        knowledge: Some(raw.knowledge.clone()),
         analysis: Some(Json(Content {
-            content: raw.analysis,
-            images: None,
+            content: analysis,
+            images: non_empty(analysis_images),
         })),
         process: Some(Json(Content {
-            content: raw.detail,
-            images: None,
+            content: detail,
+            images: non_empty(detail_images),
         })),
         steps: None,
         remark: None,
@@ -380,6 +715,7 @@ pub async fn parse_question_snippet(req: QuestionSnippetReq) -> Result<CreateQue
             item_value: r.item_value,
             sort_order: r.sort_order,
             is_select: r.is_select,
+            is_hidden: false,
         })
         .collect();
 
@@ -424,3 +760,103 @@ pub async fn parse_question_snippet(req: QuestionSnippetReq) -> Result<CreateQue
         remark_ext: Some("文本片段解析".to_string()),
     })
 }
+
+/// 预览题目文件的解析结果, 不写入任何数据
+/// 返回每个题目将要添加的内容, 警告和字典匹配结果, 方便创建任务前修改文件
+pub async fn preview(
+    app_conf: web::Data<AppConfig>,
+    req: QuestionPreviewReq,
+) -> Result<QuestionPreviewResp, Error> {
+    let (content, images) = match (req.url.as_deref(), req.content) {
+        (_, Some(content)) if !content.trim().is_empty() => (content, HashMap::new()),
+        (Some(url), _) if !url.is_empty() => {
+            // 只能读取上传目录中的文件
+            if Path::new(url).file_name().and_then(|name| name.to_str()) != Some(url) {
+                return Err(Error::new(ErrorKind::InvalidInput, "文件名称不正确"));
+            }
+            let file_path = format!("{}/{}/{}", app_conf.meta_path, meta::FILE_NAME, url);
+            let content = fs::read(file_path).map_err(|e| {
+                error!("Read preview file {} err: {}", url, e);
+                Error::new(ErrorKind::NotFound, "文件不存在或者无法读取")
+            })?;
+            parse_source(content)?
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "需要指定文件名称或者文本内容",
+            ));
+        }
+    };
+
+    let all_questions = markdown_parse::get_questions(&content)?;
+    if all_questions.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "没有读取到任何有效的题目",
+        ));
+    }
+
+    let mut warnings: Vec<String> = vec![];
+    let mut dict = load_import_dict(
+        &app_conf.db,
+        req.question_cate_id,
+        req.textbook_id,
+        &mut warnings,
+    )
+    .await?;
+    // 预览不保存图片, 图片名称使用压缩包中的路径
+    dict.image_names = images
+        .keys()
+        .map(|path| (path.clone(), path.clone()))
+        .collect();
+
+    let mut items: Vec<QuestionPreviewItem> = vec![];
+    for (parent, children) in prepare_all(all_questions, &dict) {
+        // 母题还没有添加, 变式题的母题主键为 0
+        items.push(to_preview_item(parent, None, &dict));
+        for child in children {
+            items.push(to_preview_item(child, Some(0), &dict));
+        }
+    }
+
+    Ok(QuestionPreviewResp {
+        question_count: items.len(),
+        valid_count: items
+            .iter()
+            .filter(|item| item.item.outcome == ImportOutcome::Valid)
+            .count(),
+        warnings,
+        question_type_list: dict
+            .question_type_list
+            .iter()
+            .map(|item| item.item_value.clone())
+            .collect(),
+        question_tag_list: dict
+            .question_tag_list
+            .iter()
+            .map(|item| item.item_value.clone())
+            .collect(),
+        items,
+    })
+}
+
+fn to_preview_item(
+    prepared: Prepared,
+    parent_id: Option<i64>,
+    dict: &ImportDict,
+) -> QuestionPreviewItem {
+    let req = to_req(
+        &prepared.raw,
+        parent_id,
+        dict.question_cate_id,
+        &dict.question_type_list,
+        &dict.question_tag_list,
+        &dict.image_names,
+    );
+
+    QuestionPreviewItem {
+        item: prepared.item,
+        req,
+    }
+}
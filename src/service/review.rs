@@ -0,0 +1,254 @@
+use crate::AppConfig;
+use crate::api::question::QuestionBaseResp;
+use crate::api::review::{BatchReviewReq, ReviewClaimReq, ReviewQueueReq, ReviewResultResp};
+use crate::model::question::{Question, QuestionStatus, ReviewClaim};
+use crate::model::question_knowledge::QuestionKnowledge;
+use crate::service::question::to_base_resp;
+use crate::service::review_comment;
+use crate::util::local::to_local_datetime;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+// 领取后的锁定时间, 超时后其他审核人可以重新领取
+pub const REVIEW_LOCK_SECS: f64 = 30.0 * 60.0;
+// 队列默认和最大返回数量
+const QUEUE_LIMIT: i64 = 20;
+const QUEUE_MAX_LIMIT: i64 = 100;
+
+// 待审核队列
+pub async fn queue(
+    app_conf: web::Data<AppConfig>,
+    req: ReviewQueueReq,
+) -> Result<Vec<QuestionBaseResp>, Error> {
+    let limit = req.limit.unwrap_or(QUEUE_LIMIT).clamp(1, QUEUE_MAX_LIMIT);
+
+    let rows = Question::find_review_queue(
+        &app_conf.get_ref().db,
+        req.reviewer_id,
+        req.textbook_id,
+        REVIEW_LOCK_SECS,
+        limit,
+    )
+    .await
+    .map_err(|e| {
+        error!("question review queue err: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+
+    let mut list: Vec<QuestionBaseResp> = rows.iter().map(to_base_resp).collect();
+    review_comment::fill_question_unresolved(&app_conf.get_ref().db, &mut list).await?;
+
+    Ok(list)
+}
+
+// 审核失败的原因
+fn review_fail_reason(claim: Option<&ReviewClaim>, reviewer_id: i64) -> String {
+    match claim {
+        None => "题目不存在".to_string(),
+        Some(claim) if claim.status != QuestionStatus::Pending as i16 => {
+            "题目不是待审核状态".to_string()
+        }
+        Some(claim) => match (claim.review_claim_id, claim.review_claim_at) {
+            (Some(claim_id), Some(claim_at)) if claim_id != reviewer_id => format!(
+                "题目已被审核人 {} 于 {} 领取",
+                claim_id,
+                to_local_datetime(claim_at)
+            ),
+            _ => "审核失败".to_string(),
+        },
+    }
+}
+
+async fn find_claims(
+    app_conf: &web::Data<AppConfig>,
+    ids: &[i64],
+) -> Result<HashMap<i64, ReviewClaim>, Error> {
+    let rows = Question::find_review_claims(&app_conf.get_ref().db, ids)
+        .await
+        .map_err(|e| {
+            error!("question review claims err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(rows.into_iter().map(|row| (row.id, row)).collect())
+}
+
+// 领取题目
+pub async fn claim(app_conf: web::Data<AppConfig>, req: ReviewClaimReq) -> Result<bool, Error> {
+    let row = Question::claim_review(
+        &app_conf.get_ref().db,
+        req.id,
+        req.reviewer_id,
+        REVIEW_LOCK_SECS,
+    )
+    .await
+    .map_err(|e| {
+        error!("question review claim err: {:?}", e);
+        Error::new(ErrorKind::Other, "领取失败")
+    })?;
+
+    if row == 0 {
+        let claims = find_claims(&app_conf, &[req.id]).await?;
+        return Err(Error::new(
+            ErrorKind::Other,
+            review_fail_reason(claims.get(&req.id), req.reviewer_id),
+        ));
+    }
+
+    Ok(true)
+}
+
+// 释放领取的题目
+pub async fn release(app_conf: web::Data<AppConfig>, req: ReviewClaimReq) -> Result<bool, Error> {
+    let row = Question::release_review(&app_conf.get_ref().db, req.id, req.reviewer_id)
+        .await
+        .map_err(|e| {
+            error!("question review release err: {:?}", e);
+            Error::new(ErrorKind::Other, "释放失败")
+        })?;
+
+    Ok(row > 0)
+}
+
+// 审核一个题目, 审核通过的修订稿更新到原题
+async fn review_one(
+    db: &PgPool,
+    id: i64,
+    status: i16,
+    reviewer_id: i64,
+    reject_reason: Option<String>,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let rows = Question::tx_review_by_id(
+        &mut tx,
+        id,
+        status,
+        reviewer_id,
+        reject_reason,
+        REVIEW_LOCK_SECS,
+    )
+    .await?;
+    if rows > 0
+        && status == QuestionStatus::Published as i16
+        && let Some(revision_of) = Question::tx_find_revision_of(&mut tx, id).await?
+    {
+        // 锁定原题, 和采纳勘误打开修订稿依次执行
+        Question::tx_lock_by_id(&mut tx, revision_of).await?;
+        if Question::tx_apply_revision(&mut tx, id, revision_of).await? > 0 {
+            QuestionKnowledge::tx_delete_by_question_id(&mut tx, revision_of).await?;
+            QuestionKnowledge::tx_copy(&mut tx, id, revision_of).await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(rows)
+}
+
+// 只能审核通过或者拒绝, 拒绝需要填写原因, 返回去掉首尾空白的原因
+fn check_review(status: i16, reject_reason: Option<String>) -> Result<Option<String>, Error> {
+    if status == QuestionStatus::Published as i16 {
+        Ok(None)
+    } else if status == QuestionStatus::Rejected as i16 {
+        match reject_reason.as_deref().map(str::trim) {
+            Some(reason) if !reason.is_empty() => Ok(Some(reason.to_string())),
+            _ => Err(Error::new(ErrorKind::Other, "拒绝需要填写原因")),
+        }
+    } else {
+        Err(Error::new(ErrorKind::Other, "审核状态只能是通过或者拒绝"))
+    }
+}
+
+/// 审核单个题目, 规则和批量审核相同
+pub async fn review(
+    app_conf: &web::Data<AppConfig>,
+    id: i64,
+    status: i16,
+    reviewer_id: i64,
+    reject_reason: Option<String>,
+) -> Result<bool, Error> {
+    let reject_reason = check_review(status, reject_reason)?;
+
+    let row = review_one(
+        &app_conf.get_ref().db,
+        id,
+        status,
+        reviewer_id,
+        reject_reason,
+    )
+    .await
+    .map_err(|e| {
+        error!("question review id: {} err: {:?}", id, e);
+        Error::new(ErrorKind::Other, "审核失败")
+    })?;
+    if row == 0 {
+        let claims = find_claims(app_conf, &[id]).await?;
+        return Err(Error::new(
+            ErrorKind::Other,
+            review_fail_reason(claims.get(&id), reviewer_id),
+        ));
+    }
+
+    Ok(true)
+}
+
+/// 批量审核
+/// 只有待审核的题目可以审核通过或者拒绝, 拒绝需要填写原因, 每个题目单独审核互不影响
+/// 勘误的修订稿审核通过后内容更新到原题, 修订稿归档
+pub async fn batch(
+    app_conf: web::Data<AppConfig>,
+    req: BatchReviewReq,
+) -> Result<Vec<ReviewResultResp>, Error> {
+    if req.ids.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "题目列表不能为空"));
+    }
+
+    let reject_reason = check_review(req.status, req.reject_reason)?;
+
+    let db = &app_conf.get_ref().db;
+
+    let mut res = Vec::with_capacity(req.ids.len());
+    let mut failed_ids = vec![];
+    for id in &req.ids {
+        let result = review_one(db, *id, req.status, req.reviewer_id, reject_reason.clone()).await;
+        match result {
+            Ok(row) if row > 0 => res.push(ReviewResultResp {
+                id: *id,
+                success: true,
+                message: "".to_string(),
+            }),
+            Ok(_) => {
+                failed_ids.push(*id);
+                res.push(ReviewResultResp {
+                    id: *id,
+                    success: false,
+                    message: "".to_string(),
+                });
+            }
+            Err(e) => {
+                error!("question review id: {} err: {:?}", id, e);
+                res.push(ReviewResultResp {
+                    id: *id,
+                    success: false,
+                    message: "审核失败".to_string(),
+                });
+            }
+        }
+    }
+
+    // 没有更新成功的查询具体原因
+    if !failed_ids.is_empty() {
+        let claims = find_claims(&app_conf, &failed_ids).await?;
+        for item in res.iter_mut() {
+            if !item.success && item.message.is_empty() {
+                item.message = review_fail_reason(claims.get(&item.id), req.reviewer_id);
+            }
+        }
+    }
+
+    Ok(res)
+}
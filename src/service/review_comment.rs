@@ -0,0 +1,307 @@
+use crate::AppConfig;
+use crate::api::paper::PaperResp;
+use crate::api::question::QuestionBaseResp;
+use crate::api::review_comment::{
+    CreateReviewCommentReq, RemoveReviewCommentReq, ResolveReviewCommentReq, ReviewCommentResp,
+};
+use crate::model::paper::Paper;
+use crate::model::question::Question;
+use crate::model::review_comment::{CommentTarget, ReviewComment};
+use crate::util::local::to_local_datetime;
+use actix_web::web;
+use log::error;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+// 可以评论的字段, 后面可以跟 . 加选项或者序号, 比如 options.B steps.2
+const QUESTION_ANCHORS: [&str; 9] = [
+    "title",
+    "comment",
+    "options",
+    "answer",
+    "knowledge",
+    "analysis",
+    "process",
+    "remark",
+    "steps",
+];
+const PAPER_ANCHORS: [&str; 4] = ["title", "remark", "groups", "questions"];
+
+// 评论内容最大长度
+const CONTENT_MAX_LEN: usize = 2000;
+
+// 检查评论对象类型, 返回可以评论的字段
+fn target_anchors(target_type: i16) -> Result<&'static [&'static str], Error> {
+    if target_type == CommentTarget::Question as i16 {
+        Ok(&QUESTION_ANCHORS)
+    } else if target_type == CommentTarget::Paper as i16 {
+        Ok(&PAPER_ANCHORS)
+    } else {
+        Err(Error::new(ErrorKind::Other, "评论对象类型错误"))
+    }
+}
+
+// 检查评论的字段, 空字符串当作不指定字段
+pub fn normalize_anchor(target_type: i16, anchor: Option<String>) -> Result<Option<String>, Error> {
+    let anchor = match anchor.as_deref().map(str::trim) {
+        Some(anchor) if !anchor.is_empty() => anchor.to_string(),
+        _ => return Ok(None),
+    };
+
+    let (field, suffix) = match anchor.split_once('.') {
+        Some((field, suffix)) => (field, Some(suffix)),
+        None => (anchor.as_str(), None),
+    };
+
+    let valid_suffix = suffix.is_none_or(|suffix| {
+        !suffix.is_empty()
+            && suffix.len() <= 32
+            && suffix.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    if !target_anchors(target_type)?.contains(&field) || !valid_suffix {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("评论字段错误: {}", anchor),
+        ));
+    }
+
+    Ok(Some(anchor))
+}
+
+// 检查评论对象是否存在
+async fn check_target(pool: &PgPool, target_type: i16, target_id: i64) -> Result<(), Error> {
+    let exists = if target_type == CommentTarget::Question as i16 {
+        match Question::find_by_id(pool, target_id).await {
+            Ok(_) => true,
+            Err(sqlx::Error::RowNotFound) => false,
+            Err(e) => {
+                error!("review comment find question err: {:?}", e);
+                return Err(Error::new(ErrorKind::Other, "查询失败"));
+            }
+        }
+    } else {
+        Paper::find_by_id(pool, target_id)
+            .await
+            .map_err(|e| {
+                error!("review comment find paper err: {:?}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?
+            .is_some()
+    };
+
+    if !exists {
+        return Err(Error::new(ErrorKind::Other, "评论对象不存在"));
+    }
+
+    Ok(())
+}
+
+async fn find_comment(pool: &PgPool, id: i64) -> Result<ReviewComment, Error> {
+    ReviewComment::find_by_id(pool, id)
+        .await
+        .map_err(|e| {
+            error!("review comment find by id err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .ok_or_else(|| Error::new(ErrorKind::Other, "评论不存在"))
+}
+
+/// 添加评论
+/// 回复只能挂在第一层评论下面, 跟随第一层评论的对象和字段, 回复已解决的评论会重新打开
+pub async fn add(
+    app_conf: web::Data<AppConfig>,
+    req: CreateReviewCommentReq,
+) -> Result<i64, Error> {
+    let content = req.content.trim();
+    if content.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "评论内容不能为空"));
+    }
+    if content.chars().count() > CONTENT_MAX_LEN {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("评论内容不能超过 {} 个字", CONTENT_MAX_LEN),
+        ));
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let parent_id = req.parent_id.unwrap_or_default();
+    let (target_type, target_id, anchor) = if parent_id > 0 {
+        let parent = find_comment(db, parent_id).await?;
+        if parent.parent_id > 0 {
+            return Err(Error::new(ErrorKind::Other, "只能回复第一层评论"));
+        }
+        if parent.is_resolved {
+            ReviewComment::update_resolved(db, parent.id, false, req.author_id)
+                .await
+                .map_err(|e| {
+                    error!("review comment reopen err: {:?}", e);
+                    Error::new(ErrorKind::Other, "回复失败")
+                })?;
+        }
+        (parent.target_type, parent.target_id, parent.anchor)
+    } else {
+        let anchor = normalize_anchor(req.target_type, req.anchor)?;
+        check_target(db, req.target_type, req.target_id).await?;
+        (req.target_type, req.target_id, anchor)
+    };
+
+    let id = ReviewComment::insert(
+        db,
+        target_type,
+        target_id,
+        parent_id,
+        anchor,
+        req.author_id,
+        content,
+    )
+    .await
+    .map_err(|e| {
+        error!("review comment insert err: {:?}", e);
+        Error::new(ErrorKind::Other, "添加失败")
+    })?;
+
+    Ok(id)
+}
+
+// 解决或者重新打开, 只有第一层评论有解决状态
+pub async fn resolve(
+    app_conf: web::Data<AppConfig>,
+    req: ResolveReviewCommentReq,
+) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let row = find_comment(db, req.id).await?;
+    if row.parent_id > 0 {
+        return Err(Error::new(ErrorKind::Other, "回复不能单独解决"));
+    }
+    if row.is_resolved == req.resolved {
+        return Ok(true);
+    }
+
+    let rows = ReviewComment::update_resolved(db, req.id, req.resolved, req.operator_id)
+        .await
+        .map_err(|e| {
+            error!("review comment resolve err: {:?}", e);
+            Error::new(ErrorKind::Other, "更新失败")
+        })?;
+
+    Ok(rows > 0)
+}
+
+// 删除评论, 只能删除自己的
+pub async fn remove(
+    app_conf: web::Data<AppConfig>,
+    req: RemoveReviewCommentReq,
+) -> Result<bool, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let row = find_comment(db, req.id).await?;
+    if row.author_id != req.operator_id {
+        return Err(Error::new(ErrorKind::Other, "只能删除自己的评论"));
+    }
+
+    let rows = ReviewComment::delete(db, req.id).await.map_err(|e| {
+        error!("review comment delete err: {:?}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    Ok(rows > 0)
+}
+
+fn to_resp(row: ReviewComment) -> ReviewCommentResp {
+    ReviewCommentResp {
+        id: row.id,
+        parent_id: row.parent_id,
+        anchor: row.anchor,
+        author_id: row.author_id,
+        content: row.content,
+        is_resolved: row.is_resolved,
+        resolved_id: row.resolved_id,
+        resolved_at: row.resolved_at.map(to_local_datetime),
+        replies: vec![],
+        created_at: to_local_datetime(row.created_at),
+    }
+}
+
+// 评论列表, 第一层评论按时间先后, 回复挂在对应的评论下面
+pub async fn list(
+    app_conf: web::Data<AppConfig>,
+    target_type: i16,
+    target_id: i64,
+) -> Result<Vec<ReviewCommentResp>, Error> {
+    target_anchors(target_type)?;
+
+    let rows = ReviewComment::find_by_target(&app_conf.get_ref().db, target_type, target_id)
+        .await
+        .map_err(|e| {
+            error!("review comment list err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let mut replies: HashMap<i64, Vec<ReviewCommentResp>> = HashMap::new();
+    let mut threads = vec![];
+    for row in rows {
+        if row.parent_id > 0 {
+            replies.entry(row.parent_id).or_default().push(to_resp(row));
+        } else {
+            threads.push(to_resp(row));
+        }
+    }
+    for thread in threads.iter_mut() {
+        thread.replies = replies.remove(&thread.id).unwrap_or_default();
+    }
+
+    Ok(threads)
+}
+
+// 对象未解决的评论数量
+async fn unresolved_map(
+    pool: &PgPool,
+    target_type: i16,
+    target_ids: &[i64],
+) -> Result<HashMap<i64, i64>, Error> {
+    if target_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows = ReviewComment::count_unresolved(pool, target_type, target_ids)
+        .await
+        .map_err(|e| {
+            error!("review comment count unresolved err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.target_id, row.count))
+        .collect())
+}
+
+// 补充题目未解决的评论数量
+pub async fn fill_question_unresolved(
+    pool: &PgPool,
+    list: &mut [QuestionBaseResp],
+) -> Result<(), Error> {
+    let ids: Vec<i64> = list.iter().map(|item| item.id).collect();
+    let counts = unresolved_map(pool, CommentTarget::Question as i16, &ids).await?;
+    for item in list.iter_mut() {
+        item.unresolved_comments = counts.get(&item.id).copied().unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+// 补充试卷未解决的评论数量
+pub async fn fill_paper_unresolved(pool: &PgPool, list: &mut [PaperResp]) -> Result<(), Error> {
+    let ids: Vec<i64> = list.iter().filter_map(|item| item.id).collect();
+    let counts = unresolved_map(pool, CommentTarget::Paper as i16, &ids).await?;
+    for item in list.iter_mut() {
+        if let Some(id) = item.id {
+            item.unresolved_comments = counts.get(&id).copied().unwrap_or_default();
+        }
+    }
+
+    Ok(())
+}
@@ -1,31 +1,76 @@
 use crate::AppConfig;
-use crate::api::task::{TaskAddReq, TaskInfoResp, TaskListReq, TaskListResp};
-use crate::model::task::{Task, TaskStatus};
+use crate::api::task::{
+    TaskAddReq, TaskEventResp, TaskHistoryResp, TaskInfoResp, TaskListReq, TaskListResp,
+    TaskOperateReq, TaskProgressResp, TaskRetryReq,
+};
+use crate::constant::meta;
+use crate::model::task::{NewTask, Task, TaskStatus, TaskType};
+use crate::model::task_history::{self, TaskHistory};
+use crate::task::job::Job;
+use crate::task::question::{QuestionUploadJob, QuestionUploadParams};
+use crate::task::{MAX_ATTEMPTS, registry};
 use crate::util::local::to_local_datetime;
 use actix_web::web;
+use futures_util::{Stream, stream};
 use log::error;
+use serde_json::Value;
 use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
 
 // 添加任务
 pub async fn add(app_conf: web::Data<AppConfig>, req: TaskAddReq) -> Result<i64, Error> {
     let db = &app_conf.get_ref().db;
 
-    let row_id = Task::insert(
-        db,
-        req.question_cate_id,
-        req.task_type,
-        &req.name,
-        1,
-        &req.url,
-        &req.email,
-        req.textbook_id,
-    )
-    .await
-    .map_err(|e| {
+    // 没有指定任务类型名称时按原有的任务类型处理
+    let kind = match req.kind {
+        Some(kind) => kind,
+        None if req.task_type == TaskType::UploadQuestion as i16 => {
+            QuestionUploadJob::NAME.to_string()
+        }
+        None => return Err(Error::new(ErrorKind::InvalidInput, "需要指定任务类型")),
+    };
+    let params = match req.params {
+        Some(params) => params,
+        None if kind == QuestionUploadJob::NAME => serde_json::to_value(QuestionUploadParams {
+            question_cate_id: req.question_cate_id as i32,
+            textbook_id: req.textbook_id,
+            url: req.url.clone(),
+            partial: req.partial,
+        })
+        .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?,
+        None => Value::Object(Default::default()),
+    };
+    registry().check(&kind, &params)?;
+
+    let task = NewTask {
+        kind,
+        task_type: req.task_type,
+        name: req.name,
+        author_id: meta::TEMP_ADMIN_ID,
+        params,
+        question_cate_id: req.question_cate_id,
+        textbook_id: req.textbook_id,
+        url: req.url,
+        email: req.email,
+    };
+    let row_id = Task::insert(db, &task).await.map_err(|e| {
         error!("task add err: {:?}", e);
         Error::new(ErrorKind::Other, "任务添加失败")
     })?;
 
+    if let Err(e) = TaskHistory::insert(
+        db,
+        row_id,
+        task_history::ACTION_CREATE,
+        task.author_id,
+        None,
+        None,
+    )
+    .await
+    {
+        error!("task add history err: {:?}", e);
+    }
+
     Ok(row_id)
 }
 
@@ -33,13 +78,20 @@ fn to_base_resp(row: &Task) -> TaskInfoResp {
     TaskInfoResp {
         id: row.id,
         question_cate_id: row.question_cate_id,
-        task_type: 0,
+        task_type: row.task_type,
+        kind: row.kind.clone(),
         name: row.name.clone(),
         author: "admin".to_string(),
         status: row.status,
         status_desc: TaskStatus::desc(row.status).to_string(),
         email: row.email.clone(),
         result: row.result.clone(),
+        attempts: row.attempts,
+        run_after: row.run_after.map(to_local_datetime),
+        cancel_requested: row.cancel_requested,
+        progress: to_progress_resp(row),
+        params: row.params.0.clone(),
+        output: row.output.as_ref().map(|output| output.0.clone()),
         created_at: to_local_datetime(row.created_at),
         updated_at: to_local_datetime(row.updated_at),
     }
@@ -94,3 +146,299 @@ pub async fn list(app_conf: web::Data<AppConfig>, req: TaskListReq) -> Result<Ta
         total,
     })
 }
+
+// 查询任务并检查操作权限, 只有任务创建人和管理员可以操作
+async fn find_with_permission(
+    app_conf: &web::Data<AppConfig>,
+    id: i64,
+    operator_id: i64,
+) -> Result<Task, Error> {
+    let row = Task::find_by_id(&app_conf.db, id)
+        .await
+        .map_err(|e| {
+            error!("task find by id err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?
+        .ok_or_else(|| Error::new(ErrorKind::Other, "任务不存在"))?;
+
+    if operator_id != row.author_id && operator_id != meta::TEMP_ADMIN_ID {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "没有权限操作该任务",
+        ));
+    }
+
+    Ok(row)
+}
+
+fn tx_err(e: sqlx::Error) -> Error {
+    error!("task operate err: {:?}", e);
+    Error::new(ErrorKind::Other, "操作失败")
+}
+
+// 重试延迟秒数, 按执行次数翻倍, 最长一小时
+fn backoff_secs(attempts: i32) -> f64 {
+    let exp = (attempts.max(1) - 1).min(7) as u32;
+    (30u64 * 2u64.pow(exp)).min(3600) as f64
+}
+
+/// 重试任务
+/// 失败或者取消的任务重新等待执行, 执行次数达到上限的不能再重试
+pub async fn retry(app_conf: web::Data<AppConfig>, req: TaskRetryReq) -> Result<bool, Error> {
+    let row = find_with_permission(&app_conf, req.id, req.operator_id).await?;
+
+    if row.status != TaskStatus::Failed as i16 && row.status != TaskStatus::Canceled as i16 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "只能重试处理失败或者已取消的任务",
+        ));
+    }
+    if row.attempts >= MAX_ATTEMPTS {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("任务已执行 {} 次, 不能再重试", row.attempts),
+        ));
+    }
+
+    let delay_secs = if req.backoff {
+        backoff_secs(row.attempts)
+    } else {
+        0.0
+    };
+
+    let mut tx = app_conf.db.begin().await.map_err(tx_err)?;
+
+    let rows = Task::tx_retry(&mut tx, row.id, delay_secs)
+        .await
+        .map_err(tx_err)?;
+    if rows == 0 {
+        return Err(Error::new(ErrorKind::Other, "任务状态已变更, 请刷新后重试"));
+    }
+
+    // 重试会清空上次的结果, 记录到操作记录中
+    let mut message = format!("上次结果: {}", row.result.unwrap_or_default());
+    if delay_secs > 0.0 {
+        message = format!("延迟 {} 秒执行, {}", delay_secs, message);
+    }
+    TaskHistory::insert(
+        &mut *tx,
+        row.id,
+        task_history::ACTION_RETRY,
+        req.operator_id,
+        None,
+        Some(&message),
+    )
+    .await
+    .map_err(tx_err)?;
+
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(true)
+}
+
+/// 取消任务
+/// 待执行的任务直接取消, 执行中的任务标记请求取消, 由执行进程心跳时检查后停止
+pub async fn cancel(app_conf: web::Data<AppConfig>, req: TaskOperateReq) -> Result<bool, Error> {
+    let row = find_with_permission(&app_conf, req.id, req.operator_id).await?;
+
+    let mut tx = app_conf.db.begin().await.map_err(tx_err)?;
+
+    let (rows, message) = if row.status == TaskStatus::Waiting as i16 {
+        let rows = Task::tx_cancel_waiting(&mut tx, row.id)
+            .await
+            .map_err(tx_err)?;
+        (rows, "取消待执行的任务")
+    } else if row.status == TaskStatus::Running as i16 {
+        let rows = Task::tx_request_cancel(&mut tx, row.id)
+            .await
+            .map_err(tx_err)?;
+        (rows, "请求取消执行中的任务")
+    } else {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "只能取消待处理或者处理中的任务",
+        ));
+    };
+    if rows == 0 {
+        return Err(Error::new(ErrorKind::Other, "任务状态已变更, 请刷新后重试"));
+    }
+
+    TaskHistory::insert(
+        &mut *tx,
+        row.id,
+        task_history::ACTION_CANCEL,
+        req.operator_id,
+        None,
+        Some(message),
+    )
+    .await
+    .map_err(tx_err)?;
+
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(true)
+}
+
+// 删除任务, 执行中的任务需要先取消, 操作记录保留
+pub async fn delete(app_conf: web::Data<AppConfig>, req: TaskOperateReq) -> Result<bool, Error> {
+    let row = find_with_permission(&app_conf, req.id, req.operator_id).await?;
+
+    if row.status == TaskStatus::Running as i16 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "处理中的任务不能删除, 请先取消",
+        ));
+    }
+
+    let mut tx = app_conf.db.begin().await.map_err(tx_err)?;
+
+    let rows = Task::tx_delete(&mut tx, row.id).await.map_err(tx_err)?;
+    if rows == 0 {
+        return Err(Error::new(ErrorKind::Other, "任务状态已变更, 请刷新后重试"));
+    }
+
+    let message = format!("{} {}", row.kind, row.name);
+    TaskHistory::insert(
+        &mut *tx,
+        row.id,
+        task_history::ACTION_DELETE,
+        req.operator_id,
+        None,
+        Some(&message),
+    )
+    .await
+    .map_err(tx_err)?;
+
+    tx.commit().await.map_err(tx_err)?;
+
+    Ok(true)
+}
+
+// 任务操作记录
+pub async fn history(
+    app_conf: web::Data<AppConfig>,
+    id: i64,
+) -> Result<Vec<TaskHistoryResp>, Error> {
+    let rows = TaskHistory::find_by_task_id(&app_conf.db, id)
+        .await
+        .map_err(|e| {
+            error!("task history err: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| TaskHistoryResp {
+            id: row.id,
+            action: row.action,
+            operator_id: row.operator_id,
+            worker_id: row.worker_id,
+            message: row.message,
+            created_at: to_local_datetime(row.created_at),
+        })
+        .collect())
+}
+
+fn to_progress_resp(row: &Task) -> TaskProgressResp {
+    TaskProgressResp {
+        phase: row.progress_phase.clone(),
+        done: row.progress_done,
+        total: row.progress_total,
+    }
+}
+
+// 任务事件检查间隔
+const EVENT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 没有变化时发送注释保持连接
+const EVENT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// 推送任务事件的状态
+struct EventState {
+    app_conf: web::Data<AppConfig>,
+    id: i64,
+    last: Option<(i16, String, i32, i32)>, // 上次推送的状态和进度
+    sent_at: Instant,
+    closed: bool,
+}
+
+// 生成一条事件
+fn to_event(event: &str, data: &str) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// 任务事件流
+/// 定时查询任务, 状态或者进度变化时推送, 任务结束或者被删除后结束
+pub fn events(
+    app_conf: web::Data<AppConfig>,
+    id: i64,
+) -> impl Stream<Item = Result<web::Bytes, Error>> {
+    let state = EventState {
+        app_conf,
+        id,
+        last: None,
+        sent_at: Instant::now(),
+        closed: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        if state.closed {
+            return None;
+        }
+        loop {
+            // 第一次立即推送当前状态
+            if state.last.is_some() {
+                tokio::time::sleep(EVENT_POLL_INTERVAL).await;
+            }
+
+            let row = match Task::find_by_id(&state.app_conf.db, state.id).await {
+                Ok(Some(row)) => row,
+                Ok(None) => {
+                    state.closed = true;
+                    return Some((Ok(to_event("error", "任务不存在")), state));
+                }
+                Err(e) => {
+                    error!("task events find by id err: {:?}", e);
+                    state.closed = true;
+                    return Some((Ok(to_event("error", "查询失败")), state));
+                }
+            };
+
+            let current = (
+                row.status,
+                row.progress_phase.clone(),
+                row.progress_done,
+                row.progress_total,
+            );
+            let event = match &state.last {
+                Some(last) if *last == current => None,
+                Some(last) if last.0 == row.status => Some("progress"),
+                _ => Some("status"),
+            };
+            let Some(event) = event else {
+                if state.sent_at.elapsed() >= EVENT_KEEPALIVE_INTERVAL {
+                    state.sent_at = Instant::now();
+                    return Some((Ok(web::Bytes::from(": keepalive\n\n")), state));
+                }
+                continue;
+            };
+
+            let data = TaskEventResp {
+                id: row.id,
+                status: row.status,
+                status_desc: TaskStatus::desc(row.status).to_string(),
+                result: row.result.clone(),
+                progress: to_progress_resp(&row),
+                updated_at: to_local_datetime(row.updated_at),
+            };
+            let data = serde_json::to_string(&data).unwrap_or_default();
+
+            // 成功, 失败和取消后不会再有变化
+            state.closed = row.status == TaskStatus::Success as i16
+                || row.status == TaskStatus::Failed as i16
+                || row.status == TaskStatus::Canceled as i16;
+            state.last = Some(current);
+            state.sent_at = Instant::now();
+            return Some((Ok(to_event(event, &data)), state));
+        }
+    })
+}
@@ -1,12 +1,22 @@
-use crate::api::textbook::{CreateTextbookReq, TextbookResp, UpdateTextbookReq};
+use crate::api::textbook::{
+    ArchiveTextbookReq, CreateTextbookReq, SortTextbookReq, TextbookDependencyResp, TextbookResp,
+    UpdateTextbookReq,
+};
 use crate::model::chapter_knowledge::ChapterKnowledge;
+use crate::model::chapter_mapping::ChapterMapping;
+use crate::model::knowledge_prerequisite::KnowledgePrerequisite;
+use crate::model::other_dict::TextbookDict;
+use crate::model::paper::{Paper, PaperStatus};
+use crate::model::question::{Question, QuestionStatus};
 use crate::model::question_cate::QuestionCate;
+use crate::model::question_knowledge::QuestionKnowledge;
+use crate::model::task::Task;
 use crate::model::textbook::Textbook;
 use crate::{AppConfig, constant};
 use actix_web::web;
 use log::error;
-use sqlx::PgPool;
-use std::collections::HashMap;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind};
 
 // 根据深度和父级关系将列表组合为有层级关系的列表
@@ -372,46 +382,485 @@ pub async fn edit(
     Ok(to_resp(row))
 }
 
-// 删除菜单-没有子菜单的菜单可以被删除
-pub async fn delete(app_conf: web::Data<AppConfig>, id: i32) -> Result<bool, Error> {
-    let info = info(app_conf.clone(), id).await?;
+// 批量排序和拖拽移动
+// ids 为目标父级下排好序的子级列表, 排序值按列表顺序从 1 开始重写, 未传入的原有子级依次排在后面
+// 父级发生变化的节点跟编辑一样需要检查环和同级名称, 并同步更新所有子孙节点深度
+pub async fn sort(app_conf: web::Data<AppConfig>, req: SortTextbookReq) -> Result<bool, Error> {
+    if req.ids.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "排序列表不能为空"));
+    }
+
+    let req_parent_id = req.parent_id.unwrap_or(0);
+    let mut unique_ids = HashSet::with_capacity(req.ids.len());
+    for id in &req.ids {
+        if *id == req_parent_id {
+            return Err(Error::new(ErrorKind::Other, "父级不能是自己"));
+        }
+        if !unique_ids.insert(*id) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("排序列表存在重复节点: {}", id),
+            ));
+        }
+    }
 
     let db = &app_conf.get_ref().db;
 
-    // 菜单层级检查是否存在子菜单
-    let row = Textbook::find_one_by_parent_id(db, info.id)
+    // 目标父级的深度, 移动过来的节点深度依次加1
+    let path_depth = if req_parent_id > 0 {
+        let parent_row = info(app_conf.clone(), req_parent_id).await?;
+        parent_row.path_depth.unwrap_or(0) + 1
+    } else {
+        1
+    };
+
+    let rows = Textbook::find_by_ids(db, &req.ids).await.map_err(|e| {
+        error!("Error searching textbook: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    let mut row_map: HashMap<i32, Textbook> = rows.into_iter().map(|row| (row.id, row)).collect();
+
+    // 目标父级下现有的子级, 根节点没有父级, 使用深度为1的列表
+    let siblings = if req_parent_id > 0 {
+        Textbook::find_list_by_parent_id(db, req_parent_id).await
+    } else {
+        Textbook::find_all_by_depth(db, 1).await
+    }
+    .map_err(|e| {
+        error!("Error searching textbook: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+
+    // 移动过来的节点需要检查名称和环
+    let mut labels: HashSet<String> = HashSet::with_capacity(req.ids.len());
+    for id in &req.ids {
+        let row = row_map
+            .get(id)
+            .ok_or_else(|| Error::new(ErrorKind::Other, format!("节点不存在: {}", id)))?;
+
+        if !labels.insert(row.label.clone()) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("当前层级名称已存在: {}", row.label),
+            ));
+        }
+
+        if row.parent_id == req.parent_id {
+            continue;
+        }
+
+        check_parent_and_label_is_exists(db, req.parent_id, row.label.as_str(), Some(row.id))
+            .await?;
+
+        if req_parent_id > 0 {
+            let exist = Textbook::is_descendant(db, row.id, req_parent_id)
+                .await
+                .map_err(|e| {
+                    error!("Error searching textbook: {:?}", e);
+                    Error::new(ErrorKind::Other, "查询失败")
+                })?;
+            if exist {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("{} 跟所选父级存在交叉, 不支持挂载", row.label),
+                ));
+            }
+        }
+    }
+
+    // 这部分更新使用事务
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("Error beginning transaction: {}", e);
+        Error::new(ErrorKind::Other, "更新失败")
+    })?;
+
+    let mut sort_order = 0;
+    for id in &req.ids {
+        sort_order += 1;
+        // 存在性在上面已经检查过
+        let Some(row) = row_map.remove(id) else {
+            continue;
+        };
+
+        if row.parent_id == req.parent_id {
+            Textbook::update_sort_order(&mut *tx, row.id, sort_order)
+                .await
+                .map_err(|e| {
+                    error!("Error updating textbook sort order: {:?}", e);
+                    Error::new(ErrorKind::Other, "排序失败")
+                })?;
+            continue;
+        }
+
+        Textbook::update(
+            &mut *tx,
+            row.id,
+            req.parent_id,
+            row.label.as_str(),
+            sort_order,
+            path_depth,
+            row.path_type.as_str(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Error updating textbook: {:?}", e);
+            Error::new(ErrorKind::Other, "移动失败")
+        })?;
+
+        // 所有子孙节点深度同步更新
+        Textbook::update_descendant_depth(
+            &mut *tx,
+            row.id,
+            req.parent_id,
+            path_depth,
+            row.path_type.as_str(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Error updating descendant depth: {:?}", e);
+            Error::new(ErrorKind::Other, "更新失败")
+        })?;
+    }
+
+    // 未出现在列表中的原有子级保持原来的相对顺序排在后面
+    for sibling in siblings.iter().filter(|row| !unique_ids.contains(&row.id)) {
+        sort_order += 1;
+        Textbook::update_sort_order(&mut *tx, sibling.id, sort_order)
+            .await
+            .map_err(|e| {
+                error!("Error updating textbook sort order: {:?}", e);
+                Error::new(ErrorKind::Other, "排序失败")
+            })?;
+    }
+
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "更新失败")
+    })?;
+
+    Ok(true)
+}
+
+// 节点及其所有子孙节点下的关联标识
+struct TextbookScope {
+    ids: Vec<i32>,        // 节点自己和所有子孙节点
+    bridge_ids: Vec<i32>, // 章节和知识点绑定关系
+    cate_ids: Vec<i32>,   // 绑定关系下的题型
+}
+
+async fn load_scope(conn: &mut PgConnection, id: i32) -> Result<TextbookScope, Error> {
+    let rows = Textbook::find_all_by_parent_id(&mut *conn, id)
         .await
         .map_err(|e| {
             error!("Error searching textbook: {:?}", e);
             Error::new(ErrorKind::Other, "查询失败")
         })?;
-    if row.is_some() {
-        return Err(Error::new(ErrorKind::Other, "该层级存在子菜单, 不允许删除"));
-    }
+    let mut ids = Vec::with_capacity(rows.len() + 1);
+    ids.push(id);
+    ids.extend(rows.iter().map(|row| row.id));
+
+    let bridges = ChapterKnowledge::find_by_ids(&mut *conn, ids.clone())
+        .await
+        .map_err(|e| {
+            error!("Error searching chapter knowledge: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let bridge_ids: Vec<i32> = bridges.iter().map(|row| row.id).collect();
+
+    let cates = QuestionCate::find_all_by_related_ids(&mut *conn, bridge_ids.clone())
+        .await
+        .map_err(|e| {
+            error!("Error searching question cate: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let cate_ids: Vec<i32> = cates.iter().map(|row| row.id).collect();
+
+    Ok(TextbookScope {
+        ids,
+        bridge_ids,
+        cate_ids,
+    })
+}
+
+async fn to_dependency_resp(
+    conn: &mut PgConnection,
+    id: i32,
+    scope: &TextbookScope,
+) -> Result<TextbookDependencyResp, Error> {
+    let question_count = Question::count_by_cate_ids(&mut *conn, &scope.cate_ids)
+        .await
+        .map_err(|e| {
+            error!("Error counting question: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let paper_count = Paper::count_by_related_ids(&mut *conn, &scope.ids)
+        .await
+        .map_err(|e| {
+            error!("Error counting paper: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let task_cate_ids: Vec<i64> = scope.cate_ids.iter().map(|id| *id as i64).collect();
+    let task_count = Task::count_by_textbook_or_cate(&mut *conn, &scope.ids, &task_cate_ids)
+        .await
+        .map_err(|e| {
+            error!("Error counting task: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let dict_count = TextbookDict::count_by_textbook_ids(&mut *conn, &scope.ids)
+        .await
+        .map_err(|e| {
+            error!("Error counting textbook dict: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
 
-    // 检查第7级菜单是否有子菜单
-    if let Some(path_depth) = info.path_depth
-        && path_depth == 7
-    {
-        // 检查该菜单是否关联过
-        let chapters = ChapterKnowledge::find_by_chapter_or_knowledge_id(db, info.id)
+    let prerequisite_count = KnowledgePrerequisite::count_by_textbook_ids(&mut *conn, &scope.ids)
+        .await
+        .map_err(|e| {
+            error!("Error counting knowledge prerequisite: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let chapter_mapping_count = ChapterMapping::count_by_textbook_ids(&mut *conn, &scope.ids)
+        .await
+        .map_err(|e| {
+            error!("Error counting chapter mapping: {:?}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let question_knowledge_count =
+        QuestionKnowledge::count_by_knowledge_ids(&mut *conn, &scope.ids)
             .await
             .map_err(|e| {
-                error!("Error searching textbook: {:?}", e);
+                error!("Error counting question knowledge: {:?}", e);
                 Error::new(ErrorKind::Other, "查询失败")
             })?;
-        if !chapters.is_empty() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "章节小节和知识点还存在绑定关系, 不允许删除",
-            ));
-        }
+
+    Ok(TextbookDependencyResp {
+        id,
+        node_count: (scope.ids.len() - 1) as i64,
+        chapter_knowledge_count: scope.bridge_ids.len() as i64,
+        question_cate_count: scope.cate_ids.len() as i64,
+        question_count,
+        paper_count,
+        task_count,
+        dict_count,
+        prerequisite_count,
+        chapter_mapping_count,
+        question_knowledge_count,
+    })
+}
+
+// 关联内容的描述, 没有任何关联内容时返回 None
+fn dependency_reason(resp: &TextbookDependencyResp) -> Option<String> {
+    let parts: Vec<String> = [
+        ("子菜单", resp.node_count),
+        ("章节知识点绑定", resp.chapter_knowledge_count),
+        ("题型", resp.question_cate_count),
+        ("题目", resp.question_count),
+        ("试卷", resp.paper_count),
+        ("任务", resp.task_count),
+        ("字典", resp.dict_count),
+        ("知识点前置关系", resp.prerequisite_count),
+        ("章节对应关系", resp.chapter_mapping_count),
+        ("题目知识点关联", resp.question_knowledge_count),
+    ]
+    .iter()
+    .filter(|(_, count)| *count > 0)
+    .map(|(name, count)| format!("{} {} 个", name, count))
+    .collect();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+// 节点关联内容统计, 包括所有子孙节点
+pub async fn dependency(
+    app_conf: web::Data<AppConfig>,
+    id: i32,
+) -> Result<TextbookDependencyResp, Error> {
+    let info = info(app_conf.clone(), id).await?;
+
+    let mut conn = app_conf.get_ref().db.acquire().await.map_err(|e| {
+        error!("Error acquiring connection: {}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    let scope = load_scope(&mut conn, info.id).await?;
+
+    to_dependency_resp(&mut conn, info.id, &scope).await
+}
+
+// 锁定节点及其子孙节点后在事务中统计关联内容, 统计结果在事务提交前不会变化
+async fn lock_scope(
+    tx: &mut Transaction<'_, Postgres>,
+    id: i32,
+) -> Result<(TextbookScope, TextbookDependencyResp), Error> {
+    let locked = Textbook::tx_lock_subtree(tx, id).await.map_err(|e| {
+        error!("Error locking textbook: {:?}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+    if locked.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "数据不存在"));
+    }
+
+    let scope = load_scope(tx, id).await?;
+    let resp = to_dependency_resp(tx, id, &scope).await?;
+
+    Ok((scope, resp))
+}
+
+// 删除菜单-没有任何关联内容的菜单可以被删除, 否则需要使用归档
+pub async fn delete(app_conf: web::Data<AppConfig>, id: i32) -> Result<bool, Error> {
+    let mut tx = app_conf.get_ref().db.begin().await.map_err(|e| {
+        error!("Error beginning transaction: {}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    let (_, resp) = lock_scope(&mut tx, id).await?;
+    if let Some(reason) = dependency_reason(&resp) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("该层级还存在关联内容, 不允许删除: {}", reason),
+        ));
     }
 
-    let row = Textbook::delete(db, id).await.map_err(|e| {
+    let row = Textbook::delete(&mut *tx, id).await.map_err(|e| {
         error!("Error deleting textbook: {:?}", e);
         Error::new(ErrorKind::Other, "删除失败")
     })?;
 
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
     Ok(row > 0)
 }
+
+// 级联归档删除菜单, 所有操作在一个事务中完成, 节点及其子孙节点在事务中锁定
+// 1. 题型下的题目改为已归档, 章节知识点绑定关系和没有题目的题型直接删除, 还有题目的题型保留, 题型下待执行的任务置为失败
+// 2. 指定了改挂节点时试卷, 任务和字典改挂到该节点, 题目引用的同名字典替换为改挂节点上的字典, 否则试卷改为已归档, 字典随节点一起删除
+// 3. 删除节点自己, 子孙节点, 知识点前置关系, 章节对应关系和题目知识点关联由外键级联删除
+pub async fn archive(
+    app_conf: web::Data<AppConfig>,
+    req: ArchiveTextbookReq,
+) -> Result<TextbookDependencyResp, Error> {
+    let info = info(app_conf.clone(), req.id).await?;
+
+    let db = &app_conf.get_ref().db;
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("Error beginning transaction: {}", e);
+        Error::new(ErrorKind::Other, "归档失败")
+    })?;
+
+    let (scope, resp) = lock_scope(&mut tx, info.id).await?;
+
+    let target = match req.target_id {
+        Some(target_id) => {
+            if scope.ids.contains(&target_id) {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "改挂节点不能是当前节点或者其子节点",
+                ));
+            }
+            Some(self::info(app_conf.clone(), target_id).await?)
+        }
+        None => None,
+    };
+
+    Question::tx_update_status_by_cate_ids(
+        &mut tx,
+        &scope.cate_ids,
+        QuestionStatus::Archived as i16,
+    )
+    .await
+    .map_err(|e| {
+        error!("Error archiving question: {:?}", e);
+        Error::new(ErrorKind::Other, "题目归档失败")
+    })?;
+
+    let task_cate_ids: Vec<i64> = scope.cate_ids.iter().map(|id| *id as i64).collect();
+    Task::tx_fail_waiting_by_cate(&mut tx, &task_cate_ids, "所属教材节点已归档")
+        .await
+        .map_err(|e| {
+            error!("Error failing waiting task: {:?}", e);
+            Error::new(ErrorKind::Other, "任务更新失败")
+        })?;
+
+    if let Some(target) = target {
+        Paper::tx_update_related_id(&mut tx, &scope.ids, target.id, target.label.as_str())
+            .await
+            .map_err(|e| {
+                error!("Error moving paper: {:?}", e);
+                Error::new(ErrorKind::Other, "试卷改挂失败")
+            })?;
+
+        Task::tx_update_textbook_id(&mut tx, &scope.ids, target.id)
+            .await
+            .map_err(|e| {
+                error!("Error moving task: {:?}", e);
+                Error::new(ErrorKind::Other, "任务改挂失败")
+            })?;
+
+        TextbookDict::tx_update_textbook_id(&mut tx, &scope.ids, target.id)
+            .await
+            .map_err(|e| {
+                error!("Error moving textbook dict: {:?}", e);
+                Error::new(ErrorKind::Other, "字典改挂失败")
+            })?;
+
+        // 没有改挂的同名字典随节点一起删除, 题目中的引用改为改挂节点上的字典
+        let duplicates = TextbookDict::tx_find_duplicates(&mut tx, &scope.ids, target.id)
+            .await
+            .map_err(|e| {
+                error!("Error searching textbook dict: {:?}", e);
+                Error::new(ErrorKind::Other, "字典改挂失败")
+            })?;
+        for (source_id, target_id) in duplicates {
+            Question::tx_replace_dict_id(&mut tx, source_id, target_id)
+                .await
+                .map_err(|e| {
+                    error!("Error replacing textbook dict in question: {:?}", e);
+                    Error::new(ErrorKind::Other, "字典改挂失败")
+                })?;
+        }
+    } else {
+        Paper::tx_update_status_by_related_ids(&mut tx, &scope.ids, PaperStatus::Archived as i16)
+            .await
+            .map_err(|e| {
+                error!("Error archiving paper: {:?}", e);
+                Error::new(ErrorKind::Other, "试卷归档失败")
+            })?;
+    }
+
+    QuestionCate::tx_delete_unused_by_ids(&mut tx, &scope.cate_ids)
+        .await
+        .map_err(|e| {
+            error!("Error deleting question cate: {:?}", e);
+            Error::new(ErrorKind::Other, "题型删除失败")
+        })?;
+
+    ChapterKnowledge::tx_delete_by_ids(&mut tx, &scope.bridge_ids)
+        .await
+        .map_err(|e| {
+            error!("Error deleting chapter knowledge: {:?}", e);
+            Error::new(ErrorKind::Other, "章节知识点绑定关系删除失败")
+        })?;
+
+    Textbook::delete(&mut *tx, info.id).await.map_err(|e| {
+        error!("Error deleting textbook: {:?}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("Error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "归档失败")
+    })?;
+
+    Ok(resp)
+}
@@ -1,8 +1,14 @@
 use crate::AppConfig;
-use crate::api::other_dict::{CreateTextbookDictReq, TextbookDictResp};
-use crate::model::other_dict::TextbookDict;
+use crate::api::other_dict::{
+    CreateTextbookDictReq, EffectiveDictResp, MergeTextbookDictReq, SortTextbookDictReq,
+    TextbookDictResp, TextbookDictUsageResp, UpdateTextbookDictReq,
+};
+use crate::model::other_dict::{InheritedDict, TextbookDict};
+use crate::model::question::Question;
 use actix_web::web;
 use log::error;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind};
 
 fn to_resp(row: TextbookDict) -> TextbookDictResp {
@@ -13,6 +19,7 @@ fn to_resp(row: TextbookDict) -> TextbookDictResp {
         item_value: row.item_value,
         sort_order: row.sort_order,
         is_select: row.is_select,
+        is_hidden: row.is_hidden,
     }
 }
 
@@ -63,14 +70,285 @@ pub async fn get_list(
 
 // 删除字典
 pub async fn delete(app_conf: web::Data<AppConfig>, id: i32) -> Result<bool, Error> {
-    //todo 被使用的字典不能删除, 字典id在题目题目类型和标签中
+    let db = &app_conf.get_ref().db;
+
+    // 被使用的字典不能删除, 字典id在题目类型, 标签和核心素养中
+    let question_count = count_usage(db, &[id]).await?.remove(&id).unwrap_or(0);
+    if question_count > 0 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("字典已被 {} 道题目使用, 不允许删除", question_count),
+        ));
+    }
+
+    let row = TextbookDict::delete(db, id).await.map_err(|e| {
+        error!("error deleting unique textbook item: {}", e);
+        Error::new(ErrorKind::Other, "删除失败")
+    })?;
+
+    Ok(row > 0)
+}
+
+/// 合并祖先链上的字典项, 同类型同名的字典项只保留距离最近的一个
+/// 最近的一个被标记为隐藏时, 该字典项在当前节点不生效
+/// 返回值中第二项表示是否覆盖了祖先节点的同名字典项
+fn resolve_inherited(rows: Vec<InheritedDict>) -> Vec<(InheritedDict, bool)> {
+    let mut res: Vec<(InheritedDict, bool)> = Vec::new();
+    let mut current: Option<(InheritedDict, bool)> = None;
+
+    // 查询结果已按类型, 名称, 距离排序
+    for row in rows {
+        if let Some((nearest, overridden)) = current.as_mut()
+            && nearest.dict.type_code == row.dict.type_code
+            && nearest.dict.item_value == row.dict.item_value
+        {
+            *overridden = true;
+            continue;
+        }
+        if let Some(item) = current.take() {
+            res.push(item);
+        }
+        current = Some((row, false));
+    }
+    if let Some(item) = current.take() {
+        res.push(item);
+    }
+
+    res.retain(|(row, _)| !row.dict.is_hidden);
+    res.sort_by(|a, b| {
+        (&a.0.dict.type_code, a.0.dict.sort_order, a.0.dict.id).cmp(&(
+            &b.0.dict.type_code,
+            b.0.dict.sort_order,
+            b.0.dict.id,
+        ))
+    });
+
+    res
+}
+
+// 节点实际生效的字典列表, type_code 为空时返回所有类型, 题目批量上传和移动时使用
+pub async fn load_effective(
+    db: &PgPool,
+    textbook_id: i32,
+    type_code: Option<&str>,
+) -> Result<Vec<TextbookDict>, sqlx::Error> {
+    let rows = TextbookDict::find_by_ancestors(db, textbook_id, type_code).await?;
+
+    Ok(resolve_inherited(rows)
+        .into_iter()
+        .map(|(row, _)| row.dict)
+        .collect())
+}
+
+// 节点实际生效的字典, 包含字典项的来源
+pub async fn effective(
+    app_conf: web::Data<AppConfig>,
+    textbook_id: i32,
+    type_code: Option<String>,
+) -> Result<Vec<EffectiveDictResp>, Error> {
+    let rows =
+        TextbookDict::find_by_ancestors(&app_conf.get_ref().db, textbook_id, type_code.as_deref())
+            .await
+            .map_err(|e| {
+                error!("error finding inherited textbook item: {}", e);
+                Error::new(ErrorKind::Other, "查询失败")
+            })?;
+
+    Ok(resolve_inherited(rows)
+        .into_iter()
+        .map(|(row, overridden)| EffectiveDictResp {
+            inherited: row.distance > 0,
+            overridden,
+            source_label: row.source_label,
+            item: to_resp(row.dict),
+        })
+        .collect())
+}
 
-    let row = TextbookDict::delete(&app_conf.get_ref().db, id)
+async fn find_dict(pool: &PgPool, id: i32) -> Result<TextbookDict, Error> {
+    TextbookDict::find_by_id(pool, id).await.map_err(|e| {
+        error!("error finding textbook item: {}", e);
+        Error::new(ErrorKind::Other, format!("字典不存在: {}", id))
+    })
+}
+
+async fn count_usage(pool: &PgPool, ids: &[i32]) -> Result<HashMap<i32, i64>, Error> {
+    let rows = Question::count_by_dict_ids(pool, ids).await.map_err(|e| {
+        error!("error counting textbook item usage: {}", e);
+        Error::new(ErrorKind::Other, "查询失败")
+    })?;
+
+    Ok(rows.into_iter().collect())
+}
+
+// 修改字典
+pub async fn edit(
+    app_conf: web::Data<AppConfig>,
+    req: UpdateTextbookDictReq,
+) -> Result<bool, Error> {
+    let item_value = req.item_value.trim();
+    if item_value.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "字典值不能为空"));
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let row = find_dict(db, req.id).await?;
+
+    // 同一类型下的值需要唯一
+    let exist = TextbookDict::find_by_unique(db, row.textbook_id, &row.type_code, item_value)
         .await
         .map_err(|e| {
-            error!("error deleting unique textbook item: {}", e);
-            Error::new(ErrorKind::Other, "删除失败")
+            error!("error finding unique textbook item: {}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    if exist.is_some_and(|item| item.id != req.id) {
+        return Err(Error::new(ErrorKind::Other, "字典已经存在"));
+    }
+
+    let row = TextbookDict::update(db, req.id, item_value, req.sort_order, req.is_select)
+        .await
+        .map_err(|e| {
+            error!("error updating textbook item: {}", e);
+            Error::new(ErrorKind::Other, "修改失败")
         })?;
 
     Ok(row > 0)
 }
+
+// 批量排序, 未出现在列表中的字典项保持原有顺序排在后面
+pub async fn sort(app_conf: web::Data<AppConfig>, req: SortTextbookDictReq) -> Result<bool, Error> {
+    if req.ids.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "排序列表不能为空"));
+    }
+
+    let mut unique_ids = HashSet::with_capacity(req.ids.len());
+    for id in &req.ids {
+        if !unique_ids.insert(*id) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("排序列表存在重复字典: {}", id),
+            ));
+        }
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let siblings = TextbookDict::find_by_textbook_and_type(db, req.textbook_id, &req.type_code)
+        .await
+        .map_err(|e| {
+            error!("error finding textbook item: {}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+
+    let sibling_ids: HashSet<i32> = siblings.iter().map(|row| row.id).collect();
+    if let Some(id) = req.ids.iter().find(|id| !sibling_ids.contains(id)) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("字典 {} 不属于当前节点和类型", id),
+        ));
+    }
+
+    let ids: Vec<i32> = req
+        .ids
+        .iter()
+        .copied()
+        .chain(
+            siblings
+                .iter()
+                .map(|row| row.id)
+                .filter(|id| !unique_ids.contains(id)),
+        )
+        .collect();
+
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("error starting transaction: {}", e);
+        Error::new(ErrorKind::Other, "排序失败")
+    })?;
+
+    for (index, id) in ids.iter().enumerate() {
+        TextbookDict::update_sort_order(&mut *tx, *id, index as i32 + 1)
+            .await
+            .map_err(|e| {
+                error!("error updating textbook item sort order: {}", e);
+                Error::new(ErrorKind::Other, "排序失败")
+            })?;
+    }
+
+    tx.commit().await.map_err(|e| {
+        error!("error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "排序失败")
+    })?;
+
+    Ok(true)
+}
+
+// 节点下某个类型的字典项被题目引用的数量
+pub async fn usage(
+    app_conf: web::Data<AppConfig>,
+    textbook_id: i32,
+    type_code: String,
+) -> Result<Vec<TextbookDictUsageResp>, Error> {
+    let db = &app_conf.get_ref().db;
+
+    let rows = TextbookDict::find_by_textbook_and_type(db, textbook_id, &type_code)
+        .await
+        .map_err(|e| {
+            error!("error finding textbook item: {}", e);
+            Error::new(ErrorKind::Other, "查询失败")
+        })?;
+    let ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    let mut counts = count_usage(db, &ids).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| TextbookDictUsageResp {
+            question_count: counts.remove(&row.id).unwrap_or(0),
+            id: row.id,
+            item_value: row.item_value,
+        })
+        .collect())
+}
+
+// 合并字典项, 在同一个事务中改写题目中的引用并删除被合并的字典项
+pub async fn merge(
+    app_conf: web::Data<AppConfig>,
+    req: MergeTextbookDictReq,
+) -> Result<u64, Error> {
+    if req.source_id == req.target_id {
+        return Err(Error::new(ErrorKind::Other, "不能合并到自己"));
+    }
+
+    let db = &app_conf.get_ref().db;
+
+    let source = find_dict(db, req.source_id).await?;
+    let target = find_dict(db, req.target_id).await?;
+    if source.type_code != target.type_code {
+        return Err(Error::new(ErrorKind::Other, "只能合并相同类型的字典"));
+    }
+
+    let mut tx = db.begin().await.map_err(|e| {
+        error!("error starting transaction: {}", e);
+        Error::new(ErrorKind::Other, "合并失败")
+    })?;
+
+    let question_count = Question::tx_replace_dict_id(&mut tx, source.id, target.id)
+        .await
+        .map_err(|e| {
+            error!("error replacing textbook item in question: {}", e);
+            Error::new(ErrorKind::Other, "合并失败")
+        })?;
+    TextbookDict::tx_delete(&mut tx, source.id)
+        .await
+        .map_err(|e| {
+            error!("error deleting textbook item: {}", e);
+            Error::new(ErrorKind::Other, "合并失败")
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("error committing transaction: {}", e);
+        Error::new(ErrorKind::Other, "合并失败")
+    })?;
+
+    Ok(question_count)
+}
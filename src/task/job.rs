@@ -0,0 +1,209 @@
+use crate::AppConfig;
+use crate::model::task::Task;
+use futures_util::future::BoxFuture;
+use log::error;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::Postgres;
+use sqlx::pool::PoolConnection;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 后台任务
+/// 新的任务类型实现 Job 并在 task::registry() 中注册即可, 参数和结果使用 JSON 保存在 task 表中
+
+// 执行进度最短更新间隔, 阶段变化和处理完成时不受限制
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// 任务执行时的上下文
+pub struct JobContext<'a> {
+    pub app_conf: &'a AppConfig,
+    pub task: &'a Task,
+    pub worker_id: &'a str,
+    // 领取任务时预留的连接, 心跳和进度使用, 任务在事务中占用连接池时也能更新
+    conn: tokio::sync::Mutex<PoolConnection<Postgres>>,
+    progress_at: Mutex<Option<(Instant, String)>>, // 上次更新进度的时间和阶段
+}
+
+impl<'a> JobContext<'a> {
+    pub fn new(
+        app_conf: &'a AppConfig,
+        task: &'a Task,
+        worker_id: &'a str,
+        conn: PoolConnection<Postgres>,
+    ) -> Self {
+        JobContext {
+            app_conf,
+            task,
+            worker_id,
+            conn: tokio::sync::Mutex::new(conn),
+            progress_at: Mutex::new(None),
+        }
+    }
+
+    // 执行结束后取回预留的连接
+    pub fn into_conn(self) -> PoolConnection<Postgres> {
+        self.conn.into_inner()
+    }
+
+    // 更新心跳, 返回是否请求取消, 任务已经不由当前进程持有时返回空
+    pub async fn heartbeat(&self) -> Result<Option<bool>, sqlx::Error> {
+        let mut conn = self.conn.lock().await;
+        Task::heartbeat(&mut **conn, self.task.id, self.worker_id).await
+    }
+
+    /// 更新执行进度
+    /// 同一阶段内按间隔更新, 避免逐条处理时频繁写库, 更新失败只记录日志不影响执行
+    pub async fn progress(&self, phase: &str, done: usize, total: usize) {
+        {
+            let mut progress_at = self.progress_at.lock().unwrap();
+            if let Some((at, last_phase)) = progress_at.as_ref()
+                && last_phase == phase
+                && done < total
+                && at.elapsed() < PROGRESS_INTERVAL
+            {
+                return;
+            }
+            *progress_at = Some((Instant::now(), phase.to_string()));
+        }
+
+        let mut conn = self.conn.lock().await;
+        if let Err(e) = Task::update_progress(
+            &mut **conn,
+            self.task.id,
+            self.worker_id,
+            phase,
+            done as i32,
+            total as i32,
+        )
+        .await
+        {
+            error!("Update task id: {} progress err: {}", self.task.id, e);
+        }
+    }
+}
+
+pub trait Job: Send + Sync + 'static {
+    // 任务类型名称, 对应 task.kind, 也是命令行执行的任务名称
+    const NAME: &'static str;
+
+    type Params: DeserializeOwned + Send;
+    type Output: Serialize + Send;
+
+    fn run(
+        &self,
+        ctx: &JobContext<'_>,
+        params: Self::Params,
+    ) -> impl Future<Output = Result<Self::Output, Error>> + Send;
+
+    // 保存在 result 字段中的可读结果, 默认为结构化结果的 JSON
+    fn summary(output: &Self::Output) -> String {
+        serde_json::to_string(output).unwrap_or_default()
+    }
+}
+
+// 任务执行结果
+pub struct JobResult {
+    pub summary: String,
+    pub output: Value,
+}
+
+// 擦除参数和结果类型后的任务, 用于注册表统一保存
+trait DynJob: Send + Sync {
+    fn check(&self, params: &Value) -> Result<(), Error>;
+
+    fn run<'a>(
+        &'a self,
+        ctx: &'a JobContext<'a>,
+        params: Value,
+    ) -> BoxFuture<'a, Result<JobResult, Error>>;
+}
+
+fn parse_params<J: Job>(params: Value) -> Result<J::Params, Error> {
+    serde_json::from_value(params).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("任务 {} 参数错误: {}", J::NAME, e),
+        )
+    })
+}
+
+impl<J: Job> DynJob for J {
+    fn check(&self, params: &Value) -> Result<(), Error> {
+        parse_params::<J>(params.clone()).map(|_| ())
+    }
+
+    fn run<'a>(
+        &'a self,
+        ctx: &'a JobContext<'a>,
+        params: Value,
+    ) -> BoxFuture<'a, Result<JobResult, Error>> {
+        Box::pin(async move {
+            let params = parse_params::<J>(params)?;
+            let output = Job::run(self, ctx, params).await?;
+            let value = serde_json::to_value(&output).map_err(|e| {
+                Error::new(
+                    ErrorKind::Other,
+                    format!("任务 {} 结果序列化失败: {}", J::NAME, e),
+                )
+            })?;
+
+            Ok(JobResult {
+                summary: J::summary(&output),
+                output: value,
+            })
+        })
+    }
+}
+
+// 任务注册表, 任务类型名称 -> 任务
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: HashMap<&'static str, Box<dyn DynJob>>,
+}
+
+impl JobRegistry {
+    pub fn register<J: Job>(mut self, job: J) -> Self {
+        if self.jobs.insert(J::NAME, Box::new(job)).is_some() {
+            panic!("任务重复注册: {}", J::NAME);
+        }
+        self
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.jobs.contains_key(kind)
+    }
+
+    // 所有注册的任务类型名称
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.jobs.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    // 创建任务前检查参数是否能解析
+    pub fn check(&self, kind: &str, params: &Value) -> Result<(), Error> {
+        match self.jobs.get(kind) {
+            Some(job) => job.check(params),
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("未知任务: {}", kind),
+            )),
+        }
+    }
+
+    // 按任务记录的类型执行
+    pub async fn run(&self, ctx: &JobContext<'_>) -> Result<JobResult, Error> {
+        let job = self.jobs.get(ctx.task.kind.as_str()).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("未知任务: {}", ctx.task.kind),
+            )
+        })?;
+
+        job.run(ctx, ctx.task.params.0.clone()).await
+    }
+}
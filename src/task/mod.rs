@@ -1 +1,253 @@
-pub mod question;
\ No newline at end of file
+use crate::AppConfig;
+use crate::model::task::{Task, TaskStatus};
+use crate::model::task_history::{self, TaskHistory};
+use crate::task::job::{JobContext, JobRegistry, JobResult};
+use log::{error, info, warn};
+use sqlx::pool::PoolConnection;
+use sqlx::{Executor, Postgres};
+use std::io::{Error, ErrorKind};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+pub mod job;
+mod notify;
+pub mod question;
+
+// 新的任务类型在这里注册
+static REGISTRY: LazyLock<JobRegistry> =
+    LazyLock::new(|| JobRegistry::default().register(question::QuestionUploadJob));
+
+// 执行中的任务心跳间隔, 同时检查是否请求取消
+const HEARTBEAT_SECS: u64 = 10;
+// 超过该时间没有心跳的执行中任务视为执行进程已经退出, 重新等待执行
+const LEASE_SECS: f64 = 300.0;
+// 最大执行次数, 超时恢复和手动重试都不能超过
+pub const MAX_ATTEMPTS: i32 = 5;
+
+// 任务执行结束的方式
+enum Outcome {
+    Done(Result<JobResult, Error>),
+    Canceled, // 请求取消
+    Lost,     // 已经超时被恢复, 不再由当前进程持有
+}
+
+pub fn registry() -> &'static JobRegistry {
+    &REGISTRY
+}
+
+// 执行进程标识, 主机名称加进程号
+pub fn worker_id() -> String {
+    let host = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_string());
+
+    format!("{}-{}", host, std::process::id())
+}
+
+// 执行任务, 执行期间定时更新心跳, 请求取消后在心跳时停止执行, 未提交的事务随之回滚
+// 心跳和任务同时轮询, 任务更新进度时持有预留连接不会阻塞心跳
+async fn run_with_heartbeat(
+    app_conf: &AppConfig,
+    task_info: &Task,
+    worker_id: &str,
+    conn: PoolConnection<Postgres>,
+) -> (Outcome, PoolConnection<Postgres>) {
+    let ctx = JobContext::new(app_conf, task_info, worker_id, conn);
+    let outcome = {
+        let heartbeat = async {
+            let mut ticker = tokio::time::interval(Duration::from_secs(HEARTBEAT_SECS));
+            // 第一次立即触发, 领取时已经更新过心跳
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match ctx.heartbeat().await {
+                    Ok(Some(true)) => return Outcome::Canceled,
+                    Ok(Some(false)) => {}
+                    Ok(None) => return Outcome::Lost,
+                    Err(e) => error!("Task id: {} heartbeat err: {}", task_info.id, e),
+                }
+            }
+        };
+
+        tokio::select! {
+            res = registry().run(&ctx) => Outcome::Done(res),
+            outcome = heartbeat => outcome,
+        }
+    };
+
+    (outcome, ctx.into_conn())
+}
+
+/// 执行一个已经领取的任务并记录结果
+/// conn 为领取任务时预留的连接, 执行记录, 心跳, 进度和结果都使用该连接, 任务自己的事务使用连接池
+pub async fn run_claimed(
+    app_conf: &AppConfig,
+    task_info: Task,
+    worker_id: &str,
+    mut conn: PoolConnection<Postgres>,
+) {
+    info!(
+        "Process task id: {} kind: {} name: {} start",
+        task_info.id, task_info.kind, task_info.name
+    );
+    let message = format!("第 {} 次执行", task_info.attempts);
+    record(
+        &mut *conn,
+        task_info.id,
+        task_history::ACTION_START,
+        Some(worker_id),
+        &message,
+    )
+    .await;
+
+    // 失败时数据库记录原因为捕获的错误信息, 实际的执行内容需要看脚本执行日志
+    let (outcome, mut conn) = run_with_heartbeat(app_conf, &task_info, worker_id, conn).await;
+    let (status, result, output) = match outcome {
+        Outcome::Done(Ok(res)) => (TaskStatus::Success, res.summary, Some(res.output)),
+        Outcome::Done(Err(e)) => {
+            error!("Process task name: {} err: {}", task_info.name, e);
+            (TaskStatus::Failed, e.to_string(), None)
+        }
+        Outcome::Canceled => {
+            info!("Process task name: {} canceled", task_info.name);
+            (TaskStatus::Canceled, "已取消".to_string(), None)
+        }
+        Outcome::Lost => {
+            warn!(
+                "Task id: {} is no longer held by worker {}, stop",
+                task_info.id, worker_id
+            );
+            return;
+        }
+    };
+    let action = match status {
+        TaskStatus::Success => task_history::ACTION_SUCCESS,
+        TaskStatus::Canceled => task_history::ACTION_CANCELED,
+        _ => task_history::ACTION_FAILED,
+    };
+    let message = if status == TaskStatus::Success {
+        String::new()
+    } else {
+        result.clone()
+    };
+
+    match Task::finish(
+        &mut *conn,
+        task_info.id,
+        worker_id,
+        status as i16,
+        result.clone(),
+        output.clone(),
+    )
+    .await
+    {
+        // 已经超时被其他进程重新领取, 结果以其他进程为准
+        Ok(0) => warn!(
+            "Task id: {} done, but it is no longer held by worker {}",
+            task_info.id, worker_id
+        ),
+        Ok(_) => {
+            record(&mut *conn, task_info.id, action, Some(worker_id), &message).await;
+            info!("Process task name: {} done", task_info.name);
+            notify::send_result(app_conf, &task_info, status, &result, output.as_ref()).await;
+        }
+        // 这次更新失败不做任何处理, 需要关注这类日志
+        Err(e) => error!(
+            "Task done, but update task id: {}, name {} result err: {}",
+            task_info.id, task_info.name, e
+        ),
+    }
+}
+
+// 记录执行进程的操作, 记录失败不影响任务执行
+async fn record<'e, E>(
+    executor: E,
+    task_id: i64,
+    action: &str,
+    worker_id: Option<&str>,
+    message: &str,
+) where
+    E: Executor<'e, Database = Postgres>,
+{
+    let message = Some(message).filter(|message| !message.is_empty());
+    if let Err(e) = TaskHistory::insert(executor, task_id, action, 0, worker_id, message).await {
+        error!("Record task id: {} action: {} err: {}", task_id, action, e);
+    }
+}
+
+// 恢复心跳超时的任务
+pub async fn recover_expired(app_conf: &AppConfig, kinds: &[&str]) -> Result<usize, Error> {
+    let rows = Task::recover_expired(&app_conf.db, kinds, LEASE_SECS, MAX_ATTEMPTS)
+        .await
+        .map_err(|e| {
+            error!("Recover expired task err: {}", e);
+            Error::new(ErrorKind::Other, "恢复超时任务失败")
+        })?;
+    if !rows.is_empty() {
+        warn!("Recovered {} expired running tasks", rows.len());
+    }
+    for (id, status) in &rows {
+        let message = format!("执行超时, 状态变更为{}", TaskStatus::desc(*status));
+        record(
+            &app_conf.db,
+            *id,
+            task_history::ACTION_RECOVER,
+            None,
+            &message,
+        )
+        .await;
+    }
+
+    Ok(rows.len())
+}
+
+// 为执行任务预留一个连接, 领取任务前获取, 获取不到时不领取
+pub async fn reserve_conn(app_conf: &AppConfig) -> Result<PoolConnection<Postgres>, Error> {
+    app_conf.db.acquire().await.map_err(|e| {
+        error!("Reserve task connection err: {}", e);
+        Error::new(ErrorKind::Other, "获取数据库连接失败")
+    })
+}
+
+// 领取下一个待执行的任务
+pub async fn claim_next(
+    app_conf: &AppConfig,
+    kinds: &[&str],
+    worker_id: &str,
+) -> Result<Option<Task>, Error> {
+    Task::claim_next(&app_conf.db, kinds, worker_id)
+        .await
+        .map_err(|e| {
+            error!("Claim waiting task err: {}", e);
+            Error::new(ErrorKind::Other, "领取任务失败")
+        })
+}
+
+/// 执行指定类型所有待处理的任务
+/// 任务通过数据库行锁逐个领取, 多个进程同时执行时不会重复处理, 单个任务失败只记录到任务结果中
+pub async fn run_waiting(app_conf: &AppConfig, kinds: &[&str]) -> Result<(), Error> {
+    let worker_id = worker_id();
+
+    recover_expired(app_conf, kinds).await?;
+
+    let mut count = 0;
+    loop {
+        let conn = reserve_conn(app_conf).await?;
+        let Some(task_info) = claim_next(app_conf, kinds, &worker_id).await? else {
+            break;
+        };
+        run_claimed(app_conf, task_info, &worker_id, conn).await;
+        count += 1;
+    }
+
+    if count == 0 {
+        info!("Waiting task list is empty");
+    } else {
+        info!("Waiting task list all done, count: {}", count);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use crate::AppConfig;
+use crate::model::task::{Task, TaskStatus};
+use crate::task::job::Job;
+use crate::task::question::QuestionUploadJob;
+use crate::util::mail::Lang;
+use log::{error, info};
+use serde_json::Value;
+
+/// 任务结果邮件
+///
+/// 任务处理成功或者失败后发送到添加任务时填写的邮箱, 取消的任务不发送
+/// 邮件内容从任务输出中读取题目数量和警告, 没有这些字段的任务类型只发送结果
+
+// 邮件模板, {name} 等占位符在发送时替换
+struct Template {
+    success_subject: &'static str,
+    failed_subject: &'static str,
+    success_body: &'static str,
+    failed_body: &'static str,
+    rolled_back: &'static str,
+    counts: &'static str,
+    skipped: &'static str,
+    warnings: &'static str,
+    link: &'static str,
+    footer: &'static str,
+}
+
+const ZH: Template = Template {
+    success_subject: "[题库] 任务处理成功: {name}",
+    failed_subject: "[题库] 任务处理失败: {name}",
+    success_body: "您好,\n\n任务 {name} (编号 {id}, 类型 {kind}) 已处理成功。\n",
+    failed_body: "您好,\n\n任务 {name} (编号 {id}, 类型 {kind}) 处理失败。\n\n失败原因:\n{result}\n",
+    rolled_back: "\n本次添加的题目已全部回滚。\n",
+    counts: "\n添加母题 {parents} 道, 变式题 {variants} 道, 共 {total} 道。\n",
+    skipped: "有问题跳过 {skipped} 道, 原因见下方列表。\n",
+    warnings: "\n以下题目需要检查 ({count} 条):\n",
+    link: "\n任务详情: {url}\n",
+    footer: "\n该邮件由系统自动发送, 请勿回复。\n",
+};
+
+const EN: Template = Template {
+    success_subject: "[Tiku] Task succeeded: {name}",
+    failed_subject: "[Tiku] Task failed: {name}",
+    success_body: "Hello,\n\nTask {name} (id {id}, kind {kind}) has been processed successfully.\n",
+    failed_body: "Hello,\n\nTask {name} (id {id}, kind {kind}) failed.\n\nReason:\n{result}\n",
+    rolled_back: "\nAll questions of this run have been rolled back.\n",
+    counts: "\nAdded {parents} parent questions and {variants} variants, {total} in total.\n",
+    skipped: "Skipped {skipped} questions with problems, see the list below.\n",
+    warnings: "\nThe following questions need to be checked ({count}):\n",
+    link: "\nTask details: {url}\n",
+    footer: "\nThis email was sent automatically, please do not reply.\n",
+};
+
+fn render(template: &str, vars: &[(&str, String)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |text, (key, value)| {
+            text.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+// 生成邮件标题和内容
+fn build(
+    lang: Lang,
+    task_url: &str,
+    task_info: &Task,
+    status: TaskStatus,
+    result: &str,
+    output: Option<&Value>,
+) -> (String, String) {
+    let template = if lang == Lang::En { &EN } else { &ZH };
+    let vars = [
+        ("name", task_info.name.clone()),
+        ("id", task_info.id.to_string()),
+        ("kind", task_info.kind.clone()),
+        ("result", result.to_string()),
+    ];
+
+    let (subject, mut body) = if status == TaskStatus::Success {
+        (
+            template.success_subject,
+            render(template.success_body, &vars),
+        )
+    } else {
+        (template.failed_subject, render(template.failed_body, &vars))
+    };
+    // 只有整个文件作为一个事务的题目上传才会全部回滚, 部分提交和其他任务类型不提示
+    if status == TaskStatus::Failed
+        && task_info.kind == QuestionUploadJob::NAME
+        && !task_info
+            .params
+            .get("partial")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    {
+        body.push_str(template.rolled_back);
+    }
+
+    if let Some(output) = output {
+        // 添加的题目数量包括变式题
+        if let Some(total) = output.get("questionCount").and_then(Value::as_u64) {
+            let variants = output
+                .get("similarCount")
+                .and_then(Value::as_u64)
+                .unwrap_or(0);
+            body.push_str(&render(
+                template.counts,
+                &[
+                    ("parents", total.saturating_sub(variants).to_string()),
+                    ("variants", variants.to_string()),
+                    ("total", total.to_string()),
+                ],
+            ));
+        }
+        // 部分提交时跳过的题目数量
+        if let Some(skipped) = output
+            .get("skippedCount")
+            .and_then(Value::as_u64)
+            .filter(|skipped| *skipped > 0)
+        {
+            body.push_str(&render(
+                template.skipped,
+                &[("skipped", skipped.to_string())],
+            ));
+        }
+
+        let warnings: Vec<&str> = output
+            .get("warnings")
+            .and_then(Value::as_array)
+            .map(|list| list.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        if !warnings.is_empty() {
+            body.push_str(&render(
+                template.warnings,
+                &[("count", warnings.len().to_string())],
+            ));
+            for warning in warnings {
+                body.push_str(&format!("  - {}\n", warning));
+            }
+        }
+    }
+
+    if !task_url.is_empty() {
+        let url = task_url.replace("{id}", &task_info.id.to_string());
+        body.push_str(&render(template.link, &[("url", url)]));
+    }
+    body.push_str(template.footer);
+
+    (render(subject, &vars), body)
+}
+
+// 发送任务结果邮件, 发送失败只记录日志不影响任务结果
+pub async fn send_result(
+    app_conf: &AppConfig,
+    task_info: &Task,
+    status: TaskStatus,
+    result: &str,
+    output: Option<&Value>,
+) {
+    let Some(mailer) = &app_conf.mailer else {
+        return;
+    };
+    if task_info.email.trim().is_empty() {
+        return;
+    }
+    if status != TaskStatus::Success && status != TaskStatus::Failed {
+        return;
+    }
+
+    let (subject, body) = build(
+        mailer.lang,
+        &mailer.task_url,
+        task_info,
+        status,
+        result,
+        output,
+    );
+    match mailer.send(task_info.email.trim(), &subject, body).await {
+        Ok(_) => info!(
+            "Send task id: {} result mail to {} done",
+            task_info.id, task_info.email
+        ),
+        Err(e) => error!(
+            "Send task id: {} result mail to {} err: {}",
+            task_info.id, task_info.email, e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::task::{Task, TaskStatus};
+    use crate::task::notify::build;
+    use crate::util::mail::Lang;
+    use serde_json::{Value, json};
+    use sqlx::types::Json;
+
+    fn task(kind: &str, params: Value) -> Task {
+        Task {
+            id: 42,
+            question_cate_id: 1,
+            task_type: 1,
+            name: "第一单元.md".to_string(),
+            url: String::new(),
+            email: "a@b.c".to_string(),
+            textbook_id: 1,
+            author_id: 1,
+            status: 0,
+            result: None,
+            kind: kind.to_string(),
+            params: Json(params),
+            output: None,
+            worker_id: None,
+            started_at: None,
+            heartbeat_at: None,
+            attempts: 0,
+            run_after: None,
+            cancel_requested: false,
+            progress_phase: String::new(),
+            progress_done: 0,
+            progress_total: 0,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_build_success() {
+        let info = task("question-upload", json!({}));
+        let output = json!({
+            "questionCount": 5,
+            "similarCount": 2,
+            "skippedCount": 1,
+            "warnings": ["第 3 题缺少答案"],
+        });
+
+        let (subject, body) = build(
+            Lang::Zh,
+            "https://tiku/task/{id}",
+            &info,
+            TaskStatus::Success,
+            "ok",
+            Some(&output),
+        );
+        assert_eq!(subject, "[题库] 任务处理成功: 第一单元.md");
+        assert!(body.contains("任务 第一单元.md (编号 42, 类型 question-upload) 已处理成功"));
+        assert!(body.contains("添加母题 3 道, 变式题 2 道, 共 5 道"));
+        assert!(body.contains("有问题跳过 1 道"));
+        assert!(body.contains("以下题目需要检查 (1 条)"));
+        assert!(body.contains("  - 第 3 题缺少答案\n"));
+        assert!(body.contains("任务详情: https://tiku/task/42"));
+        assert!(body.ends_with("请勿回复。\n"));
+
+        let (subject, body) = build(
+            Lang::En,
+            "https://tiku/task/{id}",
+            &info,
+            TaskStatus::Success,
+            "ok",
+            Some(&output),
+        );
+        assert_eq!(subject, "[Tiku] Task succeeded: 第一单元.md");
+        assert!(body.contains("Task 第一单元.md (id 42, kind question-upload) has been processed"));
+        assert!(body.contains("Added 3 parent questions and 2 variants, 5 in total"));
+        assert!(body.contains("Skipped 1 questions"));
+        assert!(body.contains("need to be checked (1)"));
+        assert!(body.contains("Task details: https://tiku/task/42"));
+    }
+
+    #[test]
+    fn test_build_without_output() {
+        let info = task("question-upload", json!({}));
+        let (_, body) = build(Lang::Zh, "", &info, TaskStatus::Success, "ok", None);
+        assert!(!body.contains("添加母题"));
+        assert!(!body.contains("跳过"));
+        assert!(!body.contains("需要检查"));
+        assert!(!body.contains("任务详情"));
+
+        // 没有跳过和警告时不输出对应段落
+        let output = json!({"questionCount": 2, "skippedCount": 0, "warnings": []});
+        let (_, body) = build(
+            Lang::En,
+            "",
+            &info,
+            TaskStatus::Success,
+            "ok",
+            Some(&output),
+        );
+        assert!(body.contains("Added 2 parent questions and 0 variants, 2 in total"));
+        assert!(!body.contains("Skipped"));
+        assert!(!body.contains("need to be checked"));
+        assert!(!body.contains("Task details"));
+    }
+
+    #[test]
+    fn test_build_failed() {
+        let info = task("question-upload", json!({"partial": false}));
+        let (subject, body) = build(Lang::Zh, "", &info, TaskStatus::Failed, "文件不存在", None);
+        assert_eq!(subject, "[题库] 任务处理失败: 第一单元.md");
+        assert!(body.contains("失败原因:\n文件不存在"));
+        assert!(body.contains("本次添加的题目已全部回滚"));
+
+        let (subject, body) = build(Lang::En, "", &info, TaskStatus::Failed, "not found", None);
+        assert_eq!(subject, "[Tiku] Task failed: 第一单元.md");
+        assert!(body.contains("Reason:\nnot found"));
+        assert!(body.contains("have been rolled back"));
+
+        // 部分提交的上传和其他任务类型不会全部回滚
+        let info = task("question-upload", json!({"partial": true}));
+        let (_, body) = build(Lang::Zh, "", &info, TaskStatus::Failed, "err", None);
+        assert!(!body.contains("回滚"));
+        let info = task("textbook-export", json!({}));
+        let (_, body) = build(Lang::En, "", &info, TaskStatus::Failed, "err", None);
+        assert!(!body.contains("rolled back"));
+    }
+}
@@ -1,17 +1,104 @@
-use crate::AppConfig;
 use crate::service::question_upload;
-use log::error;
+use crate::task::job::{Job, JobContext};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::io::Error;
 
 /// 批量上传题目
 ///
-/// 部署定时任务时避免任务重复启动, 使用文件锁的方式控制
-/// */5 * * * * /usr/bin/flock -n /tmp/my_task.lock -c './a/ddd task question-upload'
-/// 具体的文件需要查看自己的部署机器使用一个统一的文件即可
-/// 执行命令 -c 后面需要使用单或者双引号包裹
+/// 任务通过数据库行锁领取, 定时任务重复启动或者多个进程同时执行都不会重复处理同一个文件
+/// */5 * * * * ./a/ddd task question-upload
 ///
 
-pub async fn upload(config: &AppConfig) {
-    if let Err(e) = question_upload::batch(config).await {
-        error!("Upload question failed err: {}", e);
+pub struct QuestionUploadJob;
+
+#[derive(Serialize, Deserialize)]
+pub struct QuestionUploadParams {
+    #[serde(rename = "questionCateId")]
+    pub question_cate_id: i32, // 题型主键
+    #[serde(rename = "textbookId")]
+    pub textbook_id: i32, // 教材节点, 用来查询题目类型和标签
+    pub url: String, // 上传的文件名称
+    #[serde(default)]
+    pub partial: bool, // 为 true 时跳过有问题的题目并提交其余题目, 默认整个文件作为一个事务
+}
+
+#[derive(Serialize)]
+pub struct QuestionUploadOutput {
+    #[serde(rename = "questionCount")]
+    pub question_count: usize, // 添加的题目数量, 包括变式题
+    #[serde(rename = "similarCount")]
+    pub similar_count: usize, // 关联的变式题数量
+    pub logs: Vec<String>,     // 处理过程
+    pub warnings: Vec<String>, // 题目相关的警告和跳过原因, 带有题目标题和行号
+    #[serde(rename = "skippedCount")]
+    pub skipped_count: usize, // 跳过的题目数量, 只有部分提交时不为 0
+    pub items: Vec<QuestionImportItem>, // 每个题目的处理结果
+}
+
+// 单个题目的处理结果
+#[derive(Serialize)]
+pub struct QuestionImportItem {
+    pub title: String,
+    #[serde(rename = "isParent")]
+    pub is_parent: bool, // 母题或者变式题
+    #[serde(rename = "lineStart")]
+    pub line_start: usize, // 在文件中的起止行号
+    #[serde(rename = "lineEnd")]
+    pub line_end: usize,
+    pub fields: QuestionImportFields,
+    pub outcome: ImportOutcome,
+    #[serde(rename = "questionId")]
+    pub question_id: Option<i64>, // 添加成功后的题目主键
+    pub warnings: Vec<String>, // 不影响添加的问题
+    pub errors: Vec<String>,   // 导致题目无法添加的问题
+}
+
+// 解析出的题目字段
+#[derive(Serialize)]
+pub struct QuestionImportFields {
+    pub stem: String,
+    pub choices: Vec<String>,
+    #[serde(rename = "difficultyLevel")]
+    pub difficulty_level: String, // 文档中的难度
+    pub difficulty: Decimal, // 实际使用的难度
+    pub stage: String,
+    #[serde(rename = "questionType")]
+    pub question_type: String, // 文档中的题目类型
+    #[serde(rename = "questionTypeId")]
+    pub question_type_id: i32, // 匹配到的题目类型, -1 为没有匹配
+    pub knowledge: String,
+    #[serde(rename = "knowledgeIds")]
+    pub knowledge_ids: Vec<i32>, // 匹配到的知识点
+    pub answer: String,
+    pub analysis: String,
+    pub detail: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportOutcome {
+    Valid,   // 检查通过, 预览时或者添加前的状态
+    Added,   // 已添加
+    Skipped, // 题目有问题或者所属母题有问题, 没有添加
+    Failed,  // 添加时数据库操作失败
+}
+
+impl Job for QuestionUploadJob {
+    const NAME: &'static str = "question-upload";
+
+    type Params = QuestionUploadParams;
+    type Output = QuestionUploadOutput;
+
+    async fn run(&self, ctx: &JobContext<'_>, params: Self::Params) -> Result<Self::Output, Error> {
+        question_upload::upload(ctx, &params).await
+    }
+
+    fn summary(output: &Self::Output) -> String {
+        let mut summary = output.logs.join("\n");
+        for warning in &output.warnings {
+            summary.push_str(&format!("警告: {}\n", warning));
+        }
+        summary
     }
 }
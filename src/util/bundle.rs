@@ -0,0 +1,125 @@
+use crate::constant::meta;
+use crate::util::markdown_parse::normalize_path;
+use std::collections::HashMap;
+use std::io::{Cursor, Error, ErrorKind, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// 题目导入压缩包
+///
+/// 压缩包中包含一个 markdown 文档和文档引用的图片, 图片路径相对文档所在目录
+/// 例如 doc.md 和 img/a.png, 文档中引用为 ![](img/a.png)
+
+// 压缩包最多包含的文件数量
+const MAX_ENTRIES: usize = 1000;
+// 解压后的总大小上限
+const MAX_UNPACKED_SIZE: u64 = 100 * 1024 * 1024;
+
+pub struct Bundle {
+    pub markdown: String,                 // 文档内容
+    pub images: HashMap<String, Vec<u8>>, // 图片路径->图片内容, 路径相对文档所在目录
+}
+
+// 通过文件头判断是否为 zip 压缩包, 上传后的文件名称没有扩展名
+pub fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"PK\x03\x04")
+}
+
+// 文件扩展名, 小写
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase())
+        .unwrap_or_default()
+}
+
+// 读取压缩包中的文档和图片
+pub fn read_bundle(bytes: &[u8]) -> Result<Bundle, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("压缩包格式错误: {}", e)))?;
+    if archive.len() > MAX_ENTRIES {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("压缩包文件数量不能超过 {}", MAX_ENTRIES),
+        ));
+    }
+
+    let mut markdown: Option<(String, String)> = None;
+    let mut images: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut unpacked_size = 0;
+    for idx in 0..archive.len() {
+        let mut file = archive
+            .by_index(idx)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("压缩包读取失败: {}", e)))?;
+        // 跳过目录和系统生成的文件
+        let name = normalize_path(file.name());
+        if file.is_dir()
+            || name.starts_with("__MACOSX/")
+            || name.split('/').any(|part| part.starts_with('.'))
+        {
+            continue;
+        }
+
+        let ext = extension(&name);
+        let is_image = meta::ALLOW_IMAGE_EXTENSION.contains(&ext.as_str());
+        if ext != "md" && !is_image {
+            continue;
+        }
+        // 文件头中的大小不可信, 按实际读取的字节数限制, 多读一个字节判断是否超过
+        let limit = if is_image {
+            meta::MAX_IMAGE_SIZE as u64
+        } else {
+            MAX_UNPACKED_SIZE - unpacked_size
+        };
+        let mut content = Vec::new();
+        (&mut file).take(limit + 1).read_to_end(&mut content)?;
+        let size = content.len() as u64;
+        if is_image && size > limit {
+            return Err(Error::new(
+                ErrorKind::FileTooLarge,
+                format!(
+                    "图片 {} 大小超过限制: {}MB",
+                    name,
+                    meta::MAX_IMAGE_SIZE / 1024 / 1024
+                ),
+            ));
+        }
+        unpacked_size += size;
+        if unpacked_size > MAX_UNPACKED_SIZE {
+            return Err(Error::new(ErrorKind::FileTooLarge, "压缩包解压后过大"));
+        }
+
+        if is_image {
+            images.insert(name, content);
+            continue;
+        }
+        if let Some((first, _)) = &markdown {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("压缩包只能包含一个 markdown 文档: {}, {}", first, name),
+            ));
+        }
+        let text = String::from_utf8(content)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "文档不是 UTF-8 编码"))?;
+        markdown = Some((name, text));
+    }
+
+    let (name, markdown) =
+        markdown.ok_or_else(|| Error::new(ErrorKind::InvalidData, "压缩包中没有 markdown 文档"))?;
+
+    // 图片路径改为相对文档所在目录
+    let dir = match name.rfind('/') {
+        Some(idx) => &name[..idx + 1],
+        None => "",
+    };
+    let images = images
+        .into_iter()
+        .map(|(path, content)| match path.strip_prefix(dir) {
+            Some(relative) => (relative.to_string(), content),
+            None => (path, content),
+        })
+        .collect();
+
+    Ok(Bundle { markdown, images })
+}
@@ -0,0 +1,118 @@
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::error;
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+
+/// 邮件发送
+///
+/// 使用 SMTP 发送纯文本邮件, 不使用连接池, 每次发送单独建立连接
+/// 本地调试可以使用 mailpit 等 SMTP 捕获服务, 加密方式配置为 none
+
+// 发送超时时间
+const SEND_TIMEOUT_SECS: u64 = 30;
+
+// 邮件内容语言
+#[derive(Clone, Copy, PartialEq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    pub fn from(s: &str) -> Lang {
+        match s.trim().to_lowercase().as_str() {
+            "en" => Lang::En,
+            _ => Lang::Zh,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    pub lang: Lang,
+    pub task_url: String, // 任务详情链接, {id} 替换为任务主键
+}
+
+// SMTP 连接配置
+pub struct SmtpConfig<'a> {
+    pub host: &'a str,
+    pub port: u16,
+    pub tls: &'a str, // none starttls tls
+    pub username: &'a str,
+    pub password: &'a str,
+    pub from: &'a str,
+}
+
+impl Mailer {
+    pub fn new(conf: &SmtpConfig, lang: Lang, task_url: String) -> Result<Self, Error> {
+        let from = conf.from.parse::<Mailbox>().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("发件人地址格式错误: {}", e),
+            )
+        })?;
+
+        let builder = match conf.tls {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(conf.host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(conf.host)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?,
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(conf.host)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("不支持的加密方式: {}", conf.tls),
+                ));
+            }
+        };
+        let mut builder = builder
+            .port(conf.port)
+            .timeout(Some(Duration::from_secs(SEND_TIMEOUT_SECS)));
+        if !conf.username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                conf.username.to_string(),
+                conf.password.to_string(),
+            ));
+        }
+
+        Ok(Mailer {
+            transport: builder.build(),
+            from,
+            lang,
+            task_url,
+        })
+    }
+
+    // 发送纯文本邮件
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), Error> {
+        let to = to.parse::<Mailbox>().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("收件人地址格式错误: {}", e),
+            )
+        })?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .map_err(|e| {
+                error!("Build mail message err: {:?}", e);
+                Error::new(ErrorKind::Other, "邮件内容生成失败")
+            })?;
+
+        self.transport.send(message).await.map_err(|e| {
+            error!("Send mail err: {:?}", e);
+            Error::new(ErrorKind::Other, "邮件发送失败")
+        })?;
+
+        Ok(())
+    }
+}
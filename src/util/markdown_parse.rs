@@ -1,28 +1,38 @@
 use log::error;
-use pulldown_cmark::utils::TextMergeStream;
-use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use pulldown_cmark_to_cmark::cmark_with_source_range_and_options;
 use regex::Regex;
 use rust_decimal::Decimal;
+use std::collections::HashMap;
 use std::io::Error;
+use std::ops::Range;
 use std::str::FromStr;
+use std::sync::LazyLock;
+
+// 图片引用 ![说明](地址)
+static IMAGE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[[^\]]*\]\(([^)\s]+)\)").unwrap());
 
 /// 从 markdown 文档中解析出题目结构和内容
-/// 1. 目前表格处理只能处理固定的表格, 存在其余表格时无法正确解析, 需后续完善
-/// 2. 图片不支持, 因为文档本身无法提供图片
+/// 1. 题干, 答案等内容支持 GFM 表格, 行内代码, 列表, 引用和换行, 输出为规范化的 markdown
+/// 2. 图片保留为 ![](地址) 形式的引用, 图片文件通过 zip 压缩包和文档一起上传
 
 // 原始题目内容
 #[derive(Debug)]
 pub struct RawQuestion {
-    pub title: String,            // 标题
-    pub stem: String,             // 题干
-    pub difficulty_level: String, // 难度
-    pub stage: String,            // 学段
-    pub question_type: String,    // 题目类型
-    pub choices: Vec<String>,     // 选项内容
-    pub knowledge: String,        // 知识点
-    pub answer: String,           // 参考答案
-    pub analysis: String,         // 解题分析
-    pub detail: String,           // 详解, 对应解题过程
+    pub title: String,             // 标题
+    pub stem: String,              // 题干
+    pub difficulty_level: String,  // 难度
+    pub stage: String,             // 学段
+    pub question_type: String,     // 题目类型
+    pub choices: Vec<String>,      // 选项内容
+    pub knowledge: String,         // 知识点
+    pub answer: String,            // 参考答案
+    pub analysis: String,          // 解题分析
+    pub detail: String,            // 详解, 对应解题过程
+    pub unknown_tags: Vec<String>, // 无法识别的加粗标签
+    pub line_start: usize,         // 在文档中的起始行号, 从 1 开始, 片段解析时为 0
+    pub line_end: usize,           // 在文档中的结束行号
 }
 
 #[derive(Debug)]
@@ -33,41 +43,43 @@ pub struct Question {
 
 // 一级：分母题
 // 按母题分块, 区块内的变式题均为母题的变式题
-fn split_parents(text: &str) -> Vec<String> {
+// 返回 区块起始行号->区块内容 的列表, 区块以母题标题行开始, 行号从 1 开始
+fn split_parents(text: &str) -> Vec<(usize, String)> {
     let mut parents = Vec::new();
     let mut buf = String::new();
-    let mut started = false;
-    for line in text.lines() {
+    let mut start = 0;
+    for (idx, line) in text.lines().enumerate() {
         if line.trim_start().starts_with("##### 母题") {
             // 遇到第二个母题将上一个母题记录并清空 buf 继续保存其它题目
-            if started && !buf.trim().is_empty() {
-                parents.push(buf.trim().to_string());
+            if start > 0 && !buf.trim().is_empty() {
+                parents.push((start, buf.trim_end().to_string()));
                 buf.clear();
             }
-            started = true;
+            start = idx + 1;
         }
-        if started {
+        if start > 0 {
             buf.push_str(line);
             buf.push('\n');
         }
     }
     if !buf.trim().is_empty() {
-        parents.push(buf.trim().to_string());
+        parents.push((start, buf.trim_end().to_string()));
     }
     parents
 }
 
 // 二级：分所有H5标题（母题+变式）
-// 返回 标题->原始整体内容 的列表
-fn split_parents_and_children(block: &str) -> Vec<(String, String)> {
+// 返回 标题->原始整体内容 的列表, 以及在区块中的起止行号(从 0 开始)
+fn split_parents_and_children(block: &str) -> Vec<(String, String, usize, usize)> {
     let mut res = Vec::new();
     let mut buf = String::new();
     let mut title = String::new();
-    for line in block.lines() {
+    let (mut first, mut last) = (0, 0);
+    for (idx, line) in block.lines().enumerate() {
         if line.trim_start().starts_with("##### ") {
             // 第二次遇见标题行说明一个题目已完整记录到 buf, 保存后清空继续处理下一个题
             if !buf.is_empty() {
-                res.push((title.clone(), buf.trim().to_string()));
+                res.push((title.clone(), buf.trim().to_string(), first, last));
                 buf.clear();
             }
             title = line
@@ -76,14 +88,25 @@ fn split_parents_and_children(block: &str) -> Vec<(String, String)> {
                 .trim()
                 .to_string();
         }
-        // 将当前行也追加到原始内容中
-        if !line.trim().is_empty() || line.trim_start().starts_with("##### ") {
+        // 将当前行也追加到原始内容中, 保留空行用于区分段落, 表格等块级元素
+        if line.trim().is_empty() {
+            if !buf.is_empty() {
+                buf.push('\n');
+            }
+            continue;
+        }
+        if buf.is_empty() {
+            first = idx;
+        }
+        // 题目之间的分割线换成空行, 避免紧跟在文字后面时被当作标题的下划线
+        if line.trim() != "---" {
             buf.push_str(line);
-            buf.push('\n');
         }
+        buf.push('\n');
+        last = idx;
     }
     if !buf.trim().is_empty() {
-        res.push((title.clone(), buf.trim().to_string()));
+        res.push((title.clone(), buf.trim().to_string(), first, last));
     }
     res
 }
@@ -123,118 +146,315 @@ enum Section {
     Detail,          // 解题过程, 详解
 }
 
+impl Section {
+    // 加粗标签对应的节点, 不是已知标签时返回 None
+    fn from_tag(tag: &str) -> Option<Section> {
+        match tag {
+            "难度" => Some(Section::DifficultyLevel),
+            "适用学期" => Some(Section::Stage),
+            "题目类型" => Some(Section::QuestionType),
+            "涉及知识点" => Some(Section::Knowledge),
+            "参考答案" => Some(Section::Answer),
+            "【分析】" => Some(Section::Analysis),
+            "【详解】" => Some(Section::Detail),
+            _ => None,
+        }
+    }
+}
+
+// 形如标签的加粗文字, 以冒号结尾或者被【】包裹
+fn is_tag_like(text: &str) -> bool {
+    text.ends_with([':', '：']) || (text.starts_with('【') && text.ends_with('】'))
+}
+
+// 题目内容区块, 保留原始的 markdown 事件, 最后统一输出为规范的 markdown
+// 标签可能出现在列表, 引用等块级元素中间, 因此每个区块单独记录自己打开的标签及其层级
+#[derive(Default)]
+struct Block<'a> {
+    events: Vec<(Event<'a>, Option<Range<usize>>)>,
+    open: Vec<(usize, TagEnd)>, // 已打开的标签, 层级从 1 开始
+    trim_start: bool,           // 跳过标签后面的空白和换行
+}
+
+impl<'a> Block<'a> {
+    // 区块从块级元素中间开始时补一个段落, 保证行内内容位于块级元素中
+    fn ensure_block(&mut self, depth: usize) {
+        if depth > 0 && self.open.is_empty() {
+            self.events.push((Event::Start(Tag::Paragraph), None));
+            self.open.push((depth, TagEnd::Paragraph));
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'a>, range: Range<usize>, depth: usize) {
+        if matches!(
+            tag,
+            Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+        ) {
+            self.ensure_block(depth - 1);
+            self.trim_start = false;
+        }
+        self.open.push((depth, tag.to_end()));
+        self.events.push((Event::Start(tag), Some(range)));
+    }
+
+    // 只结束本区块打开的标签, 其余标签属于前面的区块
+    fn end(&mut self, range: Range<usize>, depth: usize) {
+        if self.open.last().is_some_and(|(d, _)| *d == depth) {
+            let (_, end) = self.open.pop().unwrap();
+            self.events.push((Event::End(end), Some(range)));
+        }
+    }
+
+    fn push(&mut self, event: Event<'a>, mut range: Range<usize>, depth: usize) {
+        let event = match event {
+            Event::SoftBreak | Event::HardBreak if self.trim_start => return,
+            Event::Text(t) if self.trim_start => {
+                let text = t.trim_start();
+                if text.is_empty() {
+                    return;
+                }
+                range.start += t.len() - text.len();
+                Event::Text(text.to_string().into())
+            }
+            event => event,
+        };
+        self.trim_start = false;
+        self.ensure_block(depth);
+        self.events.push((event, Some(range)));
+    }
+
+    // 切换到其它标签前结束所有打开的标签, 去掉末尾的换行和没有内容的标签
+    fn close(&mut self) {
+        while let Some((Event::SoftBreak | Event::HardBreak, _)) = self.events.last() {
+            self.events.pop();
+        }
+        while let Some((_, end)) = self.open.pop() {
+            match self.events.last() {
+                Some((Event::Start(tag), _)) if tag.to_end() == end => {
+                    self.events.pop();
+                }
+                _ => self.events.push((Event::End(end), None)),
+            }
+        }
+    }
+
+    fn to_markdown(&self, source: &str) -> String {
+        let options = pulldown_cmark_to_cmark::Options {
+            code_block_token_count: 3,
+            list_token: '-',
+            increment_ordered_list_bullets: true,
+            ..Default::default()
+        };
+        let mut buf = String::new();
+        // 按原文保留转义字符, 比如填空的 \_\_\_
+        let events = self.events.iter().map(|(e, r)| (e, r.clone()));
+        if let Err(e) = cmark_with_source_range_and_options(events, source, &mut buf, options) {
+            error!("Write markdown question content error: {:?}", e);
+        }
+        normalize_block_quote(&buf).trim().to_string()
+    }
+}
+
+// 引用行的缩进和引用层级, 输出的引用每一层为 " > "
+fn quote_prefix(line: &str) -> (&str, usize, &str) {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let indent = if line[indent..].starts_with('>') && indent > 0 {
+        indent - 1
+    } else {
+        indent
+    };
+    let (indent, mut rest) = line.split_at(indent);
+    let mut depth = 0;
+    while let Some(next) = rest.strip_prefix(" >") {
+        depth += 1;
+        rest = next.strip_prefix(' ').unwrap_or(next);
+    }
+    (indent, depth, rest)
+}
+
+// 引用统一为 "> 内容" 的形式, 去掉每一层引用开始时多余的空引用行和行首的空格
+// 代码块中只处理代码块所在的引用层级, 代码内容保持原样
+fn normalize_block_quote(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = vec![];
+    let mut code_depth: Option<usize> = None; // 所在代码块的引用层级
+    let mut prev_depth = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        let (indent, mut depth, mut rest) = quote_prefix(line);
+        let is_fence = |rest: &str| rest.starts_with("```") || rest.starts_with("~~~");
+        match code_depth {
+            Some(code) => {
+                if depth > code {
+                    depth = code;
+                    rest = line.get(indent.len() + code * 3..).unwrap_or_default();
+                }
+                if is_fence(rest) {
+                    code_depth = None;
+                }
+            }
+            None if is_fence(rest) => code_depth = Some(depth),
+            None => {
+                // 引用开始时的空引用行
+                let next_depth = lines.get(idx + 1).map_or(0, |next| quote_prefix(next).1);
+                if depth > prev_depth && rest.trim().is_empty() && next_depth >= depth {
+                    prev_depth = depth;
+                    continue;
+                }
+            }
+        }
+        prev_depth = depth;
+        if depth == 0 {
+            out.push(line.to_string());
+        } else if rest.is_empty() {
+            out.push(format!("{}{}", indent, vec![">"; depth].join(" ")));
+        } else {
+            out.push(format!("{}{}{}", indent, "> ".repeat(depth), rest));
+        }
+    }
+    out.join("\n")
+}
+
 // 主Markdown->结构化题的解析
-// 原始 Markdown（以及 CommonMark、GFM 等主流实现）的核心规则
-// 行末两个空格 + 换行符 → 产生 <br> 换行（软换行）
-// 单纯的换行符 → 被当作普通空格处理，不会产生新行（即相邻两行会合并成一行）
+// 题干, 答案, 分析, 详解保留表格, 代码, 列表, 引用, 图片和换行等格式, 输出为与原文渲染一致的 markdown
+// 难度, 学段等其余字段只保留文字内容
 fn parse_question(title: String, markdown: &str) -> RawQuestion {
-    let parser = Parser::new(markdown);
-    // markdown 会将一些特殊的符号当作语法处理, 比如 _ $a_{1},a_{2},a_{3},\ldots,a_{n}$
-    // a1,a2,...,an 此时就会因为 _ 导致被拆成片段, TextMergeStream 就是将这样的片段合并为一行, 避免后续添加 换行时被折断
-    let events = TextMergeStream::new(parser);
+    // 开启公式解析, 避免 $a_{1},a_{2}$ 中的 _ 被当作强调语法
+    let options = Options::ENABLE_TABLES | Options::ENABLE_MATH | Options::ENABLE_STRIKETHROUGH;
+    let mut events = Parser::new_ext(markdown, options)
+        .into_offset_iter()
+        .peekable();
 
     let mut state = Section::None;
+    let mut depth = 0; // 当前所在的标签层级
 
     let mut head5 = String::new(); // 母题变式题标题
-    let mut main_content = String::new(); // 题目主体内容包括选项等
+    let mut main_content = Block::default(); // 题目主体内容包括选项等
     let mut difficulty_level = String::new(); // 难度
     let mut stage = String::new(); // 学段
     let mut question_type = String::new(); // 题目类型
     let mut knowledge = String::new(); // 知识点
-    let mut answer = String::new(); // 参考答案
-    let mut analysis = String::new(); // 解题分析
-    let mut detail = String::new(); // 解题过程-详解
-    let mut in_strong = false; // 是否在后续的几个加粗标签中
+    let mut answer = Block::default(); // 参考答案
+    let mut analysis = Block::default(); // 解题分析
+    let mut detail = Block::default(); // 解题过程-详解
+    let mut unknown_tags: Vec<String> = vec![]; // 无法识别的加粗标签
+
+    while let Some((event, range)) = events.next() {
+        // 加粗的标签, 整个加粗内容都不作为题目内容
+        if let Event::Start(Tag::Strong) = event
+            && let Some((Event::Text(t), _)) = events.peek()
+        {
+            let s = t.trim().to_string();
+            // 去除一些特殊的字符
+            let tag = s.trim_end_matches([':', '：']);
+            let next = Section::from_tag(tag);
+            if next.is_some() || is_tag_like(&s) {
+                for (e, _) in events.by_ref() {
+                    if let Event::End(TagEnd::Strong) = e {
+                        break;
+                    }
+                }
+                match next {
+                    Some(next) => {
+                        // 离开的区块结束打开的标签, 进入的区块按当前层级补段落
+                        let block = match state {
+                            Section::Answer => Some(&mut answer),
+                            Section::Analysis => Some(&mut analysis),
+                            Section::Detail => Some(&mut detail),
+                            Section::None => Some(&mut main_content),
+                            _ => None,
+                        };
+                        if let Some(block) = block {
+                            block.close();
+                        }
+                        match next {
+                            Section::Answer => answer.trim_start = true,
+                            Section::Analysis => analysis.trim_start = true,
+                            Section::Detail => detail.trim_start = true,
+                            _ => {}
+                        }
+                        state = next;
+                    }
+                    None => {
+                        error!(
+                            "{}",
+                            format!("Parse markdown question doc, unknown strong tag: {}", s)
+                        );
+                        unknown_tags.push(tag.to_string());
+                    }
+                }
+                continue;
+            }
+        }
 
-    for event in events {
         match event {
-            Event::Start(Tag::Heading { level, .. }) => {
-                if level == pulldown_cmark::HeadingLevel::H5 {
-                    state = Section::Head5;
-                }
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H5,
+                ..
+            }) => {
+                depth += 1;
+                state = Section::Head5;
+                continue;
             }
-            Event::End(TagEnd::Heading { .. }) => {
+            Event::End(TagEnd::Heading(HeadingLevel::H5)) => {
+                depth -= 1;
                 if state == Section::Head5 {
                     state = Section::None;
                 }
+                continue;
             }
-            Event::Start(Tag::Strong) => {
-                in_strong = true;
-            }
-            Event::End(TagEnd::Strong) => {
-                in_strong = false;
-            }
-            Event::Text(t) => {
-                let s = t.trim();
-
-                // 加粗的类型
-                if in_strong {
-                    // 去除一些特殊的字符
-                    match s.trim_end_matches(|c| c == ':' || c == '：') {
-                        "难度" => {
-                            state = Section::DifficultyLevel;
-                            continue;
-                        }
-                        "适用学期" => {
-                            state = Section::Stage;
-                            continue;
-                        }
-                        "题目类型" => {
-                            state = Section::QuestionType;
-                            continue;
-                        }
-                        "涉及知识点" => {
-                            state = Section::Knowledge;
-                            continue;
-                        }
-                        "参考答案" => {
-                            state = Section::Answer;
-                            continue;
-                        }
-                        "【分析】" => {
-                            state = Section::Analysis;
-                            continue;
-                        }
-                        "【详解】" => {
-                            state = Section::Detail;
-                            continue;
-                        }
-                        _ => {
-                            error!(
-                                "{}",
-                                format!("Parse markdown question doc, unknown strong tag: {}", s)
-                            );
-                            continue;
-                        }
-                    }
-                }
-                match state {
-                    Section::Head5 => head5.push_str(s),
-                    Section::DifficultyLevel => difficulty_level.push_str(&s),
-                    Section::Stage => stage.push_str(&s),
-                    Section::QuestionType => question_type.push_str(&s),
-                    Section::Knowledge => knowledge.push_str(s),
-                    Section::Answer => {
-                        answer.push_str(s);
-                        answer.push_str("  \n");
-                    }
-                    Section::Analysis => {
-                        analysis.push_str(s);
-                        analysis.push_str("  \n");
-                    }
-                    Section::Detail => {
-                        detail.push_str(s);
-                        detail.push_str("  \n");
+            // 分割线为题目之间的分隔
+            Event::Rule => continue,
+            _ => {}
+        }
+
+        let block = match state {
+            Section::Answer => &mut answer,
+            Section::Analysis => &mut analysis,
+            Section::Detail => &mut detail,
+            Section::None => &mut main_content,
+            _ => {
+                let text = match &event {
+                    Event::Text(t) | Event::Code(t) | Event::InlineMath(t) => t.trim(),
+                    Event::Start(_) => {
+                        depth += 1;
+                        continue;
                     }
-                    Section::None => {
-                        main_content.push_str(s);
-                        main_content.push_str("  \n");
+                    Event::End(_) => {
+                        depth -= 1;
+                        continue;
                     }
+                    _ => continue,
+                };
+                match state {
+                    Section::Head5 => head5.push_str(text),
+                    Section::DifficultyLevel => difficulty_level.push_str(text),
+                    Section::Stage => stage.push_str(text),
+                    Section::QuestionType => question_type.push_str(text),
+                    Section::Knowledge => knowledge.push_str(text),
+                    _ => {}
                 }
+                continue;
             }
-            _ => {}
+        };
+        match event {
+            Event::Start(tag) => {
+                depth += 1;
+                block.start(tag, range, depth);
+            }
+            Event::End(_) => {
+                block.end(range, depth);
+                depth -= 1;
+            }
+            event => block.push(event, range, depth),
         }
     }
+    let main_content = main_content.to_markdown(markdown);
     let (stem, choices) = extract_choices_and_stem(&main_content, &question_type);
 
     RawQuestion {
@@ -249,10 +469,70 @@ fn parse_question(title: String, markdown: &str) -> RawQuestion {
         stage: stage.trim().to_string(),
         question_type: question_type.trim().to_string(),
         knowledge: knowledge.trim().to_string(),
-        answer: answer.trim().to_string(),
-        analysis: analysis.trim().to_string(),
-        detail: detail.trim().to_string(),
+        answer: answer.to_markdown(markdown),
+        analysis: analysis.to_markdown(markdown),
+        detail: detail.to_markdown(markdown),
+        unknown_tags,
+        line_start: 0,
+        line_end: 0,
+    }
+}
+
+// 是否为需要随文档上传的本地图片, 网络图片直接保留
+pub fn is_local_image(url: &str) -> bool {
+    let url = url.to_lowercase();
+    !(url.starts_with("http://")
+        || url.starts_with("https://")
+        || url.starts_with("//")
+        || url.starts_with("data:"))
+}
+
+// 统一图片路径, 去掉 ./ 和 .. 等相对路径, 用于和压缩包中的文件对应
+pub fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = vec![];
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
     }
+    parts.join("/")
+}
+
+// 得到文本中引用的本地图片路径
+pub fn image_refs(text: &str) -> Vec<String> {
+    IMAGE_RE
+        .captures_iter(text)
+        .map(|caps| caps[1].to_string())
+        .filter(|url| is_local_image(url))
+        .map(|url| normalize_path(&url))
+        .collect()
+}
+
+// 将文本中的本地图片引用替换为存储后的名称, 返回替换后的文本和图片名称列表
+// 没有对应名称的引用保持不变
+pub fn rewrite_images(text: &str, names: &HashMap<String, String>) -> (String, Vec<String>) {
+    let mut images: Vec<String> = vec![];
+    let content = IMAGE_RE.replace_all(text, |caps: &regex::Captures| {
+        let url = &caps[1];
+        if !is_local_image(url) {
+            return caps[0].to_string();
+        }
+        match names.get(&normalize_path(url)) {
+            Some(name) => {
+                if !images.contains(name) {
+                    images.push(name.clone());
+                }
+                format!("![]({})", name)
+            }
+            None => caps[0].to_string(),
+        }
+    });
+
+    (content.to_string(), images)
 }
 
 // 解析出题目难度, 解析失败等均返回 1
@@ -299,22 +579,24 @@ pub fn get_questions(content: &str) -> Result<Vec<Question>, Error> {
     let blocks = split_parents(content);
     let mut all_questions = Vec::new();
 
-    for block in blocks {
+    for (start, block) in blocks {
         let subs = split_parents_and_children(&block);
         // 没有母题变式题
         if subs.is_empty() {
             continue;
         }
+        // 区块内的行号转换为文档中的行号
+        let parse = |(title, md, first, last): &(String, String, usize, usize)| {
+            let mut question = parse_question(title.clone(), md);
+            question.line_start = start + first;
+            question.line_end = start + last;
+            question
+        };
         // 第一项为母题
-        let (parent_title, parent_md) = &subs[0];
-        let parent_struct = parse_question(parent_title.clone(), parent_md);
+        let parent_struct = parse(&subs[0]);
 
         // 变式
-        let mut var_vec = Vec::new();
-        for (title, md) in subs.iter().skip(1) {
-            let var = parse_question(title.clone(), md);
-            var_vec.push(var);
-        }
+        let var_vec: Vec<RawQuestion> = subs.iter().skip(1).map(parse).collect();
 
         all_questions.push(Question {
             parent: parent_struct,
@@ -332,7 +614,8 @@ pub fn get_question(content: &str) -> RawQuestion {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::markdown_parse::get_questions;
+    use crate::util::markdown_parse::{get_question, get_questions, image_refs, rewrite_images};
+    use std::collections::HashMap;
 
     #[test]
     fn test_parse() {
@@ -460,4 +743,120 @@ $a_{10} = 6 \times 10 + 5 = 65$，
             }
         }
     }
+
+    #[test]
+    fn test_line_range() {
+        let content = "说明文字\n\n##### 母题 1\n\n计算 $1+1$\n\n**备注：** 无\n\n---\n\n##### 变式 1\n\n计算 $2+2$\n\n\n##### 母题 2\n\n计算 $3+3$\n";
+        let all_questions = get_questions(content).unwrap();
+
+        assert_eq!(all_questions.len(), 2);
+        let first = &all_questions[0];
+        assert_eq!((first.parent.line_start, first.parent.line_end), (3, 9));
+        assert_eq!(first.parent.unknown_tags, vec!["备注".to_string()]);
+        let child = &first.children[0];
+        assert_eq!((child.line_start, child.line_end), (11, 13));
+        let second = &all_questions[1].parent;
+        assert_eq!((second.line_start, second.line_end), (16, 18));
+    }
+
+    #[test]
+    fn test_images() {
+        let content = "##### 母题 1\n\n如图 ![图1](./img/stem.png) 所示（   ）\n\nA．![](img/a.png)　　B．![](img/b.png)\n\n**题目类型：** 选择题\n\n**【分析】** 见 ![](img/a.png) 和 ![](https://example.com/x.png)\n";
+        let all_questions = get_questions(content).unwrap();
+        let parent = &all_questions[0].parent;
+
+        assert_eq!(
+            parent.choices,
+            vec!["A．![](img/a.png)", "B．![](img/b.png)"]
+        );
+        assert_eq!(image_refs(&parent.stem), vec!["img/stem.png"]);
+        assert_eq!(image_refs(&parent.analysis), vec!["img/a.png"]);
+
+        let names = HashMap::from([
+            ("img/stem.png".to_string(), "s1".to_string()),
+            ("img/a.png".to_string(), "a1".to_string()),
+        ]);
+        let (stem, images) = rewrite_images(&parent.stem, &names);
+        assert!(stem.contains("![](s1)"));
+        assert_eq!(images, vec!["s1"]);
+        let (analysis, images) = rewrite_images(&parent.analysis, &names);
+        assert!(analysis.contains("![](a1)"));
+        assert!(analysis.contains("![](https://example.com/x.png)"));
+        assert_eq!(images, vec!["a1"]);
+    }
+
+    #[test]
+    fn test_table() {
+        let content = "##### 母题 1\n\n根据下表求平均分\n\n| 姓名 | 分数 |\n| :--- | ---: |\n| 甲 | 90 |\n| 乙 | 80 |\n\n**参考答案：** 85\n\n**【详解】** 列表计算\n\n|合计|平均|\n|---|---|\n|170|85|\n";
+        let parent = &get_questions(content).unwrap()[0].parent;
+
+        assert_eq!(
+            parent.stem,
+            "根据下表求平均分\n\n|姓名|分数|\n|:-|-:|\n|甲|90|\n|乙|80|"
+        );
+        assert_eq!(parent.answer, "85");
+        assert_eq!(parent.detail, "列表计算\n\n|合计|平均|\n|--|--|\n|170|85|");
+    }
+
+    #[test]
+    fn test_inline_code() {
+        let raw = get_question(
+            "调用 `sum(a, b)` 的结果是多少\n\n**参考答案：** `3`\n\n**【分析】** 函数 `sum` 返回 `a + b`, 注意 `` ` `` 符号",
+        );
+
+        assert_eq!(raw.stem, "调用 `sum(a, b)` 的结果是多少");
+        assert_eq!(raw.answer, "`3`");
+        assert_eq!(raw.analysis, "函数 `sum` 返回 `a + b`, 注意 `` ` `` 符号");
+    }
+
+    #[test]
+    fn test_list() {
+        let raw = get_question(
+            "下列说法正确的是\n\n1. 甲\n2. 乙\n   * 乙一\n   * 乙二\n\n**【详解】** 逐项判断:\n\n- 甲正确\n- 乙错误\n",
+        );
+
+        assert_eq!(
+            raw.stem,
+            "下列说法正确的是\n\n1. 甲\n2. 乙\n   - 乙一\n   - 乙二"
+        );
+        assert_eq!(raw.detail, "逐项判断:\n\n- 甲正确\n- 乙错误");
+    }
+
+    #[test]
+    fn test_block_quote() {
+        let content = "##### 母题 9\n\n> 观察下列等式：\n> $1 = 1^{2}$\n> $1 + 3 = 2^{2}$\n\n- **难度：** 3\n- **题目类型：** 解答题\n- **参考答案：** $n^2$\n**【详解】** 由规律得 $1+3+...+(2n-1)=n^2$.\n---";
+        let raw = &get_questions(content).unwrap()[0].parent;
+
+        // 引用保持为引用, 列表中的标签依然可以识别, 列表不会留在题干中
+        assert_eq!(
+            raw.stem,
+            "> 观察下列等式：\n> $1 = 1^{2}$\n> $1 + 3 = 2^{2}$"
+        );
+        assert_eq!(raw.difficulty_level, "3");
+        assert_eq!(raw.question_type, "解答题");
+        assert_eq!(raw.answer, "$n^2$");
+        assert_eq!(raw.detail, "由规律得 $1+3+...+(2n-1)=n^2$.");
+    }
+
+    #[test]
+    fn test_line_break() {
+        let raw = get_question(
+            "第一行\n第二行  \n第三行\\\n第四行\n\n**【详解】**\n解：∵ $x = 2$，  \n∴ $2x = 4$。\n\n故选：B。",
+        );
+
+        // 软换行保持不变, 反斜杠硬换行统一为行末两个空格
+        assert_eq!(raw.stem, "第一行\n第二行  \n第三行  \n第四行");
+        assert_eq!(raw.detail, "解：∵ $x = 2$，  \n∴ $2x = 4$。\n\n故选：B。");
+    }
+
+    #[test]
+    fn test_escape() {
+        let raw = get_question("则 $a_{10} =$ \\_\\_\\_\\_ , 2 \\* 3 = 6, *强调* 和 **加粗**");
+
+        assert_eq!(
+            raw.stem,
+            "则 $a_{10} =$ \\_\\_\\_\\_ , 2 \\* 3 = 6, *强调* 和 **加粗**"
+        );
+        assert!(raw.unknown_tags.is_empty());
+    }
 }
@@ -3,3 +3,6 @@ pub mod response;
 pub mod upload;
 pub mod markdown_parse;
 pub mod local;
+pub mod similarity;
+pub mod mail;
+pub mod bundle;
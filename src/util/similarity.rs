@@ -0,0 +1,137 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+/// 题目内容相似度计算, 用于推荐变式题
+
+// 候选题目较多, 正则只编译一次
+static RE_FORMULA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\$?([^$]+)\$\$?").unwrap());
+static RE_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\\[a-zA-Z]+|[+\-*/=<>^_()\[\]{}|]").unwrap());
+
+// 字符二元组, 中文题目不分词直接按字符计算
+fn char_bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// 文本相似度, 字符二元组的 Dice 系数, 范围 0-1
+pub fn text_similarity(a: &str, b: &str) -> f64 {
+    let a = char_bigrams(a);
+    let b = char_bigrams(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let common = a.intersection(&b).count();
+    (2 * common) as f64 / (a.len() + b.len()) as f64
+}
+
+/// 公式结构, 统计公式中出现的 LaTeX 指令和运算符, 忽略具体数字和字母
+pub fn latex_structure(text: &str) -> HashMap<String, usize> {
+    let mut tokens: HashMap<String, usize> = HashMap::new();
+    for formula in RE_FORMULA.captures_iter(text) {
+        for token in RE_TOKEN.find_iter(&formula[1]) {
+            *tokens.entry(token.as_str().to_string()).or_default() += 1;
+        }
+    }
+
+    tokens
+}
+
+/// 公式结构相似度, 按出现次数计算的 Jaccard 系数, 范围 0-1
+pub fn latex_similarity(a: &HashMap<String, usize>, b: &HashMap<String, usize>) -> f64 {
+    let mut min_sum = 0;
+    let mut max_sum = 0;
+    for key in a.keys().chain(b.keys().filter(|key| !a.contains_key(*key))) {
+        let x = a.get(key).copied().unwrap_or(0);
+        let y = b.get(key).copied().unwrap_or(0);
+        min_sum += x.min(y);
+        max_sum += x.max(y);
+    }
+    if max_sum == 0 {
+        return 0.0;
+    }
+
+    min_sum as f64 / max_sum as f64
+}
+
+/// 集合重合度, Jaccard 系数, 范围 0-1
+pub fn set_similarity(a: &HashSet<i32>, b: &HashSet<i32>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    a.intersection(b).count() as f64 / a.union(b).count() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::similarity::{
+        latex_similarity, latex_structure, set_similarity, text_similarity,
+    };
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_text_similarity() {
+        let text = "已知二次函数的图像经过点";
+        assert_eq!(text_similarity(text, text), 1.0);
+        // 空白不参与计算
+        assert_eq!(text_similarity("二次 函数", "二次函数"), 1.0);
+        assert_eq!(text_similarity("二次函数", "三角形面积"), 0.0);
+        assert_eq!(text_similarity("", text), 0.0);
+        assert_eq!(text_similarity("二", "二"), 0.0);
+
+        // 二元组 {二次, 次函, 函数} 和 {二次, 次方, 方程} 有一个相同, 2 * 1 / (3 + 3)
+        let score = text_similarity("二次函数", "二次方程");
+        assert!((score - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_latex_structure() {
+        let tokens = latex_structure("计算 $\\frac{1}{2} + \\frac{a}{b}$ 和 $$x^2 = 4$$");
+        assert_eq!(tokens.get("\\frac"), Some(&2));
+        assert_eq!(tokens.get("+"), Some(&1));
+        assert_eq!(tokens.get("^"), Some(&1));
+        assert_eq!(tokens.get("="), Some(&1));
+        assert_eq!(tokens.get("{"), Some(&4));
+
+        // 公式外的内容不统计
+        assert!(latex_structure("a + b = c").is_empty());
+        assert!(latex_structure("").is_empty());
+    }
+
+    #[test]
+    fn test_latex_similarity() {
+        // 只有数字和字母不同时结构相同
+        let a = latex_structure("$\\sqrt{x^2 + 1}$");
+        let b = latex_structure("$\\sqrt{y^3 + 5}$");
+        assert_eq!(latex_similarity(&a, &b), 1.0);
+
+        let c = latex_structure("$\\sin x \\cdot \\cos x$");
+        assert_eq!(latex_similarity(&a, &c), 0.0);
+
+        // 共同部分取较小次数, {+: 1, =: 1} 和 {+: 2}, 1 / 3
+        let d = latex_structure("$a + b = c$");
+        let e = latex_structure("$a + b + c$");
+        assert!((latex_similarity(&d, &e) - 1.0 / 3.0).abs() < 1e-9);
+
+        let empty = latex_structure("没有公式");
+        assert_eq!(latex_similarity(&empty, &empty), 0.0);
+        assert_eq!(latex_similarity(&a, &empty), 0.0);
+    }
+
+    #[test]
+    fn test_set_similarity() {
+        let a: HashSet<i32> = [1, 2, 3].into();
+        let b: HashSet<i32> = [2, 3, 4].into();
+        let c: HashSet<i32> = [5, 6].into();
+        let empty: HashSet<i32> = HashSet::new();
+
+        assert_eq!(set_similarity(&a, &a), 1.0);
+        assert_eq!(set_similarity(&a, &b), 0.5);
+        assert_eq!(set_similarity(&a, &c), 0.0);
+        assert_eq!(set_similarity(&a, &empty), 0.0);
+        assert_eq!(set_similarity(&empty, &empty), 0.0);
+    }
+}
@@ -59,19 +59,23 @@ fn generate_safe_filename(original_name: &str) -> String {
 }
 
 /// 保存文件
-async fn save_file(field: &mut actix_multipart::Field, file_path: &str) -> Result<usize, Error> {
+async fn save_file(
+    field: &mut actix_multipart::Field,
+    file_path: &str,
+    max_size: usize,
+) -> Result<usize, Error> {
     let mut file = tokio::fs::File::create(file_path).await?;
     let mut file_size = 0;
 
     while let Some(chunk) = field.next().await {
         match chunk {
             Ok(chunk) => {
-                if file_size + chunk.len() > meta::MAX_IMAGE_SIZE {
+                if file_size + chunk.len() > max_size {
                     // 删除已创建的文件
                     let _ = tokio::fs::remove_file(file_path).await;
                     return Err(Error::new(
                         ErrorKind::FileTooLarge,
-                        format!("文件大小超过限制: {}MB", meta::MAX_IMAGE_SIZE / 1024 / 1024),
+                        format!("文件大小超过限制: {}MB", max_size / 1024 / 1024),
                     ));
                 }
 
@@ -122,7 +126,12 @@ pub async fn upload_file(
     let file_path = format!("{}/{}", upload_path, safe_filename);
 
     // 保存
-    let file_size = save_file(&mut field, &file_path).await?;
+    let max_size = if *is_image {
+        meta::MAX_IMAGE_SIZE
+    } else {
+        meta::MAX_FILE_SIZE
+    };
+    let file_size = save_file(&mut field, &file_path, max_size).await?;
 
     Ok(UploadFileResp {
         original_name: original_filename,
@@ -131,3 +140,29 @@ pub async fn upload_file(
         url: safe_filename,
     })
 }
+
+// 保存导入文件中的图片, 和上传图片使用相同的校验和命名方式, 返回存储后的名称
+pub async fn save_image(
+    meta_path: &str,
+    original_name: &str,
+    content: &[u8],
+) -> Result<String, Error> {
+    validate_file_type(original_name, true)?;
+    if content.len() > meta::MAX_IMAGE_SIZE {
+        return Err(Error::new(
+            ErrorKind::FileTooLarge,
+            format!(
+                "图片 {} 大小超过限制: {}MB",
+                original_name,
+                meta::MAX_IMAGE_SIZE / 1024 / 1024
+            ),
+        ));
+    }
+
+    let upload_path = format!("{}/{}", meta_path, meta::IMAGE_NAME);
+    tokio::fs::create_dir_all(&upload_path).await?;
+    let safe_filename = generate_safe_filename(original_name);
+    tokio::fs::write(format!("{}/{}", upload_path, safe_filename), content).await?;
+
+    Ok(safe_filename)
+}